@@ -55,7 +55,7 @@ impl Font {
 
         writeln!(
             file,
-            "pub const CHARACTER_VERTICES : [(char, &'static [f32]);{}] = [",
+            "pub const CHARACTER_VERTICES : [(char, &[f32]);{}] = [",
             self.characters.len()
         )
         .unwrap();
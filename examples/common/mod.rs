@@ -3,7 +3,9 @@
 use glume::gl;
 
 use glenda::renderers::{
+    FrameClock,
     Renderer,
+    ResourceCache,
     Viewport,
 };
 
@@ -23,17 +25,26 @@ pub fn run_example<A: Application + 'static>() -> Result<(), Box<dyn std::error:
     }
 
     let mut app = A::new()?;
+    let mut clock = FrameClock::new();
+    let mut resources = ResourceCache::new();
+    let mut window_viewport = Viewport::default();
 
     window.run(move |wc, event| {
         use glume::window::Event;
         match event {
             Event::Resized(width, height) => {
-                let viewport = Viewport::from([width as i32, height as i32]);
-                app.set_viewport(viewport);
+                window_viewport = Viewport::from([width as i32, height as i32]);
+                app.set_viewport(window_viewport);
+            }
+
+            Event::Tick(_) => {
+                wc.request_redraw();
             }
 
             Event::RedrawRequested => {
-                app.render();
+                let mut ctx = clock.next_frame(window_viewport, 1.0, &mut resources);
+                app.update(&mut ctx);
+                app.render_with_context(&ctx);
             }
 
             Event::KeyPressed(key) => {
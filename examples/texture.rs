@@ -1,4 +1,4 @@
-/// Example demonstrating the TextureRenderer
+//! Example demonstrating the TextureRenderer
 
 use glume::gl;
 use gl::types::*;
@@ -1,4 +1,4 @@
-/// Example demonstrating the TilemapRenderer
+//! Example demonstrating the TilemapRenderer
 
 use glume::gl;
 use gl::types::*;
@@ -1,4 +1,7 @@
 pub mod renderers;
+pub mod shader;
+pub mod sprite_animation;
+pub mod texture_atlas;
 
 pub use gl;
 
@@ -0,0 +1,336 @@
+use std::f32::consts::PI;
+
+/// Standard easing curves, mapping linear progress in `[0, 1]` to eased progress.
+/// See <https://easings.net> for plots of each curve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Easing {
+    #[default]
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+    ExpoIn,
+    ExpoOut,
+    ExpoInOut,
+    BackIn,
+    BackOut,
+    BackInOut,
+    ElasticIn,
+    ElasticOut,
+    ElasticInOut,
+    BounceIn,
+    BounceOut,
+    BounceInOut,
+}
+
+impl Easing {
+    /// Applies the curve to `t`, which is clamped to `[0, 1]` first.
+    /// The `Back` and `Elastic` curves overshoot, so the result can leave that range.
+    pub fn apply(&self, t: f32) -> f32 {
+        const BACK_C1: f32 = 1.70158;
+        const BACK_C2: f32 = BACK_C1 * 1.525;
+        const BACK_C3: f32 = BACK_C1 + 1.0;
+        const ELASTIC_C4: f32 = (2.0 * PI) / 3.0;
+        const ELASTIC_C5: f32 = (2.0 * PI) / 4.5;
+
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::QuadInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+                }
+            }
+
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+
+            Easing::SineIn => 1.0 - (t * PI / 2.0).cos(),
+            Easing::SineOut => (t * PI / 2.0).sin(),
+            Easing::SineInOut => -((PI * t).cos() - 1.0) / 2.0,
+
+            Easing::ExpoIn => {
+                if t == 0.0 { 0.0 } else { 2f32.powf(10.0 * t - 10.0) }
+            }
+            Easing::ExpoOut => {
+                if t == 1.0 { 1.0 } else { 1.0 - 2f32.powf(-10.0 * t) }
+            }
+            Easing::ExpoInOut => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else if t < 0.5 {
+                    2f32.powf(20.0 * t - 10.0) / 2.0
+                } else {
+                    (2.0 - 2f32.powf(-20.0 * t + 10.0)) / 2.0
+                }
+            }
+
+            Easing::BackIn => BACK_C3 * t * t * t - BACK_C1 * t * t,
+            Easing::BackOut => {
+                1.0 + BACK_C3 * (t - 1.0).powi(3) + BACK_C1 * (t - 1.0).powi(2)
+            }
+            Easing::BackInOut => {
+                if t < 0.5 {
+                    ((2.0 * t).powi(2) * ((BACK_C2 + 1.0) * 2.0 * t - BACK_C2)) / 2.0
+                } else {
+                    ((2.0 * t - 2.0).powi(2) * ((BACK_C2 + 1.0) * (t * 2.0 - 2.0) + BACK_C2) + 2.0) / 2.0
+                }
+            }
+
+            Easing::ElasticIn => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else {
+                    -(2f32.powf(10.0 * t - 10.0)) * ((t * 10.0 - 10.75) * ELASTIC_C4).sin()
+                }
+            }
+            Easing::ElasticOut => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else {
+                    2f32.powf(-10.0 * t) * ((t * 10.0 - 0.75) * ELASTIC_C4).sin() + 1.0
+                }
+            }
+            Easing::ElasticInOut => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else if t < 0.5 {
+                    -(2f32.powf(20.0 * t - 10.0) * ((20.0 * t - 11.125) * ELASTIC_C5).sin()) / 2.0
+                } else {
+                    (2f32.powf(-20.0 * t + 10.0) * ((20.0 * t - 11.125) * ELASTIC_C5).sin()) / 2.0 + 1.0
+                }
+            }
+
+            Easing::BounceIn => 1.0 - bounce_out(1.0 - t),
+            Easing::BounceOut => bounce_out(t),
+            Easing::BounceInOut => {
+                if t < 0.5 {
+                    (1.0 - bounce_out(1.0 - 2.0 * t)) / 2.0
+                } else {
+                    (1.0 + bounce_out(2.0 * t - 1.0)) / 2.0
+                }
+            }
+        }
+    }
+}
+
+fn bounce_out(t: f32) -> f32 {
+    const N1: f32 = 7.5625;
+    const D1: f32 = 2.75;
+
+    if t < 1.0 / D1 {
+        N1 * t * t
+    } else if t < 2.0 / D1 {
+        let t = t - 1.5 / D1;
+        N1 * t * t + 0.75
+    } else if t < 2.5 / D1 {
+        let t = t - 2.25 / D1;
+        N1 * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D1;
+        N1 * t * t + 0.984375
+    }
+}
+
+/// Linear interpolation between `a` and `b`.
+pub fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// A transition from one value to another over a fixed duration.
+/// The tween only tracks time; interpolating the values is up to the owner,
+/// since some values (like split points) can only be interpolated once resolved
+/// against a viewport.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tween<T> {
+    pub from: T,
+    pub to: T,
+
+    /// Duration in seconds.
+    pub duration: f32,
+
+    /// Time elapsed since the start, in seconds.
+    pub elapsed: f32,
+    pub easing: Easing,
+}
+
+impl<T> Tween<T> {
+    pub fn new(from: T, to: T, duration: f32, easing: Easing) -> Self {
+        Self {
+            from,
+            to,
+            duration,
+            elapsed: 0.0,
+            easing,
+        }
+    }
+
+    /// Advances the tween by `dt` seconds.
+    pub fn advance(&mut self, dt: f32) {
+        self.elapsed = (self.elapsed + dt).min(self.duration.max(0.0));
+    }
+
+    /// Eased progress of the tween, 0.0 at the start and 1.0 at the end.
+    pub fn progress(&self) -> f32 {
+        if self.duration <= 0.0 {
+            return 1.0;
+        }
+        self.easing.apply(self.elapsed / self.duration)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+impl Tween<f32> {
+    /// The current interpolated value.
+    pub fn value(&self) -> f32 {
+        lerp(self.from, self.to, self.progress())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Easing; 22] = [
+        Easing::Linear,
+        Easing::QuadIn,
+        Easing::QuadOut,
+        Easing::QuadInOut,
+        Easing::CubicIn,
+        Easing::CubicOut,
+        Easing::CubicInOut,
+        Easing::SineIn,
+        Easing::SineOut,
+        Easing::SineInOut,
+        Easing::ExpoIn,
+        Easing::ExpoOut,
+        Easing::ExpoInOut,
+        Easing::BackIn,
+        Easing::BackOut,
+        Easing::BackInOut,
+        Easing::ElasticIn,
+        Easing::ElasticOut,
+        Easing::ElasticInOut,
+        Easing::BounceIn,
+        Easing::BounceOut,
+        Easing::BounceInOut,
+    ];
+
+    /// The curves that stay within `[0, 1]` and never go back.
+    const MONOTONIC: [Easing; 13] = [
+        Easing::Linear,
+        Easing::QuadIn,
+        Easing::QuadOut,
+        Easing::QuadInOut,
+        Easing::CubicIn,
+        Easing::CubicOut,
+        Easing::CubicInOut,
+        Easing::SineIn,
+        Easing::SineOut,
+        Easing::SineInOut,
+        Easing::ExpoIn,
+        Easing::ExpoOut,
+        Easing::ExpoInOut,
+    ];
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-3, "{} is not close to {}", actual, expected);
+    }
+
+    #[test]
+    fn easing_endpoints() {
+        for easing in ALL {
+            assert_close(easing.apply(0.0), 0.0);
+            assert_close(easing.apply(1.0), 1.0);
+        }
+    }
+
+    #[test]
+    fn easing_clamps_input() {
+        for easing in ALL {
+            assert_eq!(easing.apply(-1.0), easing.apply(0.0), "{:?}", easing);
+            assert_eq!(easing.apply(2.0), easing.apply(1.0), "{:?}", easing);
+        }
+    }
+
+    #[test]
+    fn easing_monotonic() {
+        for easing in MONOTONIC {
+            let mut previous = easing.apply(0.0);
+            for i in 1..=100 {
+                let value = easing.apply(i as f32 / 100.0);
+                assert!(value >= previous - 1e-6, "{:?} decreases at {}", easing, i);
+                assert!((0.0..=1.0 + 1e-6).contains(&value), "{:?} leaves [0, 1] at {}", easing, i);
+                previous = value;
+            }
+        }
+    }
+
+    #[test]
+    fn easing_in_out_is_symmetric_at_midpoint() {
+        for easing in [Easing::QuadInOut, Easing::CubicInOut, Easing::SineInOut, Easing::ExpoInOut] {
+            assert_close(easing.apply(0.5), 0.5);
+        }
+    }
+
+    #[test]
+    fn tween_advances_and_finishes() {
+        let mut tween = Tween::new(0.0, 10.0, 2.0, Easing::Linear);
+        assert_eq!(tween.value(), 0.0);
+        assert!(!tween.is_finished());
+
+        tween.advance(0.5);
+        assert_close(tween.value(), 2.5);
+        assert!(!tween.is_finished());
+
+        tween.advance(1.5);
+        assert_close(tween.value(), 10.0);
+        assert!(tween.is_finished());
+    }
+
+    #[test]
+    fn tween_clamps_elapsed_to_duration() {
+        let mut tween = Tween::new(5.0, 1.0, 1.0, Easing::QuadOut);
+        tween.advance(10.0);
+
+        assert_eq!(tween.elapsed, 1.0);
+        assert_eq!(tween.progress(), 1.0);
+        assert_eq!(tween.value(), 1.0);
+        assert!(tween.is_finished());
+    }
+
+    #[test]
+    fn tween_with_zero_duration_is_finished() {
+        let tween = Tween::new(0.0, 3.0, 0.0, Easing::CubicIn);
+        assert!(tween.is_finished());
+        assert_eq!(tween.value(), 3.0);
+    }
+
+    #[test]
+    fn tween_uses_easing() {
+        let mut tween = Tween::new(0.0, 1.0, 1.0, Easing::QuadIn);
+        tween.advance(0.5);
+        assert_close(tween.progress(), 0.25);
+    }
+}
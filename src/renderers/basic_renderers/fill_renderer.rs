@@ -0,0 +1,260 @@
+use crate::renderers::{Renderer, Viewport};
+use crate::shader::ShaderProgram;
+
+use crate::Error;
+
+const MAX_GRADIENT_STOPS: usize = 8;
+
+const VCODE: &str = r#"
+#version 450 core
+const vec2 vertices[4] = vec2[4](
+    vec2(-1.0, -1.0),
+    vec2(-1.0, 1.0),
+    vec2(1.0, 1.0),
+    vec2(1.0, -1.0)
+);
+
+void main() {
+    gl_Position = vec4(vertices[gl_VertexID], 0.0, 1.0);
+}
+"#;
+
+const FCODE: &str = r#"
+#version 450 core
+out vec4 fColor;
+layout (location = 1) out vec4 fNormal;
+
+const int MODE_SOLID = 0;
+const int MODE_LINEAR = 1;
+const int MODE_RADIAL = 2;
+const int MODE_CHECKERBOARD = 3;
+const int MODE_STRIPES = 4;
+
+uniform int u_mode;
+uniform vec2 u_viewport_pos;
+uniform vec2 u_viewport_size;
+
+// Gradient stops; solid fills and patterns use the first one or two colors.
+uniform vec4 u_colors[8];
+uniform float u_offsets[8];
+uniform int u_stop_count;
+
+// Start and end of a linear gradient, or center and radius of a radial one.
+uniform vec2 u_p0;
+uniform vec2 u_p1;
+
+// Cell size or stripe width in pixels, and stripe angle.
+uniform float u_pattern_size;
+uniform float u_pattern_angle;
+
+vec4 gradient(float t) {
+    if (t <= u_offsets[0]) {
+        return u_colors[0];
+    }
+    for (int i = 1; i < u_stop_count; i++) {
+        if (t <= u_offsets[i]) {
+            float span = max(u_offsets[i] - u_offsets[i - 1], 1e-6);
+            return mix(u_colors[i - 1], u_colors[i], (t - u_offsets[i - 1]) / span);
+        }
+    }
+    return u_colors[u_stop_count - 1];
+}
+
+void main() {
+    vec2 pixel = gl_FragCoord.xy - u_viewport_pos;
+    vec2 rel = pixel / u_viewport_size;
+
+    if (u_mode == MODE_LINEAR) {
+        vec2 dir = u_p1 - u_p0;
+        fColor = gradient(dot(rel - u_p0, dir) / max(dot(dir, dir), 1e-12));
+    } else if (u_mode == MODE_RADIAL) {
+        float scale = min(u_viewport_size.x, u_viewport_size.y);
+        float dist = length((rel - u_p0) * u_viewport_size) / max(u_p1.x * scale, 1e-6);
+        fColor = gradient(dist);
+    } else if (u_mode == MODE_CHECKERBOARD) {
+        ivec2 cell = ivec2(floor(pixel / u_pattern_size));
+        fColor = u_colors[(cell.x + cell.y) & 1];
+    } else if (u_mode == MODE_STRIPES) {
+        float across = dot(pixel, vec2(cos(u_pattern_angle), sin(u_pattern_angle)));
+        fColor = u_colors[int(floor(across / u_pattern_size)) & 1];
+    } else {
+        fColor = u_colors[0];
+    }
+
+    // A flat surface, for the scene of a lighting renderer with normal mapping.
+    fNormal = vec4(0.5, 0.5, 1.0, fColor.a);
+}
+"#;
+
+/// A color at a position along a gradient, from 0.0 at its start to 1.0 at its end.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GradientStop {
+    pub offset: f32,
+    pub color: [f32; 4],
+}
+
+impl GradientStop {
+    pub fn new(offset: f32, color: [f32; 4]) -> Self {
+        Self { offset, color }
+    }
+}
+
+/// What a [`FillRenderer`] fills its viewport with.
+///
+/// Positions are relative to the viewport, from `[0.0, 0.0]` at the bottom-left corner to
+/// `[1.0, 1.0]` at the top-right, so gradients follow the viewport as the layout changes.
+/// Pattern sizes are in pixels, so patterns repeat more often in larger viewports rather
+/// than growing.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fill {
+    Solid([f32; 4]),
+
+    /// Colors change along the line from `start` to `end`, and stay constant across it.
+    /// Before the first stop and after the last one, the nearest stop's color is used.
+    LinearGradient {
+        start: [f32; 2],
+        end: [f32; 2],
+        stops: Vec<GradientStop>,
+    },
+
+    /// Colors change with the distance from `center`. `radius` is relative to the
+    /// smaller side of the viewport, so the gradient stays circular.
+    RadialGradient {
+        center: [f32; 2],
+        radius: f32,
+        stops: Vec<GradientStop>,
+    },
+
+    /// Alternating square cells, starting with `colors[0]` at the bottom-left corner.
+    /// Useful behind transparent images.
+    Checkerboard {
+        cell_size: f32,
+        colors: [[f32; 4]; 2],
+    },
+
+    /// Alternating stripes of the given width. An `angle` of 0.0 gives vertical stripes,
+    /// and larger angles rotate them counter-clockwise, in radians.
+    Stripes {
+        width: f32,
+        angle: f32,
+        colors: [[f32; 4]; 2],
+    },
+}
+
+impl Fill {
+    /// A gradient from one color to another.
+    pub fn linear(start: [f32; 2], end: [f32; 2], from: [f32; 4], to: [f32; 4]) -> Self {
+        Fill::LinearGradient {
+            start,
+            end,
+            stops: vec![GradientStop::new(0.0, from), GradientStop::new(1.0, to)],
+        }
+    }
+
+    /// A gradient from one color at the center to another at the radius.
+    pub fn radial(center: [f32; 2], radius: f32, inner: [f32; 4], outer: [f32; 4]) -> Self {
+        Fill::RadialGradient {
+            center,
+            radius,
+            stops: vec![GradientStop::new(0.0, inner), GradientStop::new(1.0, outer)],
+        }
+    }
+
+    /// The light and dark gray checkerboard commonly shown behind transparent images.
+    pub fn transparency_checkerboard() -> Self {
+        Fill::Checkerboard {
+            cell_size: 8.0,
+            colors: [[0.8, 0.8, 0.8, 1.0], [0.6, 0.6, 0.6, 1.0]],
+        }
+    }
+}
+
+/// Fills its viewport with a solid color, a gradient or a pattern.
+pub struct FillRenderer {
+    viewport: Viewport,
+    program: ShaderProgram,
+    fill: Fill,
+}
+
+impl FillRenderer {
+    pub fn new(fill: Fill) -> Result<Self, Error> {
+        let program = ShaderProgram::new(VCODE, FCODE)?;
+
+        let mut _self = Self {
+            viewport: Viewport::default(),
+            program,
+            fill: Fill::Solid([0.0, 0.0, 0.0, 0.0]),
+        };
+
+        _self.set_fill(fill)?;
+
+        Ok(_self)
+    }
+
+    pub fn fill(&self) -> &Fill {
+        &self.fill
+    }
+
+    /// Fails if a gradient has no stops, more than 8, or stops out of order,
+    /// or if a pattern size isn't positive. The previous fill is kept in that case.
+    pub fn set_fill(&mut self, fill: Fill) -> Result<(), Error> {
+        let (mode, stops, p0, p1, pattern_size, pattern_angle) = match &fill {
+            Fill::Solid(color) => (0, vec![GradientStop::new(0.0, *color)], [0.0; 2], [0.0; 2], 1.0, 0.0),
+            Fill::LinearGradient { start, end, stops } => (1, stops.clone(), *start, *end, 1.0, 0.0),
+            Fill::RadialGradient { center, radius, stops } => (2, stops.clone(), *center, [*radius, 0.0], 1.0, 0.0),
+            Fill::Checkerboard { cell_size, colors } => (3, pattern_stops(colors), [0.0; 2], [0.0; 2], *cell_size, 0.0),
+            Fill::Stripes { width, angle, colors } => (4, pattern_stops(colors), [0.0; 2], [0.0; 2], *width, *angle),
+        };
+
+        if stops.is_empty() || stops.len() > MAX_GRADIENT_STOPS {
+            return Err(format!(
+                "FillRenderer::set_fill: Gradients need between 1 and {} stops, got {}",
+                MAX_GRADIENT_STOPS, stops.len()
+            ).into());
+        }
+
+        if stops.windows(2).any(|pair| pair[1].offset < pair[0].offset) {
+            return Err("FillRenderer::set_fill: Gradient stops must be in increasing order of offset".into());
+        }
+
+        if pattern_size <= 0.0 {
+            return Err(format!("FillRenderer::set_fill: Pattern size must be positive, got {}", pattern_size).into());
+        }
+
+        let colors: Vec<[f32; 4]> = stops.iter().map(|stop| stop.color).collect();
+        let offsets: Vec<f32> = stops.iter().map(|stop| stop.offset).collect();
+
+        let program = &self.program;
+        program.set_builtin("u_mode", mode);
+        program.set_builtin("u_colors", colors.as_slice());
+        program.set_builtin("u_offsets", offsets.as_slice());
+        program.set_builtin("u_stop_count", stops.len() as i32);
+        program.set_builtin("u_p0", p0);
+        program.set_builtin("u_p1", p1);
+        program.set_builtin("u_pattern_size", pattern_size);
+        program.set_builtin("u_pattern_angle", pattern_angle);
+
+        self.fill = fill;
+        Ok(())
+    }
+}
+
+fn pattern_stops(colors: &[[f32; 4]; 2]) -> Vec<GradientStop> {
+    vec![GradientStop::new(0.0, colors[0]), GradientStop::new(1.0, colors[1])]
+}
+
+impl Renderer for FillRenderer {
+    fn set_viewport(&mut self, viewport: Viewport) {
+        self.viewport = viewport;
+        self.program.set_builtin("u_viewport_pos", [viewport.pos[0] as f32, viewport.pos[1] as f32]);
+        self.program.set_builtin("u_viewport_size", [viewport.size[0] as f32, viewport.size[1] as f32]);
+    }
+
+    fn render(&self) {
+        self.viewport.gl_viewport();
+        self.program.use_program();
+        unsafe {
+            gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
+        }
+    }
+}
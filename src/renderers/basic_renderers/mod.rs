@@ -1,9 +1,20 @@
 
-use crate::renderers::{Renderer, Viewport};
+use crate::renderers::{render_child, FrameContext, Renderer, Viewport};
+use crate::renderers::animation::{lerp, Easing, Tween};
+use gl::types::*;
+pub use crate::renderers::viewport::SplitPoint;
+use crate::Error;
+use crate::gl;
+
+mod fill_renderer;
+pub use fill_renderer::{Fill, FillRenderer, GradientStop};
 
 mod mono_color_renderer;
 pub use mono_color_renderer::MonoColorRenderer;
 
+mod rounded_rect;
+use rounded_rect::RoundedRectProgram;
+
 /// A renderer that does nothing.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct NullRenderer;
@@ -13,36 +24,11 @@ impl Renderer for NullRenderer {
     fn render(&self) {}
 }
 
-/// Defines the split point of a split renderer.
-/// Use a negative value to specify a split point relative to the far edge of the viewport.
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum SplitPoint {
-    /// Absolute split point in pixels.
-    Absolute(i32),
-
-    /// Ratio of the viewport size (0.0 to 1.0).
-    Ratio(f32),
-}
-
-impl SplitPoint {
-    pub fn to_absolute(&self, viewport_size: i32) -> i32 {
-        let mut sp = match self {
-            SplitPoint::Absolute(x) => *x,
-            SplitPoint::Ratio(r) => (viewport_size as f32 * r) as i32,
-        };
-
-        if sp < 0 {
-            sp = viewport_size + sp
-        }
-
-        sp.clamp(0, viewport_size)
-    }
-}
-
 struct SplitRenderer<R1: Renderer, R2: Renderer> {
     viewport: Viewport,
     horizontal: bool,
     split_point: SplitPoint,
+    split_animation: Option<Tween<SplitPoint>>,
     r1: R1,
     r2: R2,
 }
@@ -53,6 +39,7 @@ impl<R1: Renderer, R2: Renderer> SplitRenderer<R1, R2> {
             viewport: Viewport::default(),
             horizontal,
             split_point,
+            split_animation: None,
             r1,
             r2,
         };
@@ -79,34 +66,49 @@ impl<R1: Renderer, R2: Renderer> SplitRenderer<R1, R2> {
 
     pub fn set_split_point(&mut self, split_point: SplitPoint) {
         self.split_point = split_point;
+        self.split_animation = None;
         self.reset_subrenderer_viewports();
     }
 
+    pub fn animate_split_point(&mut self, target: SplitPoint, duration: f32, easing: Easing) {
+        let from = match self.split_animation {
+            Some(_) => SplitPoint::Absolute(self.current_split_point()),
+            None => self.split_point,
+        };
+
+        self.split_point = target;
+        self.split_animation = Some(Tween::new(from, target, duration, easing));
+        self.reset_subrenderer_viewports();
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.split_animation.is_some()
+    }
+
+    /// The split point in pixels, taking any running animation into account.
+    fn current_split_point(&self) -> i32 {
+        let size = if self.horizontal {
+            self.viewport.size[0]
+        } else {
+            self.viewport.size[1]
+        };
+
+        match self.split_animation {
+            Some(ref tween) => {
+                let from = tween.from.to_absolute(size) as f32;
+                let to = tween.to.to_absolute(size) as f32;
+                (lerp(from, to, tween.progress()).round() as i32).clamp(0, size)
+            }
+            None => self.split_point.to_absolute(size),
+        }
+    }
+
     fn reset_subrenderer_viewports(&mut self) {
+        let sp = SplitPoint::Absolute(self.current_split_point());
         let (r1v, r2v) = if self.horizontal {
-            let sp = self.split_point.to_absolute(self.viewport.size[0]);
-            let r1v = Viewport {
-                pos: self.viewport.pos,
-                size: [sp, self.viewport.size[1]],
-            };
-            let r2v = Viewport {
-                pos: [self.viewport.pos[0] + sp, self.viewport.pos[1]],
-                size: [self.viewport.size[0] - sp, self.viewport.size[1]],
-            };
-
-            (r1v, r2v)
+            self.viewport.split_horizontal(sp)
         } else {
-            let sp = self.split_point.to_absolute(self.viewport.size[1]);
-            let r1v = Viewport {
-                pos: self.viewport.pos,
-                size: [self.viewport.size[0], sp],
-            };
-            let r2v = Viewport {
-                pos: [self.viewport.pos[0], self.viewport.pos[1] + sp],
-                size: [self.viewport.size[0], self.viewport.size[1] - sp],
-            };
-
-            (r1v, r2v)
+            self.viewport.split_vertical(sp)
         };
 
         self.r1.set_viewport(r1v);
@@ -124,6 +126,24 @@ impl<R1: Renderer, R2: Renderer> Renderer for SplitRenderer<R1, R2> {
         self.r1.render();
         self.r2.render();
     }
+
+    fn update(&mut self, ctx: &mut FrameContext) {
+        if let Some(ref mut tween) = self.split_animation {
+            tween.advance(ctx.delta_time);
+            if tween.is_finished() {
+                self.split_animation = None;
+            }
+            self.reset_subrenderer_viewports();
+        }
+
+        self.r1.update(ctx);
+        self.r2.update(ctx);
+    }
+
+    fn render_with_context(&self, ctx: &FrameContext) {
+        self.r1.render_with_context(ctx);
+        self.r2.render_with_context(ctx);
+    }
 }
 
 /// Splits the viewport between a left and right renderer.
@@ -156,6 +176,15 @@ impl<Left: Renderer, Right: Renderer> HSplitRenderer<Left, Right> {
     pub fn set_split_point(&mut self, split_point: SplitPoint) {
         self.split_renderer.set_split_point(split_point);
     }
+
+    /// Moves the split point to `target` over `duration` seconds, driven by [`Renderer::update`].
+    pub fn animate_split_point(&mut self, target: SplitPoint, duration: f32, easing: Easing) {
+        self.split_renderer.animate_split_point(target, duration, easing);
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.split_renderer.is_animating()
+    }
 }
 
 impl<Left: Renderer, Right: Renderer> Renderer for HSplitRenderer<Left, Right> {
@@ -166,6 +195,14 @@ impl<Left: Renderer, Right: Renderer> Renderer for HSplitRenderer<Left, Right> {
     fn render(&self) {
         self.split_renderer.render();
     }
+
+    fn update(&mut self, ctx: &mut FrameContext) {
+        self.split_renderer.update(ctx);
+    }
+
+    fn render_with_context(&self, ctx: &FrameContext) {
+        self.split_renderer.render_with_context(ctx);
+    }
 }
 
 /// Splits the viewport between a top and bottom renderer.
@@ -198,6 +235,15 @@ impl<Top: Renderer, Bottom: Renderer> VSplitRenderer<Top, Bottom> {
     pub fn set_split_point(&mut self, split_point: SplitPoint) {
         self.split_renderer.set_split_point(split_point);
     }
+
+    /// Moves the split point to `target` over `duration` seconds, driven by [`Renderer::update`].
+    pub fn animate_split_point(&mut self, target: SplitPoint, duration: f32, easing: Easing) {
+        self.split_renderer.animate_split_point(target, duration, easing);
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.split_renderer.is_animating()
+    }
 }
 
 impl<Top: Renderer, Bottom: Renderer> Renderer for VSplitRenderer<Top, Bottom> {
@@ -208,22 +254,135 @@ impl<Top: Renderer, Bottom: Renderer> Renderer for VSplitRenderer<Top, Bottom> {
     fn render(&self) {
         self.split_renderer.render();
     }
+
+    fn update(&mut self, ctx: &mut FrameContext) {
+        self.split_renderer.update(ctx);
+    }
+
+    fn render_with_context(&self, ctx: &FrameContext) {
+        self.split_renderer.render_with_context(ctx);
+    }
+}
+
+/// Per-side distances used by [`InsetRenderer`].
+/// Each side is resolved with [`SplitPoint::to_absolute`], against the viewport
+/// width for `left` and `right`, and against the viewport height for `top` and `bottom`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Insets {
+    pub left: SplitPoint,
+    pub right: SplitPoint,
+    pub top: SplitPoint,
+    pub bottom: SplitPoint,
+}
+
+impl Insets {
+    pub fn new(left: SplitPoint, right: SplitPoint, top: SplitPoint, bottom: SplitPoint) -> Self {
+        Self { left, right, top, bottom }
+    }
+
+    /// The same inset on all four sides.
+    pub fn uniform(inset: SplitPoint) -> Self {
+        Self::new(inset, inset, inset, inset)
+    }
+
+    /// One inset for the left and right sides, another for the top and bottom.
+    pub fn symmetric(horizontal: SplitPoint, vertical: SplitPoint) -> Self {
+        Self::new(horizontal, horizontal, vertical, vertical)
+    }
+
+    /// The same absolute inset, in pixels, on all four sides.
+    pub fn absolute(inset: i32) -> Self {
+        Self::uniform(SplitPoint::Absolute(inset))
+    }
+
+    /// The same inset on all four sides, as a ratio of the viewport size.
+    pub fn ratio(ratio: f32) -> Self {
+        Self::uniform(SplitPoint::Ratio(ratio))
+    }
+
+    /// Resolves each side to pixels for a viewport of the given size,
+    /// in the order left, right, top, bottom.
+    pub fn to_absolute(&self, viewport_size: [i32; 2]) -> [i32; 4] {
+        [
+            self.left.to_absolute(viewport_size[0]),
+            self.right.to_absolute(viewport_size[0]),
+            self.top.to_absolute(viewport_size[1]),
+            self.bottom.to_absolute(viewport_size[1]),
+        ]
+    }
+
+    /// Applies the insets to a viewport.
+    /// If the insets are larger than the viewport, the result has a size of zero.
+    pub fn apply(&self, viewport: Viewport) -> Viewport {
+        let [left, right, top, bottom] = self.to_absolute(viewport.size);
+        viewport.inset(left, right, top, bottom)
+    }
+}
+
+impl Default for Insets {
+    fn default() -> Self {
+        Self::absolute(0)
+    }
+}
+
+impl From<i32> for Insets {
+    fn from(inset: i32) -> Self {
+        Self::absolute(inset)
+    }
+}
+
+impl From<SplitPoint> for Insets {
+    fn from(inset: SplitPoint) -> Self {
+        Self::uniform(inset)
+    }
+}
+
+/// A solid frame drawn along the edge of the outer renderer of an [`InsetRenderer`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Border {
+    pub color: [f32; 4],
+
+    /// Thickness of the border in pixels.
+    pub thickness: i32,
+}
+
+impl Border {
+    pub fn new(color: [f32; 4], thickness: i32) -> Self {
+        Self { color, thickness }
+    }
 }
 
 /// Renders one renderer inside another, with a specified inset.
-/// The inset is the distance from the edge of the viewport to the edge of the inner renderer.
+/// The inset is the distance from the edge of the outer renderer to the edge of the inner renderer.
+///
+/// The outer renderer can itself be pulled in from the edge of the viewport with a margin,
+/// framed with a [`Border`], and clipped to rounded corners. Rounded corners use the stencil
+/// buffer, so the framebuffer needs one for them to have any effect. Inside another stencil
+/// clip, such as another rounded `InsetRenderer`, they stay within that clip as well.
 pub struct InsetRenderer<Outer: Renderer, Inner: Renderer> {
     viewport: Viewport,
-    inset: i32,
+    outer_viewport: Viewport,
+    inset: Insets,
+    inset_animation: Option<Tween<Insets>>,
+    margin: Insets,
+    border: Option<Border>,
+    corner_radius: i32,
+    frame_program: Option<RoundedRectProgram>,
     outer: Outer,
     inner: Inner,
 }
 
 impl<Outer: Renderer, Inner: Renderer> InsetRenderer<Outer, Inner> {
-    pub fn new(inset: i32, outer: Outer, inner: Inner) -> Self {
+    pub fn new<I: Into<Insets>>(inset: I, outer: Outer, inner: Inner) -> Self {
         let mut self_ = Self {
             viewport: Viewport::default(),
-            inset,
+            outer_viewport: Viewport::default(),
+            inset: inset.into(),
+            inset_animation: None,
+            margin: Insets::default(),
+            border: None,
+            corner_radius: 0,
+            frame_program: None,
             outer,
             inner,
         };
@@ -248,30 +407,133 @@ impl<Outer: Renderer, Inner: Renderer> InsetRenderer<Outer, Inner> {
         &mut self.inner
     }
 
-    pub fn set_inset(&mut self, inset: i32) {
-        self.inset = inset;
+    pub fn set_inset<I: Into<Insets>>(&mut self, inset: I) {
+        self.inset = inset.into();
+        self.inset_animation = None;
         self.reset_subrenderer_viewports();
     }
 
-    fn reset_subrenderer_viewports(&mut self) {
-        let ix = self.viewport.pos[0] + self.inset;
-        let iy = self.viewport.pos[1] + self.inset;
-        let iw = self.viewport.size[0] - 2 * self.inset;
-        let ih = self.viewport.size[1] - 2 * self.inset;
-        let isize_ = if iw < 0 || ih < 0 {
-            [0, 0]
-        } else {
-            [iw, ih]
+    /// Moves the inset to `target` over `duration` seconds, driven by [`Renderer::update`].
+    pub fn animate_inset<I: Into<Insets>>(&mut self, target: I, duration: f32, easing: Easing) {
+        let target = target.into();
+        let from = match self.inset_animation {
+            Some(_) => self.current_inset(),
+            None => self.inset,
+        };
+
+        self.inset = target;
+        self.inset_animation = Some(Tween::new(from, target, duration, easing));
+        self.reset_subrenderer_viewports();
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.inset_animation.is_some()
+    }
+
+    /// The inset, taking any running animation into account.
+    fn current_inset(&self) -> Insets {
+        let Some(ref tween) = self.inset_animation else {
+            return self.inset;
         };
 
-        let irect = Viewport {
-            pos: [ix, iy],
-            size: isize_,
+        let size = self.outer_viewport.size;
+        let from = tween.from.to_absolute(size);
+        let to = tween.to.to_absolute(size);
+        let t = tween.progress();
+        let side = |i: usize| {
+            SplitPoint::Absolute((lerp(from[i] as f32, to[i] as f32, t).round() as i32).max(0))
         };
 
-        self.outer.set_viewport(self.viewport);
+        Insets::new(side(0), side(1), side(2), side(3))
+    }
+
+    /// Sets the distance from the edge of the viewport to the edge of the outer renderer.
+    pub fn set_margin<I: Into<Insets>>(&mut self, margin: I) {
+        self.margin = margin.into();
+        self.reset_subrenderer_viewports();
+    }
+
+    /// Sets the border drawn along the edge of the outer renderer, on top of it
+    /// and underneath the inner renderer. Use `None` to remove the border.
+    pub fn set_border(&mut self, border: Option<Border>) -> Result<(), Error> {
+        if border.is_some() {
+            self.ensure_frame_program()?;
+        }
+        self.border = border;
+        Ok(())
+    }
+
+    /// Clips the outer renderer, the border and the inner renderer to a rectangle
+    /// with corners of the given radius in pixels. Use 0 for square corners.
+    pub fn set_corner_radius(&mut self, radius: i32) -> Result<(), Error> {
+        if radius > 0 {
+            self.ensure_frame_program()?;
+        }
+        self.corner_radius = radius.max(0);
+        Ok(())
+    }
+
+    fn ensure_frame_program(&mut self) -> Result<(), Error> {
+        if self.frame_program.is_none() {
+            self.frame_program = Some(RoundedRectProgram::new()?);
+        }
+        Ok(())
+    }
+
+    fn reset_subrenderer_viewports(&mut self) {
+        self.outer_viewport = self.margin.apply(self.viewport);
+        let irect = self.current_inset().apply(self.outer_viewport);
+
+        self.outer.set_viewport(self.outer_viewport);
         self.inner.set_viewport(irect);
     }
+
+    /// Marks the rounded shape by incrementing the stencil buffer, within the clip in effect
+    /// if there is one, and restricts drawing to it. Returns the stencil state to restore.
+    fn begin_corner_clip(&self, program: &RoundedRectProgram) -> StencilState {
+        let previous = StencilState::capture();
+        let clip = previous.clip_reference();
+        let base = clip.unwrap_or(0);
+
+        unsafe {
+            gl::Enable(gl::STENCIL_TEST);
+            gl::StencilMask(0xFF);
+            gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+
+            // Without an enclosing clip, clear the square area first.
+            if clip.is_none() {
+                gl::StencilFunc(gl::ALWAYS, 0, 0xFF);
+                gl::StencilOp(gl::KEEP, gl::KEEP, gl::REPLACE);
+                program.draw(self.outer_viewport, 0, 0, [0.0; 4]);
+            }
+
+            gl::StencilFunc(gl::EQUAL, base, 0xFF);
+            gl::StencilOp(gl::KEEP, gl::KEEP, gl::INCR);
+            program.draw(self.outer_viewport, self.corner_radius, 0, [0.0; 4]);
+
+            gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+            gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
+            gl::StencilFunc(gl::EQUAL, base + 1, 0xFF);
+        }
+
+        previous
+    }
+
+    /// Undoes the increments of [`begin_corner_clip`](Self::begin_corner_clip) for an
+    /// enclosing clip, and restores the stencil state.
+    fn end_corner_clip(&self, program: &RoundedRectProgram, previous: StencilState) {
+        if let Some(base) = previous.clip_reference() {
+            unsafe {
+                gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+                gl::StencilFunc(gl::EQUAL, base + 1, 0xFF);
+                gl::StencilOp(gl::KEEP, gl::KEEP, gl::DECR);
+                program.draw(self.outer_viewport, self.corner_radius, 0, [0.0; 4]);
+                gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+            }
+        }
+
+        previous.restore();
+    }
 }
 
 impl<Outer: Renderer, Inner: Renderer> Renderer for InsetRenderer<Outer, Inner> {
@@ -281,51 +543,173 @@ impl<Outer: Renderer, Inner: Renderer> Renderer for InsetRenderer<Outer, Inner>
     }
 
     fn render(&self) {
-        self.outer.render();
-        self.inner.render();
+        self.render_parts(None);
+    }
+
+    fn update(&mut self, ctx: &mut FrameContext) {
+        if let Some(ref mut tween) = self.inset_animation {
+            tween.advance(ctx.delta_time);
+            if tween.is_finished() {
+                self.inset_animation = None;
+            }
+            self.reset_subrenderer_viewports();
+        }
+
+        self.outer.update(ctx);
+        self.inner.update(ctx);
+    }
+
+    fn render_with_context(&self, ctx: &FrameContext) {
+        self.render_parts(Some(ctx));
     }
 }
 
-pub struct FixedAspectRatioRenderer<R: Renderer> {
+impl<Outer: Renderer, Inner: Renderer> InsetRenderer<Outer, Inner> {
+    fn render_parts(&self, ctx: Option<&FrameContext>) {
+        let clip = match self.frame_program {
+            Some(ref program) if self.corner_radius > 0 => Some((program, self.begin_corner_clip(program))),
+            _ => None,
+        };
+
+        render_child(&self.outer, ctx);
+
+        if let (Some(border), Some(program)) = (self.border, &self.frame_program) {
+            program.draw(
+                self.outer_viewport,
+                self.corner_radius,
+                border.thickness.max(1),
+                border.color,
+            );
+        }
+
+        render_child(&self.inner, ctx);
+
+        if let Some((program, previous)) = clip {
+            self.end_corner_clip(program, previous);
+        }
+    }
+}
+
+/// Determines how a [`FixedAspectRatioRenderer`] sizes its subrenderer within its viewport.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalingPolicy {
+    /// The largest size with the aspect ratio that fits inside the viewport.
+    /// The leftover space is given to the bar renderer.
+    Fit,
+
+    /// The smallest size with the aspect ratio that covers the whole viewport.
+    /// Whatever falls outside of the viewport is cropped.
+    Fill,
+
+    /// Like `Fit`, but only whole multiples of the given base size in pixels are used.
+    /// The aspect ratio of the base size takes the place of the configured aspect ratio.
+    /// If the viewport is smaller than the base size, this falls back to `Fit`.
+    IntegerScale([i32; 2]),
+
+    /// The whole viewport, ignoring the aspect ratio.
+    Stretch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HorizontalAlignment {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerticalAlignment {
+    Top,
+    Center,
+    Bottom,
+}
+
+/// Keeps its subrenderer at a fixed aspect ratio within the viewport.
+///
+/// With the `Fit` and `IntegerScale` policies, the leftover letterbox or pillarbox regions
+/// are drawn by the bar renderer, which is given the full viewport and clipped to each bar.
+pub struct FixedAspectRatioRenderer<R: Renderer, B: Renderer = NullRenderer> {
     viewport: Viewport,
+    inner_viewport: Viewport,
     aspect_ratio: f32,
+    aspect_ratio_animation: Option<Tween<f32>>,
+    scaling_policy: ScalingPolicy,
+    horizontal_alignment: HorizontalAlignment,
+    vertical_alignment: VerticalAlignment,
     renderer: R,
+    bar_renderer: B,
 }
 
 impl<R: Renderer> FixedAspectRatioRenderer<R> {
     pub fn new(aspect_ratio: f32, renderer: R) -> Self {
         let mut self_ = Self {
             viewport: Viewport::default(),
+            inner_viewport: Viewport::default(),
             aspect_ratio,
+            aspect_ratio_animation: None,
+            scaling_policy: ScalingPolicy::Fit,
+            horizontal_alignment: HorizontalAlignment::Center,
+            vertical_alignment: VerticalAlignment::Center,
             renderer,
+            bar_renderer: NullRenderer,
         };
 
         self_.reset_subrenderer_viewports();
         self_
     }
+}
 
-    fn reset_subrenderer_viewports(&mut self) {
-        let viewport_size = self.viewport.size;
-        let new_width = (viewport_size[1] as f32 * self.aspect_ratio) as i32;
-        let new_height = (viewport_size[0] as f32 / self.aspect_ratio) as i32;
+impl<R: Renderer, B: Renderer> FixedAspectRatioRenderer<R, B> {
+    /// Replaces the renderer used to draw the bars around the subrenderer.
+    pub fn with_bar_renderer<B2: Renderer>(self, bar_renderer: B2) -> FixedAspectRatioRenderer<R, B2> {
+        let mut new_self = FixedAspectRatioRenderer {
+            viewport: self.viewport,
+            inner_viewport: self.inner_viewport,
+            aspect_ratio: self.aspect_ratio,
+            aspect_ratio_animation: self.aspect_ratio_animation,
+            scaling_policy: self.scaling_policy,
+            horizontal_alignment: self.horizontal_alignment,
+            vertical_alignment: self.vertical_alignment,
+            renderer: self.renderer,
+            bar_renderer,
+        };
 
-        let new_size = if new_width < viewport_size[0] {
-            [new_width, viewport_size[1]]
-        } else {
-            [viewport_size[0], new_height]
+        new_self.reset_subrenderer_viewports();
+        new_self
+    }
+
+    fn reset_subrenderer_viewports(&mut self) {
+        let aspect_ratio = self.current_aspect_ratio();
+        let new_size = match self.scaling_policy {
+            ScalingPolicy::Fit => self.viewport.aspect_fit(aspect_ratio).size,
+            ScalingPolicy::Fill => self.viewport.aspect_fill(aspect_ratio).size,
+            ScalingPolicy::IntegerScale(base_size) => {
+                integer_scale_size(self.viewport, base_size, aspect_ratio)
+            }
+            ScalingPolicy::Stretch => self.viewport.size,
         };
 
-        let new_pos = [
-            self.viewport.pos[0] + (viewport_size[0] - new_size[0]) / 2,
-            self.viewport.pos[1] + (viewport_size[1] - new_size[1]) / 2,
-        ];
+        let align_x = match self.horizontal_alignment {
+            HorizontalAlignment::Left => 0.0,
+            HorizontalAlignment::Center => 0.5,
+            HorizontalAlignment::Right => 1.0,
+        };
 
-        let new_viewport = Viewport {
-            pos: new_pos,
-            size: new_size,
+        let align_y = match self.vertical_alignment {
+            VerticalAlignment::Bottom => 0.0,
+            VerticalAlignment::Center => 0.5,
+            VerticalAlignment::Top => 1.0,
         };
 
-        self.renderer.set_viewport(new_viewport);
+        self.inner_viewport = self.viewport.place(new_size, [align_x, align_y]);
+
+        self.renderer.set_viewport(self.inner_viewport);
+        self.bar_renderer.set_viewport(self.viewport);
+    }
+
+    /// The viewport most recently given to the subrenderer.
+    pub fn inner_viewport(&self) -> Viewport {
+        self.inner_viewport
     }
 
     pub fn get_subrenderer(&self) -> &R {
@@ -336,19 +720,251 @@ impl<R: Renderer> FixedAspectRatioRenderer<R> {
         &mut self.renderer
     }
 
+    pub fn get_bar_renderer(&self) -> &B {
+        &self.bar_renderer
+    }
+
+    pub fn get_bar_renderer_mut(&mut self) -> &mut B {
+        &mut self.bar_renderer
+    }
+
     pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
         self.aspect_ratio = aspect_ratio;
+        self.aspect_ratio_animation = None;
         self.reset_subrenderer_viewports();
     }
+
+    /// Moves the aspect ratio to `target` over `duration` seconds, driven by [`Renderer::update`].
+    pub fn animate_aspect_ratio(&mut self, target: f32, duration: f32, easing: Easing) {
+        let from = self.current_aspect_ratio();
+        self.aspect_ratio = target;
+        self.aspect_ratio_animation = Some(Tween::new(from, target, duration, easing));
+        self.reset_subrenderer_viewports();
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.aspect_ratio_animation.is_some()
+    }
+
+    /// The aspect ratio, taking any running animation into account.
+    fn current_aspect_ratio(&self) -> f32 {
+        match self.aspect_ratio_animation {
+            Some(ref tween) => tween.value(),
+            None => self.aspect_ratio,
+        }
+    }
+
+    pub fn set_scaling_policy(&mut self, scaling_policy: ScalingPolicy) {
+        self.scaling_policy = scaling_policy;
+        self.reset_subrenderer_viewports();
+    }
+
+    pub fn set_alignment(&mut self, horizontal: HorizontalAlignment, vertical: VerticalAlignment) {
+        self.horizontal_alignment = horizontal;
+        self.vertical_alignment = vertical;
+        self.reset_subrenderer_viewports();
+    }
+
+    /// The parts of the viewport not covered by the subrenderer.
+    fn bar_regions(&self) -> Vec<Viewport> {
+        self.viewport.subtract(&self.inner_viewport)
+    }
 }
 
-impl<R: Renderer> Renderer for FixedAspectRatioRenderer<R> {
+impl<R: Renderer, B: Renderer> Renderer for FixedAspectRatioRenderer<R, B> {
     fn set_viewport(&mut self, viewport: Viewport) {
         self.viewport = viewport;
         self.reset_subrenderer_viewports();
     }
 
     fn render(&self) {
-        self.renderer.render();
+        self.render_parts(None);
+    }
+
+    fn update(&mut self, ctx: &mut FrameContext) {
+        if let Some(ref mut tween) = self.aspect_ratio_animation {
+            tween.advance(ctx.delta_time);
+            if tween.is_finished() {
+                self.aspect_ratio_animation = None;
+            }
+            self.reset_subrenderer_viewports();
+        }
+
+        self.renderer.update(ctx);
+        self.bar_renderer.update(ctx);
+    }
+
+    fn render_with_context(&self, ctx: &FrameContext) {
+        self.render_parts(Some(ctx));
+    }
+}
+
+impl<R: Renderer, B: Renderer> FixedAspectRatioRenderer<R, B> {
+    fn render_parts(&self, ctx: Option<&FrameContext>) {
+        for region in self.bar_regions() {
+            with_scissor(region, || render_child(&self.bar_renderer, ctx));
+        }
+
+        if self.scaling_policy == ScalingPolicy::Fill {
+            with_scissor(self.viewport, || render_child(&self.renderer, ctx));
+        } else {
+            render_child(&self.renderer, ctx);
+        }
+    }
+}
+
+fn integer_scale_size(viewport: Viewport, base_size: [i32; 2], aspect_ratio: f32) -> [i32; 2] {
+    if base_size[0] <= 0 || base_size[1] <= 0 {
+        return viewport.aspect_fit(aspect_ratio).size;
+    }
+
+    let scale = (viewport.size[0] / base_size[0]).min(viewport.size[1] / base_size[1]);
+    if scale >= 1 {
+        [base_size[0] * scale, base_size[1] * scale]
+    } else {
+        viewport.aspect_fit(base_size[0] as f32 / base_size[1] as f32).size
+    }
+}
+
+/// Runs `f` with the scissor test restricted to `rect`, intersected with any scissor
+/// rectangle already in effect. The previous scissor state is restored afterwards.
+pub(crate) fn with_scissor<F: FnOnce()>(rect: Viewport, f: F) {
+    unsafe {
+        let was_enabled = gl::IsEnabled(gl::SCISSOR_TEST) == gl::TRUE;
+        let mut prev = [0; 4];
+        gl::GetIntegerv(gl::SCISSOR_BOX, prev.as_mut_ptr());
+
+        let rect = if was_enabled {
+            rect.intersect(&Viewport::from(prev)).unwrap_or_default()
+        } else {
+            rect
+        };
+
+        gl::Enable(gl::SCISSOR_TEST);
+        gl::Scissor(rect.pos[0], rect.pos[1], rect.size[0].max(0), rect.size[1].max(0));
+
+        f();
+
+        gl::Scissor(prev[0], prev[1], prev[2], prev[3]);
+        if !was_enabled {
+            gl::Disable(gl::SCISSOR_TEST);
+        }
+    }
+}
+
+/// The stencil test settings, so renderers clipping with the stencil buffer can put them back.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct StencilState {
+    enabled: bool,
+    func: GLint,
+    reference: GLint,
+    value_mask: GLint,
+    write_mask: GLint,
+    ops: [GLint; 3],
+}
+
+impl StencilState {
+    pub fn capture() -> Self {
+        let mut state = Self {
+            enabled: false,
+            func: 0,
+            reference: 0,
+            value_mask: 0,
+            write_mask: 0,
+            ops: [0; 3],
+        };
+
+        unsafe {
+            state.enabled = gl::IsEnabled(gl::STENCIL_TEST) == gl::TRUE;
+            gl::GetIntegerv(gl::STENCIL_FUNC, &mut state.func);
+            gl::GetIntegerv(gl::STENCIL_REF, &mut state.reference);
+            gl::GetIntegerv(gl::STENCIL_VALUE_MASK, &mut state.value_mask);
+            gl::GetIntegerv(gl::STENCIL_WRITEMASK, &mut state.write_mask);
+            gl::GetIntegerv(gl::STENCIL_FAIL, &mut state.ops[0]);
+            gl::GetIntegerv(gl::STENCIL_PASS_DEPTH_FAIL, &mut state.ops[1]);
+            gl::GetIntegerv(gl::STENCIL_PASS_DEPTH_PASS, &mut state.ops[2]);
+        }
+
+        state
+    }
+
+    /// The reference value of the clip in effect, if the stencil test is on and only passes
+    /// where the stencil buffer equals it.
+    pub fn clip_reference(&self) -> Option<GLint> {
+        (self.enabled && self.func as GLenum == gl::EQUAL).then_some(self.reference)
+    }
+
+    pub fn restore(&self) {
+        let [fail, depth_fail, pass] = self.ops.map(|op| op as GLenum);
+        unsafe {
+            if self.enabled {
+                gl::Enable(gl::STENCIL_TEST);
+            } else {
+                gl::Disable(gl::STENCIL_TEST);
+            }
+            gl::StencilFunc(self.func as GLenum, self.reference, self.value_mask as GLuint);
+            gl::StencilMask(self.write_mask as GLuint);
+            gl::StencilOp(fail, depth_fail, pass);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insets_apply_absolute_sides() {
+        let viewport = Viewport::new([10, 20], [100, 50]);
+        let insets = Insets::new(
+            SplitPoint::Absolute(5),
+            SplitPoint::Absolute(15),
+            SplitPoint::Absolute(4),
+            SplitPoint::Absolute(6),
+        );
+
+        assert_eq!(insets.apply(viewport), Viewport::new([15, 26], [80, 40]));
+    }
+
+    #[test]
+    fn insets_apply_ratios_per_axis() {
+        let viewport = Viewport::new([0, 0], [200, 100]);
+        let insets = Insets::symmetric(SplitPoint::Ratio(0.1), SplitPoint::Ratio(0.2));
+
+        assert_eq!(insets.apply(viewport), Viewport::new([20, 20], [160, 60]));
+    }
+
+    #[test]
+    fn insets_apply_negative_from_far_edge() {
+        // -90 resolves to 10 of 100 pixels.
+        let viewport = Viewport::new([0, 0], [100, 100]);
+        let insets = Insets::new(
+            SplitPoint::Absolute(-90),
+            SplitPoint::Absolute(0),
+            SplitPoint::Absolute(0),
+            SplitPoint::Absolute(0),
+        );
+
+        assert_eq!(insets.apply(viewport), Viewport::new([10, 0], [90, 100]));
+    }
+
+    #[test]
+    fn insets_apply_zero_is_identity() {
+        let viewport = Viewport::new([3, 4], [50, 60]);
+        assert_eq!(Insets::default().apply(viewport), viewport);
+    }
+
+    #[test]
+    fn insets_larger_than_viewport_give_zero_size() {
+        let viewport = Viewport::new([0, 0], [100, 100]);
+        let applied = Insets::ratio(0.75).apply(viewport);
+
+        assert_eq!(applied.size, [0, 0]);
+    }
+
+    #[test]
+    fn insets_apply_to_empty_viewport() {
+        let applied = Insets::absolute(10).apply(Viewport::default());
+        assert_eq!(applied.size, [0, 0]);
     }
 }
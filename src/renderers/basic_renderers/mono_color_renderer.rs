@@ -1,4 +1,5 @@
-use crate::renderers::{Renderer, Viewport};
+use crate::renderers::{FrameContext, Renderer, Viewport};
+use crate::shader::{build_with_hooks, CustomShader, CustomShaderFiles, HookedProgram, ReloadStatus, ShaderProgram, UniformValue};
 
 use crate::Error;
 
@@ -10,36 +11,60 @@ const vec2 vertices[4] = vec2[4](
     vec2(1.0, 1.0),
     vec2(1.0, -1.0)
 );
+out vec2 v_uv;
+
+vec4 position(vec2 pos);
 
 void main() {
-    gl_Position = vec4(vertices[gl_VertexID], 0.0, 1.0);
+    vec2 pos = vertices[gl_VertexID];
+    v_uv = pos * 0.5 + 0.5;
+    gl_Position = position(pos);
+}
+"#;
+
+const DEFAULT_VERTEX_HOOK: &str = r#"
+vec4 position(vec2 pos) {
+    return vec4(pos, 0.0, 1.0);
 }
 "#;
 
 const FCODE: &str = r#"
 #version 450 core
+in vec2 v_uv;
 out vec4 fColor;
+layout (location = 1) out vec4 fNormal;
 uniform vec4 color;
+
+vec4 effect(vec2 uv);
+
 void main() {
-    fColor = color;
+    fColor = effect(v_uv);
+
+    // A flat surface, for the scene of a lighting renderer with normal mapping.
+    fNormal = vec4(0.5, 0.5, 1.0, fColor.a);
+}
+"#;
+
+const DEFAULT_FRAGMENT_HOOK: &str = r#"
+vec4 effect(vec2 uv) {
+    return color;
 }
 "#;
 
 pub struct MonoColorRenderer {
     viewport: Viewport,
-    program: u32,
+    shader: HookedProgram,
+    color: [f32; 4],
 }
 
 impl MonoColorRenderer {
     pub fn new(color: [f32; 4]) -> Result<Self, Error> {
-        let program = glh::ProgramBuilder::new()
-            .with_vertex_shader(VCODE)?
-            .with_fragment_shader(FCODE)?
-            .build()?;
+        let shader = HookedProgram::new(build_program)?;
 
         let mut _self = Self {
             viewport: Viewport::default(),
-            program,
+            shader,
+            color,
         };
 
         _self.set_color(color);
@@ -48,12 +73,60 @@ impl MonoColorRenderer {
     }
 
     pub fn set_color(&mut self, color: [f32; 4]) {
-        unsafe {
-            let loc = gl::GetUniformLocation(self.program, "color\0".as_ptr() as *const i8);
-            gl::UseProgram(self.program);
-            gl::Uniform4f(loc, color[0], color[1], color[2], color[3]);
-        };
+        self.color = color;
+        self.shader.program().set_builtin("color", color);
+    }
+
+    /// Replaces the shader hooks, or goes back to the built-in ones with `None`.
+    /// If the shader fails to compile, the error is returned and the previous shader is kept.
+    ///
+    /// The vertex hook is `vec4 position(vec2 pos)`. It receives a corner of the viewport,
+    /// from -1.0 to 1.0, and returns the clip-space position. The built-in one returns
+    /// `vec4(pos, 0.0, 1.0)`.
+    ///
+    /// The fragment hook is `vec4 effect(vec2 uv)`. It receives the position within the
+    /// viewport, from `[0.0, 0.0]` at the bottom-left corner to `[1.0, 1.0]` at the top-right,
+    /// and returns the color. The built-in one returns the `color` uniform.
+    ///
+    /// Uniforms declared by the hooks are set with [`set_shader_uniform`](Self::set_shader_uniform),
+    /// and have to be set again after the shader changes.
+    pub fn set_custom_shader(&mut self, shader: Option<CustomShader>) -> Result<(), Error> {
+        self.shader.set_custom_shader(shader)?;
+        self.set_color(self.color);
+        Ok(())
+    }
+
+    pub fn custom_shader(&self) -> Option<&CustomShader> {
+        self.shader.custom_shader()
+    }
+
+    /// Development mode: loads the hooks from `files` and reloads them in
+    /// [`update`](Renderer::update) whenever they change. See [`CustomShaderFiles`].
+    pub fn watch_shader_files(&mut self, files: CustomShaderFiles) -> Result<(), Error> {
+        self.shader.watch_files(files)?;
+        self.set_color(self.color);
+        Ok(())
+    }
+
+    /// Stops reloading the hooks. The last ones that compiled stay in use.
+    pub fn stop_watching_shader_files(&mut self) {
+        self.shader.stop_watching_files();
+    }
+
+    /// The error of the last failed reload in development mode, if the current files don't compile.
+    pub fn shader_error(&self) -> Option<&str> {
+        self.shader.files_error()
     }
+
+    /// Sets a uniform declared by a custom shader. Fails if the uniform doesn't exist,
+    /// was removed by the compiler as unused, or has a different type.
+    pub fn set_shader_uniform<V: UniformValue>(&mut self, name: &str, value: V) -> Result<(), Error> {
+        self.shader.program().set_uniform(name, value)
+    }
+}
+
+fn build_program(custom: Option<&CustomShader>) -> Result<ShaderProgram, Error> {
+    build_with_hooks(VCODE, DEFAULT_VERTEX_HOOK, FCODE, DEFAULT_FRAGMENT_HOOK, custom)
 }
 
 impl Renderer for MonoColorRenderer {
@@ -61,19 +134,17 @@ impl Renderer for MonoColorRenderer {
         self.viewport = viewport;
     }
 
-    fn render(&self) {
-        self.viewport.gl_viewport();
-        unsafe {
-            gl::UseProgram(self.program);
-            gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
+    fn update(&mut self, _ctx: &mut FrameContext) {
+        if self.shader.poll_files() == ReloadStatus::Reloaded {
+            self.set_color(self.color);
         }
     }
-}
 
-impl Drop for MonoColorRenderer {
-    fn drop(&mut self) {
+    fn render(&self) {
+        self.viewport.gl_viewport();
+        self.shader.program().use_program();
         unsafe {
-            gl::DeleteProgram(self.program);
+            gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
         }
     }
 }
@@ -0,0 +1,84 @@
+use crate::renderers::Viewport;
+
+use crate::shader::ShaderProgram;
+use crate::Error;
+
+const VCODE: &str = r#"
+#version 450 core
+const vec2 vertices[4] = vec2[4](
+    vec2(-1.0, -1.0),
+    vec2(-1.0, 1.0),
+    vec2(1.0, 1.0),
+    vec2(1.0, -1.0)
+);
+
+void main() {
+    gl_Position = vec4(vertices[gl_VertexID], 0.0, 1.0);
+}
+"#;
+
+const FCODE: &str = r#"
+#version 450 core
+out vec4 fColor;
+layout (location = 1) out vec4 fNormal;
+uniform vec4 u_rect;
+uniform float u_radius;
+uniform float u_thickness;
+uniform vec4 u_color;
+
+float rounded_rect_sdf(vec2 p, vec2 half_size, float r) {
+    vec2 q = abs(p) - half_size + vec2(r);
+    return length(max(q, 0.0)) + min(max(q.x, q.y), 0.0) - r;
+}
+
+void main() {
+    vec2 half_size = u_rect.zw * 0.5;
+    vec2 p = gl_FragCoord.xy - u_rect.xy - half_size;
+    float r = min(u_radius, min(half_size.x, half_size.y));
+    float d = rounded_rect_sdf(p, half_size, r);
+    if (d > 0.0 || (u_thickness > 0.0 && d < -u_thickness)) {
+        discard;
+    }
+    fColor = u_color;
+
+    // A flat surface, for the scene of a lighting renderer with normal mapping.
+    fNormal = vec4(0.5, 0.5, 1.0, fColor.a);
+}
+"#;
+
+/// Draws a filled or outlined rectangle with rounded corners.
+/// Used by the layout renderers for borders and corner clipping.
+pub(crate) struct RoundedRectProgram {
+    program: ShaderProgram,
+}
+
+impl RoundedRectProgram {
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self {
+            program: ShaderProgram::new(VCODE, FCODE)?,
+        })
+    }
+
+    /// Draws the rectangle covering `rect`. A `thickness` of zero or less
+    /// fills the whole shape, otherwise only an outline of that many pixels
+    /// is drawn along the inside of the edge.
+    pub fn draw(&self, rect: Viewport, radius: i32, thickness: i32, color: [f32; 4]) {
+        if rect.size[0] <= 0 || rect.size[1] <= 0 {
+            return;
+        }
+
+        rect.gl_viewport();
+        let program = &self.program;
+        program.set_builtin(
+            "u_rect",
+            [rect.pos[0] as f32, rect.pos[1] as f32, rect.size[0] as f32, rect.size[1] as f32],
+        );
+        program.set_builtin("u_radius", radius.max(0) as f32);
+        program.set_builtin("u_thickness", thickness as f32);
+        program.set_builtin("u_color", color);
+        program.use_program();
+        unsafe {
+            gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use gl::types::*;
+
+/// How a renderer's output is combined with what is already in the framebuffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// Standard "over" blending for straight (non-premultiplied) alpha.
+    #[default]
+    Alpha,
+
+    /// "Over" blending for textures whose color is already multiplied by alpha.
+    PremultipliedAlpha,
+
+    /// Adds the color, weighted by alpha, to the destination. Useful for glows and light.
+    Additive,
+
+    /// Multiplies the destination by the color. Darkens; white has no effect.
+    Multiply,
+
+    /// Inverse of multiply. Lightens; black has no effect.
+    Screen,
+
+    /// Blending is disabled and alpha is ignored.
+    Opaque,
+}
+
+impl BlendMode {
+    /// Blend factors as `[src_rgb, dst_rgb, src_alpha, dst_alpha]`,
+    /// or `None` if blending is disabled.
+    fn factors(&self) -> Option<[GLenum; 4]> {
+        match self {
+            BlendMode::Alpha => Some([gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA, gl::ONE, gl::ONE_MINUS_SRC_ALPHA]),
+            BlendMode::PremultipliedAlpha => Some([gl::ONE, gl::ONE_MINUS_SRC_ALPHA, gl::ONE, gl::ONE_MINUS_SRC_ALPHA]),
+            BlendMode::Additive => Some([gl::SRC_ALPHA, gl::ONE, gl::ZERO, gl::ONE]),
+            BlendMode::Multiply => Some([gl::DST_COLOR, gl::ONE_MINUS_SRC_ALPHA, gl::ZERO, gl::ONE]),
+            BlendMode::Screen => Some([gl::ONE, gl::ONE_MINUS_SRC_COLOR, gl::ZERO, gl::ONE]),
+            BlendMode::Opaque => None,
+        }
+    }
+
+    /// Whether the blend factors expect the shader to output premultiplied color,
+    /// given a texture with straight alpha.
+    pub(crate) fn wants_premultiplied_output(&self) -> bool {
+        matches!(self, BlendMode::Multiply | BlendMode::Screen)
+    }
+
+    /// Sets the OpenGL blend state for this mode.
+    pub fn apply(&self) {
+        unsafe {
+            match self.factors() {
+                Some([src_rgb, dst_rgb, src_alpha, dst_alpha]) => {
+                    gl::Enable(gl::BLEND);
+                    gl::BlendEquation(gl::FUNC_ADD);
+                    gl::BlendFuncSeparate(src_rgb, dst_rgb, src_alpha, dst_alpha);
+                }
+                None => gl::Disable(gl::BLEND),
+            }
+        }
+    }
+}
+
+/// A snapshot of the OpenGL blend state, so renderers that change it can put it back.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BlendState {
+    enabled: bool,
+    src_rgb: GLint,
+    dst_rgb: GLint,
+    src_alpha: GLint,
+    dst_alpha: GLint,
+    equation_rgb: GLint,
+    equation_alpha: GLint,
+}
+
+impl BlendState {
+    pub fn capture() -> Self {
+        let mut state = Self {
+            enabled: false,
+            src_rgb: 0,
+            dst_rgb: 0,
+            src_alpha: 0,
+            dst_alpha: 0,
+            equation_rgb: 0,
+            equation_alpha: 0,
+        };
+
+        unsafe {
+            state.enabled = gl::IsEnabled(gl::BLEND) == gl::TRUE;
+            gl::GetIntegerv(gl::BLEND_SRC_RGB, &mut state.src_rgb);
+            gl::GetIntegerv(gl::BLEND_DST_RGB, &mut state.dst_rgb);
+            gl::GetIntegerv(gl::BLEND_SRC_ALPHA, &mut state.src_alpha);
+            gl::GetIntegerv(gl::BLEND_DST_ALPHA, &mut state.dst_alpha);
+            gl::GetIntegerv(gl::BLEND_EQUATION_RGB, &mut state.equation_rgb);
+            gl::GetIntegerv(gl::BLEND_EQUATION_ALPHA, &mut state.equation_alpha);
+        }
+
+        state
+    }
+
+    pub fn restore(&self) {
+        unsafe {
+            if self.enabled {
+                gl::Enable(gl::BLEND);
+            } else {
+                gl::Disable(gl::BLEND);
+            }
+            gl::BlendEquationSeparate(self.equation_rgb as GLenum, self.equation_alpha as GLenum);
+            gl::BlendFuncSeparate(
+                self.src_rgb as GLenum,
+                self.dst_rgb as GLenum,
+                self.src_alpha as GLenum,
+                self.dst_alpha as GLenum,
+            );
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crate::renderers::Viewport;
+
+/// Per-frame information passed to [`Renderer::update`](crate::renderers::Renderer::update)
+/// and [`Renderer::render_with_context`](crate::renderers::Renderer::render_with_context).
+///
+/// `update` receives the context mutably, so renderers can load things into the shared
+/// [`ResourceCache`] there and read them back while rendering.
+#[derive(Debug)]
+pub struct FrameContext<'a> {
+    /// Time since the previous frame, in seconds.
+    pub delta_time: f32,
+
+    /// Time since the first frame, in seconds.
+    pub total_time: f64,
+
+    /// Number of frames before this one.
+    pub frame_index: u64,
+
+    /// The viewport of the whole window, not the viewport of the renderer
+    /// receiving the context.
+    pub viewport: Viewport,
+
+    /// Ratio of physical pixels to logical pixels.
+    pub dpi_scale: f32,
+
+    /// Resources shared between renderers.
+    pub resources: &'a mut ResourceCache,
+}
+
+impl<'a> FrameContext<'a> {
+    /// A context for a single frame with no elapsed time, mostly useful for
+    /// driving renderers outside of a regular frame loop.
+    pub fn new(viewport: Viewport, resources: &'a mut ResourceCache) -> Self {
+        Self {
+            delta_time: 0.0,
+            total_time: 0.0,
+            frame_index: 0,
+            viewport,
+            dpi_scale: 1.0,
+            resources,
+        }
+    }
+}
+
+/// Measures frame times and hands out a [`FrameContext`] for each frame.
+#[derive(Debug, Clone)]
+pub struct FrameClock {
+    start: Option<Instant>,
+    last: Option<Instant>,
+    frame_index: u64,
+}
+
+impl FrameClock {
+    pub fn new() -> Self {
+        Self {
+            start: None,
+            last: None,
+            frame_index: 0,
+        }
+    }
+
+    /// Starts a new frame. The first call reports a delta time of zero.
+    pub fn next_frame<'a>(
+        &mut self,
+        viewport: Viewport,
+        dpi_scale: f32,
+        resources: &'a mut ResourceCache,
+    ) -> FrameContext<'a> {
+        let now = Instant::now();
+        let start = *self.start.get_or_insert(now);
+        let delta_time = self.last.map_or(0.0, |last| (now - last).as_secs_f32());
+        self.last = Some(now);
+
+        let frame_index = self.frame_index;
+        self.frame_index += 1;
+
+        FrameContext {
+            delta_time,
+            total_time: (now - start).as_secs_f64(),
+            frame_index,
+            viewport,
+            dpi_scale,
+            resources,
+        }
+    }
+}
+
+impl Default for FrameClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A map holding at most one value of each type, used to share things
+/// like textures and programs between renderers.
+#[derive(Debug, Default)]
+pub struct ResourceCache {
+    resources: HashMap<TypeId, Box<dyn Any>>,
+}
+
+impl ResourceCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores a value, returning the previous value of the same type, if any.
+    pub fn insert<T: Any>(&mut self, value: T) -> Option<T> {
+        self.resources
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|old| old.downcast().ok())
+            .map(|old| *old)
+    }
+
+    pub fn get<T: Any>(&self) -> Option<&T> {
+        self.resources
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+
+    pub fn get_mut<T: Any>(&mut self) -> Option<&mut T> {
+        self.resources
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_mut())
+    }
+
+    /// Returns the value of type `T`, creating it with `f` if it isn't there yet.
+    pub fn get_or_insert_with<T: Any, F: FnOnce() -> T>(&mut self, f: F) -> &mut T {
+        self.resources
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(f()))
+            .downcast_mut()
+            .expect("resource stored under the wrong type")
+    }
+
+    pub fn remove<T: Any>(&mut self) -> Option<T> {
+        self.resources
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast().ok())
+            .map(|value| *value)
+    }
+
+    pub fn contains<T: Any>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<T>())
+    }
+}
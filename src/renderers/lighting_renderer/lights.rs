@@ -0,0 +1,222 @@
+/// The shape of the area a [`Light`] shines on.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LightKind {
+    /// Shines in all directions.
+    #[default]
+    Point,
+
+    /// Shines in a cone.
+    Spot {
+        /// Direction of the cone in radians, counterclockwise from the positive x axis.
+        direction: f32,
+
+        /// Half of the cone's opening angle, in radians.
+        angle: f32,
+
+        /// Part of the angle over which the edge of the cone fades out, from 0.0 (a hard
+        /// edge) to 1.0.
+        softness: f32,
+    },
+}
+
+/// A light of a [`LightingRenderer`](super::LightingRenderer).
+///
+/// Positions and sizes are in pixels from the bottom-left corner of the renderer's viewport.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Light {
+    pub position: [f32; 2],
+    pub color: [f32; 3],
+
+    /// Multiplied with the color.
+    pub intensity: f32,
+
+    /// Distance at which the light reaches zero.
+    pub radius: f32,
+
+    /// Exponent of the falloff from the center to the radius: 1.0 is linear,
+    /// higher values concentrate the light near the center.
+    pub falloff: f32,
+
+    pub kind: LightKind,
+
+    /// Distance of the light above the scene, which sets how steeply it hits normal-mapped
+    /// surfaces: low lights bring out their relief, high ones light them more evenly. Only
+    /// used with [`set_normal_mapping`](super::LightingRenderer::set_normal_mapping).
+    pub height: f32,
+
+    /// Whether the renderer's occluders block this light.
+    pub casts_shadows: bool,
+}
+
+impl Light {
+    pub fn point(position: [f32; 2], radius: f32, color: [f32; 3]) -> Self {
+        Self {
+            position,
+            color,
+            intensity: 1.0,
+            radius,
+            falloff: 2.0,
+            kind: LightKind::Point,
+            height: radius * 0.5,
+            casts_shadows: true,
+        }
+    }
+
+    /// A spot light pointing in `direction` with a cone of half-angle `angle`, both in radians.
+    pub fn spot(position: [f32; 2], radius: f32, color: [f32; 3], direction: f32, angle: f32) -> Self {
+        Self {
+            kind: LightKind::Spot {
+                direction,
+                angle,
+                softness: 0.2,
+            },
+            ..Self::point(position, radius, color)
+        }
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn with_falloff(mut self, falloff: f32) -> Self {
+        self.falloff = falloff;
+        self
+    }
+
+    pub fn with_height(mut self, height: f32) -> Self {
+        self.height = height;
+        self
+    }
+
+    pub fn with_shadows(mut self, casts_shadows: bool) -> Self {
+        self.casts_shadows = casts_shadows;
+        self
+    }
+}
+
+/// A polygon that blocks light, in pixels from the bottom-left corner of the viewport.
+/// The points go around the outline in either direction; the last one connects back to the first.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Occluder {
+    pub points: Vec<[f32; 2]>,
+}
+
+impl Occluder {
+    pub fn polygon(points: Vec<[f32; 2]>) -> Self {
+        Self { points }
+    }
+
+    /// A rectangle with its bottom-left corner at `pos`.
+    pub fn rect(pos: [f32; 2], size: [f32; 2]) -> Self {
+        let [x, y] = pos;
+        let [w, h] = size;
+        Self::polygon(vec![[x, y], [x + w, y], [x + w, y + h], [x, y + h]])
+    }
+
+    /// The bounding box as `[min_x, min_y, max_x, max_y]`.
+    pub(crate) fn bounds(&self) -> [f32; 4] {
+        self.points.iter().fold(
+            [f32::INFINITY, f32::INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY],
+            |[x1, y1, x2, y2], &[x, y]| [x1.min(x), y1.min(y), x2.max(x), y2.max(y)],
+        )
+    }
+
+    /// Occluders for the solid tiles of a tilemap, laid out like a
+    /// [`TilemapRenderer`](crate::renderers::tilemap_renderer::TilemapRenderer) map: row by row,
+    /// starting from the top. `origin` is the top-left corner of the map and `tile_size` the
+    /// size of a tile on screen, both in pixels. Runs of solid tiles in a row are merged into
+    /// a single rectangle.
+    pub fn from_tiles<F: Fn(u16) -> bool>(
+        map_size: [usize; 2],
+        tile_indices: &[u16],
+        is_solid: F,
+        origin: [f32; 2],
+        tile_size: [f32; 2],
+    ) -> Vec<Occluder> {
+        let mut occluders = Vec::new();
+        let solid = |x: usize, y: usize| tile_indices.get(y * map_size[0] + x).is_some_and(|&index| is_solid(index));
+
+        for y in 0..map_size[1] {
+            let mut x = 0;
+            while x < map_size[0] {
+                if !solid(x, y) {
+                    x += 1;
+                    continue;
+                }
+
+                let start = x;
+                while x < map_size[0] && solid(x, y) {
+                    x += 1;
+                }
+
+                let bottom = origin[1] - (y + 1) as f32 * tile_size[1];
+                occluders.push(Occluder::rect(
+                    [origin[0] + start as f32 * tile_size[0], bottom],
+                    [(x - start) as f32 * tile_size[0], tile_size[1]],
+                ));
+            }
+        }
+
+        occluders
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds_cover_all_points() {
+        let occluder = Occluder::polygon(vec![[2.0, -1.0], [5.0, 3.0], [-4.0, 0.5]]);
+        assert_eq!(occluder.bounds(), [-4.0, -1.0, 5.0, 3.0]);
+        assert_eq!(Occluder::rect([1.0, 2.0], [3.0, 4.0]).bounds(), [1.0, 2.0, 4.0, 6.0]);
+    }
+
+    #[test]
+    fn bounds_of_an_empty_occluder_are_inverted() {
+        let [min_x, min_y, max_x, max_y] = Occluder::default().bounds();
+        assert!(min_x > max_x && min_y > max_y);
+    }
+
+    #[test]
+    fn from_tiles_merges_runs_in_a_row() {
+        #[rustfmt::skip]
+        let tiles = [
+            1, 1, 0, 1,
+            0, 1, 1, 1,
+        ];
+        let occluders = Occluder::from_tiles([4, 2], &tiles, |index| index != 0, [0.0, 0.0], [1.0, 1.0]);
+
+        assert_eq!(
+            occluders,
+            [
+                Occluder::rect([0.0, -1.0], [2.0, 1.0]),
+                Occluder::rect([3.0, -1.0], [1.0, 1.0]),
+                Occluder::rect([1.0, -2.0], [3.0, 1.0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn from_tiles_goes_down_from_the_top_left_origin() {
+        #[rustfmt::skip]
+        let tiles = [
+            0, 0,
+            0, 0,
+            2, 0,
+        ];
+        let occluders = Occluder::from_tiles([2, 3], &tiles, |index| index == 2, [10.0, 100.0], [8.0, 16.0]);
+
+        // The bottom row spans from 100 - 3 * 16 up to 100 - 2 * 16.
+        assert_eq!(occluders, [Occluder::rect([10.0, 52.0], [8.0, 16.0])]);
+        assert_eq!(occluders[0].bounds(), [10.0, 52.0, 18.0, 68.0]);
+    }
+
+    #[test]
+    fn from_tiles_treats_missing_tiles_as_empty() {
+        let occluders = Occluder::from_tiles([3, 2], &[1, 1, 1, 1], |_| true, [0.0, 0.0], [1.0, 1.0]);
+        assert_eq!(occluders, [Occluder::rect([0.0, -1.0], [3.0, 1.0]), Occluder::rect([0.0, -2.0], [1.0, 1.0])]);
+        assert!(Occluder::from_tiles([0, 0], &[], |_| true, [0.0, 0.0], [1.0, 1.0]).is_empty());
+    }
+}
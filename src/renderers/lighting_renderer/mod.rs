@@ -0,0 +1,519 @@
+use gl::types::*;
+
+use crate::renderers::{
+    render_child,
+    FrameContext,
+    Renderer,
+    Viewport,
+    basic_renderers::StencilState,
+    blend::{BlendMode, BlendState},
+    render_target::{FramebufferState, RenderTarget, TextureUnitState},
+};
+use crate::shader::{ShaderProgram, TextureUnit};
+use crate::Error;
+
+mod lights;
+pub use lights::{Light, LightKind, Occluder};
+
+const LIGHT_VCODE: &str = r#"
+#version 450 core
+layout (location = 0) in vec2 in_pos;
+out vec2 v_pos;
+uniform vec2 u_viewport_size;
+
+void main() {
+    v_pos = in_pos;
+    gl_Position = vec4(in_pos / u_viewport_size * 2.0 - 1.0, 0.0, 1.0);
+}
+"#;
+
+const LIGHT_FCODE: &str = r#"
+#version 450 core
+in vec2 v_pos;
+out vec4 fColor;
+uniform vec2 u_viewport_size;
+uniform vec2 u_light_position;
+uniform vec3 u_light_color;
+uniform float u_radius;
+uniform float u_falloff;
+uniform bool u_spot;
+uniform vec2 u_spot_direction;
+uniform float u_spot_outer;
+uniform float u_spot_inner;
+uniform bool u_normal_mapping;
+uniform sampler2D u_normals;
+uniform float u_light_height;
+
+void main() {
+    vec2 offset = v_pos - u_light_position;
+    float distance = length(offset);
+    float light = pow(clamp(1.0 - distance / u_radius, 0.0, 1.0), u_falloff);
+
+    if (u_spot) {
+        float cosine = distance > 0.0 ? dot(offset / distance, u_spot_direction) : 1.0;
+        light *= smoothstep(u_spot_outer, u_spot_inner, cosine);
+    }
+
+    if (u_normal_mapping) {
+        vec3 normal = normalize(texture(u_normals, v_pos / u_viewport_size).rgb * 2.0 - 1.0);
+        vec3 to_light = normalize(vec3(-offset, u_light_height));
+        light *= max(dot(normal, to_light), 0.0);
+    }
+
+    fColor = vec4(u_light_color * light, 1.0);
+}
+"#;
+
+const SHADOW_FCODE: &str = r#"
+#version 450 core
+out vec4 fColor;
+
+void main() {
+    fColor = vec4(0.0);
+}
+"#;
+
+const COMPOSITE_VCODE: &str = r#"
+#version 450 core
+out vec2 v_uv;
+
+void main() {
+    vec2 pos = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+    v_uv = pos;
+    gl_Position = vec4(pos * 2.0 - 1.0, 0.0, 1.0);
+}
+"#;
+
+const COMPOSITE_FCODE: &str = r#"
+#version 450 core
+in vec2 v_uv;
+out vec4 fColor;
+uniform sampler2D u_light_map;
+uniform bool u_normal_mapping;
+uniform sampler2D u_scene;
+
+void main() {
+    vec3 light = texture(u_light_map, v_uv).rgb;
+    if (u_normal_mapping) {
+        vec4 color = texture(u_scene, v_uv);
+        fColor = vec4(color.rgb * light, color.a);
+    } else {
+        fColor = vec4(light, 1.0);
+    }
+}
+"#;
+
+/// Lights a scene: renders its child as usual, then multiplies a light map over it.
+///
+/// Without normal mapping, the light map is multiplied over everything in the viewport, so
+/// whatever was drawn there before, such as the clear color or renderers drawn earlier in the
+/// frame, is lit too. Make those part of the child if they should be lit, or turn on normal
+/// mapping, which lights only what the child draws.
+///
+/// The light map starts out as the ambient light, and each [`Light`] adds its color on top.
+/// Lights that cast shadows are blocked by the [`Occluder`] polygons, such as walls or the
+/// solid tiles of a tilemap from [`Occluder::from_tiles`]. Lights and occluders are given in
+/// pixels from the bottom-left corner of the viewport, like
+/// [`ShapeRenderer`](crate::renderers::shape_renderer::ShapeRenderer) coordinates.
+///
+/// With [`set_normal_mapping`](Self::set_normal_mapping), the child is rendered offscreen
+/// together with the normals of the texture and tilemap renderers that have a normal map, and
+/// each light is shaded by how much the surface faces it.
+///
+/// If the offscreen buffers can't be resized to a new viewport, nothing is drawn until a later
+/// viewport works, and the error is available from [`last_error`](Self::last_error).
+pub struct LightingRenderer<R: Renderer> {
+    viewport: Viewport,
+    child: R,
+    lights: Vec<Light>,
+    occluders: Vec<Occluder>,
+    ambient: [f32; 3],
+
+    light_program: ShaderProgram,
+    shadow_program: ShaderProgram,
+    composite_program: ShaderProgram,
+    vao: GLuint,
+    buffer: GLuint,
+    composite_vao: GLuint,
+    light_map: RenderTarget,
+    scene: Option<RenderTarget>,
+
+    /// Why the buffers couldn't be resized to the current viewport.
+    error: Option<String>,
+}
+
+/// Attachment of the scene target the normals are rendered into.
+const NORMALS_ATTACHMENT: usize = 1;
+
+/// The normal of a surface facing the viewer, encoded like in a normal map.
+const FLAT_NORMAL: [f32; 4] = [0.5, 0.5, 1.0, 0.0];
+
+impl<R: Renderer> LightingRenderer<R> {
+    pub fn new(child: R) -> Result<Self, Error> {
+        let light_program = ShaderProgram::new(LIGHT_VCODE, LIGHT_FCODE)?;
+        let shadow_program = ShaderProgram::new(LIGHT_VCODE, SHADOW_FCODE)?;
+        let composite_program = ShaderProgram::new(COMPOSITE_VCODE, COMPOSITE_FCODE)?;
+        composite_program.set_builtin("u_light_map", TextureUnit(0));
+        composite_program.set_builtin("u_scene", TextureUnit(1));
+        light_program.set_builtin("u_normals", TextureUnit(1));
+
+        let mut buffer = 0;
+        let mut vao = 0;
+        let mut composite_vao = 0;
+        unsafe {
+            gl::CreateBuffers(1, &mut buffer);
+            gl::GenVertexArrays(1, &mut vao);
+            gl::CreateVertexArrays(1, &mut composite_vao);
+        }
+
+        glh::enable_interleaved_vertex_array_attributes(vao, buffer, gl::FLOAT, false, 0, &[2])?;
+
+        Ok(Self {
+            viewport: Viewport::default(),
+            child,
+            lights: Vec::new(),
+            occluders: Vec::new(),
+            ambient: [0.2, 0.2, 0.2],
+            light_program,
+            shadow_program,
+            composite_program,
+            vao,
+            buffer,
+            composite_vao,
+            light_map: RenderTarget::with_depth_stencil([1, 1])?,
+            scene: None,
+            error: None,
+        })
+    }
+
+    pub fn get_child(&self) -> &R {
+        &self.child
+    }
+
+    pub fn get_child_mut(&mut self) -> &mut R {
+        &mut self.child
+    }
+
+    /// Sets the light everything receives regardless of the lights, from black (only the
+    /// lights are visible) to white (the scene is unchanged). Defaults to a dark gray.
+    pub fn set_ambient(&mut self, ambient: [f32; 3]) {
+        self.ambient = ambient;
+    }
+
+    pub fn ambient(&self) -> [f32; 3] {
+        self.ambient
+    }
+
+    /// Turns per-pixel lighting from normal maps on or off. It is off by default.
+    ///
+    /// While on, the child draws into an offscreen target with a second color output for
+    /// normals, which [`TextureRenderer`](crate::renderers::texture_renderer::TextureRenderer)
+    /// and [`TilemapRenderer`](crate::renderers::tilemap_renderer::TilemapRenderer) fill from
+    /// their normal maps, or with flat normals if they have none. The other renderers always
+    /// draw flat normals. The child's viewport then covers that target instead of the screen.
+    pub fn set_normal_mapping(&mut self, enabled: bool) -> Result<(), Error> {
+        if enabled == self.scene.is_some() {
+            return Ok(());
+        }
+
+        if enabled {
+            let scene = RenderTarget::with_attachments(self.viewport.size, 2, true)?;
+            self.child.set_viewport(scene.viewport());
+            self.scene = Some(scene);
+        } else {
+            self.scene = None;
+            self.child.set_viewport(self.viewport);
+        }
+
+        Ok(())
+    }
+
+    pub fn normal_mapping(&self) -> bool {
+        self.scene.is_some()
+    }
+
+    /// Why the offscreen buffers couldn't be resized to the current viewport, if they
+    /// couldn't. Nothing is drawn until this is cleared by a viewport that works.
+    pub fn last_error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    fn resize_targets(&mut self) -> Result<(), Error> {
+        self.light_map.resize(self.viewport.size)?;
+        if let Some(ref mut scene) = self.scene {
+            scene.resize(self.viewport.size)?;
+        }
+        Ok(())
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    pub fn lights_mut(&mut self) -> &mut Vec<Light> {
+        &mut self.lights
+    }
+
+    pub fn set_lights(&mut self, lights: &[Light]) {
+        self.lights.clear();
+        self.lights.extend_from_slice(lights);
+    }
+
+    pub fn push_light(&mut self, light: Light) {
+        self.lights.push(light);
+    }
+
+    pub fn clear_lights(&mut self) {
+        self.lights.clear();
+    }
+
+    pub fn occluders(&self) -> &[Occluder] {
+        &self.occluders
+    }
+
+    pub fn occluders_mut(&mut self) -> &mut Vec<Occluder> {
+        &mut self.occluders
+    }
+
+    pub fn set_occluders(&mut self, occluders: &[Occluder]) {
+        self.occluders.clear();
+        self.occluders.extend_from_slice(occluders);
+    }
+
+    pub fn push_occluder(&mut self, occluder: Occluder) {
+        self.occluders.push(occluder);
+    }
+
+    pub fn clear_occluders(&mut self) {
+        self.occluders.clear();
+    }
+
+    fn render_frame(&self, ctx: Option<&FrameContext>) {
+        if self.error.is_some() {
+            return;
+        }
+
+        match &self.scene {
+            Some(scene) => scene.draw_into(|| {
+                scene.clear([0.0; 4]);
+                scene.clear_attachment(NORMALS_ATTACHMENT, FLAT_NORMAL);
+                render_child(&self.child, ctx);
+            }),
+            None => render_child(&self.child, ctx),
+        }
+
+        if self.viewport.is_empty() {
+            return;
+        }
+
+        let screen = FramebufferState::capture();
+        let blend_state = BlendState::capture();
+        let stencil_state = StencilState::capture();
+        let texture_units = [TextureUnitState::capture(0), TextureUnitState::capture(1)];
+
+        let normals = self.scene.as_ref().and_then(|scene| scene.attachment_texture(NORMALS_ATTACHMENT));
+        if let Some(normals) = normals {
+            TextureUnitState::bind(1, normals);
+        }
+        self.light_program.set_builtin("u_normal_mapping", normals.is_some());
+
+        self.light_map.bind();
+        self.light_map.clear([self.ambient[0], self.ambient[1], self.ambient[2], 1.0]);
+        self.draw_lights();
+
+        screen.restore();
+        stencil_state.restore();
+        self.viewport.gl_viewport();
+
+        // The scene target was cleared to transparent, so its colors are premultiplied.
+        match &self.scene {
+            Some(scene) => {
+                BlendMode::PremultipliedAlpha.apply();
+                TextureUnitState::bind(1, scene.texture());
+            }
+            None => BlendMode::Multiply.apply(),
+        }
+        TextureUnitState::bind(0, self.light_map.texture());
+        self.composite_program.set_builtin("u_normal_mapping", self.scene.is_some());
+        self.composite_program.use_program();
+        unsafe {
+            gl::BindVertexArray(self.composite_vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+
+        for texture_unit in texture_units {
+            texture_unit.restore();
+        }
+        blend_state.restore();
+    }
+
+    /// Draws the lights into the bound light map, each after marking its shadows in the
+    /// stencil buffer.
+    fn draw_lights(&self) {
+        let size = [self.viewport.size[0] as f32, self.viewport.size[1] as f32];
+        self.light_program.set_builtin("u_viewport_size", size);
+        self.shadow_program.set_builtin("u_viewport_size", size);
+
+        // One quad per light, followed by its shadow volumes.
+        let mut vertices = Vec::new();
+        let mut ranges = Vec::with_capacity(self.lights.len());
+        for light in &self.lights {
+            let start = vertices.len() / 2;
+            push_light_quad(&mut vertices, light);
+            if light.casts_shadows {
+                for occluder in &self.occluders {
+                    push_shadow_volume(&mut vertices, light, occluder);
+                }
+            }
+            ranges.push((start as GLint, (vertices.len() / 2 - start) as GLsizei));
+        }
+
+        if vertices.is_empty() {
+            return;
+        }
+
+        unsafe {
+            gl::NamedBufferData(
+                self.buffer,
+                std::mem::size_of_val(vertices.as_slice()) as isize,
+                vertices.as_ptr() as *const _,
+                gl::STREAM_DRAW,
+            );
+            gl::BindVertexArray(self.vao);
+            gl::StencilMask(0xFF);
+        }
+
+        for (light, &(start, count)) in self.lights.iter().zip(&ranges) {
+            let shadow_count = count - 6;
+            unsafe {
+                if shadow_count > 0 {
+                    gl::ClearNamedFramebufferiv(self.light_map.framebuffer(), gl::STENCIL, 0, &0);
+                    gl::Enable(gl::STENCIL_TEST);
+                    gl::StencilFunc(gl::ALWAYS, 1, 0xFF);
+                    gl::StencilOp(gl::KEEP, gl::KEEP, gl::REPLACE);
+                    gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+                    self.shadow_program.use_program();
+                    gl::DrawArrays(gl::TRIANGLES, start + 6, shadow_count);
+
+                    gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+                    gl::StencilFunc(gl::EQUAL, 0, 0xFF);
+                    gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
+                } else {
+                    gl::Disable(gl::STENCIL_TEST);
+                }
+            }
+
+            self.set_light_uniforms(light);
+            BlendMode::Additive.apply();
+            self.light_program.use_program();
+            unsafe {
+                gl::DrawArrays(gl::TRIANGLES, start, 6);
+            }
+        }
+    }
+
+    fn set_light_uniforms(&self, light: &Light) {
+        let program = &self.light_program;
+        let color = light.color.map(|c| c * light.intensity);
+        program.set_builtin("u_light_position", light.position);
+        program.set_builtin("u_light_color", color);
+        program.set_builtin("u_radius", light.radius.max(0.001));
+        program.set_builtin("u_falloff", light.falloff.max(0.01));
+        program.set_builtin("u_light_height", light.height.max(0.001));
+
+        match light.kind {
+            LightKind::Point => program.set_builtin("u_spot", false),
+            LightKind::Spot { direction, angle, softness } => {
+                let angle = angle.clamp(0.0, std::f32::consts::PI);
+                let inner = angle * (1.0 - softness.clamp(0.0, 1.0));
+                program.set_builtin("u_spot", true);
+                program.set_builtin("u_spot_direction", [direction.cos(), direction.sin()]);
+                program.set_builtin("u_spot_outer", angle.cos());
+                // Keeps smoothstep's edges apart for hard cones.
+                program.set_builtin("u_spot_inner", inner.cos().max(angle.cos() + 0.0001));
+            }
+        }
+    }
+}
+
+/// Adds two triangles covering the area a light can reach.
+fn push_light_quad(vertices: &mut Vec<f32>, light: &Light) {
+    let [x, y] = light.position;
+    let r = light.radius.max(0.0);
+
+    #[rustfmt::skip]
+    vertices.extend_from_slice(&[
+        x - r, y - r,   x + r, y - r,   x + r, y + r,
+        x - r, y - r,   x + r, y + r,   x - r, y + r,
+    ]);
+}
+
+/// Adds the area an occluder hides from a light: each edge of the outline, extended away
+/// from the light past the light's reach.
+fn push_shadow_volume(vertices: &mut Vec<f32>, light: &Light, occluder: &Occluder) {
+    let [x1, y1, x2, y2] = occluder.bounds();
+    let [lx, ly] = light.position;
+    let r = light.radius;
+    if occluder.points.len() < 2 || x2 < lx - r || x1 > lx + r || y2 < ly - r || y1 > ly + r {
+        return;
+    }
+
+    // Far enough to leave the light's quad, whose corners are r * sqrt(2) away.
+    let reach = r * 2.0;
+    let project = |[px, py]: [f32; 2]| {
+        let (dx, dy) = (px - lx, py - ly);
+        let length = (dx * dx + dy * dy).sqrt().max(0.0001);
+        [px + dx / length * reach, py + dy / length * reach]
+    };
+
+    let points = &occluder.points;
+    for (i, &a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        let (far_a, far_b) = (project(a), project(b));
+
+        #[rustfmt::skip]
+        vertices.extend_from_slice(&[
+            a[0], a[1],   b[0], b[1],   far_b[0], far_b[1],
+            a[0], a[1],   far_b[0], far_b[1],   far_a[0], far_a[1],
+        ]);
+    }
+}
+
+impl<R: Renderer> Renderer for LightingRenderer<R> {
+    fn set_viewport(&mut self, viewport: Viewport) {
+        self.viewport = viewport;
+        if !viewport.is_empty() {
+            self.error = self
+                .resize_targets()
+                .err()
+                .map(|e| format!("LightingRenderer::set_viewport: {}", e));
+        }
+
+        match &self.scene {
+            Some(scene) => self.child.set_viewport(scene.viewport()),
+            None => self.child.set_viewport(viewport),
+        }
+    }
+
+    fn render(&self) {
+        self.render_frame(None);
+    }
+
+    fn update(&mut self, ctx: &mut FrameContext) {
+        self.child.update(ctx);
+    }
+
+    fn render_with_context(&self, ctx: &FrameContext) {
+        self.render_frame(Some(ctx));
+    }
+}
+
+impl<R: Renderer> Drop for LightingRenderer<R> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+            gl::DeleteVertexArrays(1, &self.composite_vao);
+            gl::DeleteBuffers(1, &self.buffer);
+        }
+    }
+}
@@ -0,0 +1,364 @@
+use std::cell::Cell;
+
+use crate::Error;
+use gl;
+
+use crate::renderers::{
+    Renderer,
+    Viewport,
+    Transformable,
+    Mat4,
+    blend::{BlendMode, BlendState},
+};
+use crate::shader::ShaderProgram;
+
+mod segments;
+
+use segments::{Segments, FLOATS_PER_INSTANCE};
+
+const VCODE : &str = r#"
+#version 450 core
+layout (location = 0) in vec2 in_corner;
+layout (location = 1) in vec4 in_points;
+layout (location = 2) in vec4 in_color_a;
+layout (location = 3) in vec4 in_color_b;
+layout (location = 4) in vec4 in_dash;
+out vec4 v_color;
+out float v_across;
+out float v_distance;
+flat out float v_half_width;
+flat out vec2 v_dash;
+uniform mat4 u_transform;
+uniform vec2 u_viewport_size;
+uniform float u_width;
+uniform bool u_world_width;
+
+vec2 direction(vec2 from, vec2 to) {
+    vec2 d = to - from;
+    return dot(d, d) > 0.0 ? normalize(d) : vec2(1.0, 0.0);
+}
+
+void main() {
+    vec2 a = in_points.xy;
+    vec2 b = in_points.zw;
+    float half_width = u_width * 0.5;
+
+    if (u_world_width) {
+        vec2 dir = direction(a, b);
+        vec2 p = mix(a, b, in_corner.x) + vec2(-dir.y, dir.x) * in_corner.y * half_width;
+        gl_Position = u_transform * vec4(p, 0.0, 1.0);
+        v_across = in_corner.y * half_width;
+    } else {
+        // Offset in screen space, leaving a pixel on each side for anti-aliasing.
+        vec4 clip_a = u_transform * vec4(a, 0.0, 1.0);
+        vec4 clip_b = u_transform * vec4(b, 0.0, 1.0);
+        vec2 half_viewport = 0.5 * u_viewport_size;
+        vec2 screen_a = clip_a.xy / clip_a.w * half_viewport;
+        vec2 screen_b = clip_b.xy / clip_b.w * half_viewport;
+        vec2 dir = direction(screen_a, screen_b);
+        float extent = half_width + 1.0;
+        vec2 screen = mix(screen_a, screen_b, in_corner.x) + vec2(-dir.y, dir.x) * in_corner.y * extent;
+        vec4 clip = mix(clip_a, clip_b, in_corner.x);
+        gl_Position = vec4(screen / half_viewport * clip.w, clip.zw);
+        v_across = in_corner.y * extent;
+    }
+
+    v_color = mix(in_color_a, in_color_b, in_corner.x);
+    v_distance = mix(in_dash.z, in_dash.w, in_corner.x);
+    v_half_width = half_width;
+    v_dash = in_dash.xy;
+}
+"#;
+
+const FCODE : &str = r#"
+#version 450 core
+in vec4 v_color;
+in float v_across;
+in float v_distance;
+flat in float v_half_width;
+flat in vec2 v_dash;
+out vec4 fColor;
+layout (location = 1) out vec4 fNormal;
+
+void main() {
+    if (v_dash.x > 0.0 && v_dash.y > 0.0 && mod(v_distance, v_dash.x + v_dash.y) > v_dash.x) {
+        discard;
+    }
+
+    float pixel = max(fwidth(v_across), 1e-6);
+    float coverage = clamp((v_half_width - abs(v_across)) / pixel + 0.5, 0.0, 1.0);
+    fColor = vec4(v_color.rgb, v_color.a * coverage);
+
+    // A flat surface, for the scene of a lighting renderer with normal mapping.
+    fNormal = vec4(0.5, 0.5, 1.0, fColor.a);
+}
+"#;
+
+/// How thick lines are drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineWidth {
+    /// Constant on screen, in pixels, whatever the transform.
+    Pixels(f32),
+
+    /// In the same units as the line coordinates, so lines scale with the transform.
+    World(f32),
+}
+
+impl Default for LineWidth {
+    fn default() -> Self {
+        LineWidth::Pixels(1.0)
+    }
+}
+
+/// Dashes and gaps, measured along the line in the same units as the line coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dash {
+    pub dash: f32,
+    pub gap: f32,
+}
+
+impl Dash {
+    pub fn new(dash: f32, gap: f32) -> Self {
+        Self { dash, gap }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineVertex {
+    pub position: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl LineVertex {
+    pub fn new(position: [f32; 2], color: [f32; 4]) -> Self {
+        Self { position, color }
+    }
+}
+
+/// Draws colored lines for debugging and gizmos.
+///
+/// Like [`ShapeRenderer`](crate::renderers::shape_renderer::ShapeRenderer), lines are added
+/// in an immediate-mode style and drawn together, but their coordinates go through the
+/// transform, so a `LineRenderer` sharing a transform with other renderers draws on top of
+/// them in the same coordinates. Each segment is drawn as its own anti-aliased quad in a
+/// single instanced draw call. Segments are not joined at corners; use a `ShapeRenderer`
+/// for thick outlines.
+pub struct LineRenderer {
+    viewport: Viewport,
+    program: ShaderProgram,
+    vao: u32,
+    corner_buffer: u32,
+    instance_buffer: u32,
+
+    segments: Segments,
+
+    /// Number of segments the instance buffer has room for.
+    instance_capacity: Cell<usize>,
+    dirty: Cell<bool>,
+}
+
+impl LineRenderer {
+    pub fn new() -> Result<Self, Error> {
+        let program = ShaderProgram::new(VCODE, FCODE)?;
+
+        // x runs along the segment, y across it.
+        #[rustfmt::skip]
+        let corners: &[f32] = &[
+            0.0, -1.0,
+            1.0, -1.0,
+            1.0,  1.0,
+            0.0,  1.0,
+        ];
+
+        let corner_buffer = glh::create_buffer(corners, gl::STATIC_DRAW)?;
+        let mut instance_buffer = 0;
+        let mut vao = 0;
+        unsafe {
+            gl::CreateBuffers(1, &mut instance_buffer);
+            gl::GenVertexArrays(1, &mut vao);
+        }
+
+        glh::enable_interleaved_vertex_array_attributes(
+            vao,
+            corner_buffer,
+            gl::FLOAT,
+            false,
+            0,
+            &[2],
+        )?;
+
+        glh::enable_interleaved_vertex_array_attributes(
+            vao,
+            instance_buffer,
+            gl::FLOAT,
+            false,
+            1,
+            &[4, 4, 4, 4],
+        )?;
+
+        unsafe {
+            for index in 1..=4 {
+                gl::VertexAttribDivisor(index, 1);
+            }
+        }
+
+        let mut self_ = Self {
+            viewport: Viewport::default(),
+            program,
+            vao,
+            corner_buffer,
+            instance_buffer,
+            segments: Segments::default(),
+            instance_capacity: Cell::new(0),
+            dirty: Cell::new(false),
+        };
+
+        self_.set_width(LineWidth::default());
+        self_.clear_transform();
+
+        Ok(self_)
+    }
+
+    /// Sets the width of all lines. Defaults to one pixel.
+    pub fn set_width(&mut self, width: LineWidth) {
+        let (width, world) = match width {
+            LineWidth::Pixels(width) => (width, false),
+            LineWidth::World(width) => (width, true),
+        };
+
+        self.program.set_builtin("u_width", width);
+        self.program.set_builtin("u_world_width", world);
+    }
+
+    /// Sets the dash pattern for lines added from now on, or `None` for solid lines.
+    /// The pattern continues across the segments of a strip.
+    pub fn set_dash(&mut self, dash: Option<Dash>) {
+        self.segments.dash = dash;
+    }
+
+    /// Removes all lines.
+    pub fn clear(&mut self) {
+        self.segments.records.clear();
+        self.dirty.set(true);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.records.is_empty()
+    }
+
+    /// Gives access to the segments to add lines to. The lines are uploaded on the next render.
+    fn segments_mut(&mut self) -> &mut Segments {
+        self.dirty.set(true);
+        &mut self.segments
+    }
+
+    pub fn line(&mut self, from: [f32; 2], to: [f32; 2], color: [f32; 4]) {
+        self.segments_mut().line(from, to, color);
+    }
+
+    /// Draws a line with its color blended from one end to the other.
+    pub fn line_gradient(&mut self, from: LineVertex, to: LineVertex) {
+        self.segments_mut().line_gradient(from, to);
+    }
+
+    /// Draws a separate line between each pair of vertices. A last unpaired vertex is ignored.
+    pub fn line_list(&mut self, vertices: &[LineVertex]) {
+        self.segments_mut().line_list(vertices);
+    }
+
+    /// Draws connected lines through the vertices.
+    pub fn line_strip(&mut self, vertices: &[LineVertex]) {
+        self.segments_mut().line_strip(vertices);
+    }
+
+    /// Draws connected lines of a single color through the points.
+    pub fn polyline(&mut self, points: &[[f32; 2]], color: [f32; 4]) {
+        self.segments_mut().polyline(points, color);
+    }
+
+    /// Draws a line with an arrowhead at `to`. `head_size` is the length of the
+    /// arrowhead's sides, in line coordinates.
+    pub fn arrow(&mut self, from: [f32; 2], to: [f32; 2], head_size: f32, color: [f32; 4]) {
+        self.segments_mut().arrow(from, to, head_size, color);
+    }
+
+    /// Draws the lines of a grid of `cells[0]` by `cells[1]` cells, starting at `origin`.
+    /// A cell size can be negative to grow the grid downwards or to the left.
+    ///
+    /// To outline the tiles of a [`TilemapRenderer`](crate::renderers::tilemap_renderer::TilemapRenderer)
+    /// drawn with the same transform, use its map offset as the origin, its map tile size with
+    /// the y component negated as the cell size, and its map size as the cell count.
+    pub fn grid(&mut self, origin: [f32; 2], cell_size: [f32; 2], cells: [usize; 2], color: [f32; 4]) {
+        self.segments_mut().grid(origin, cell_size, cells, color);
+    }
+
+    /// Draws the x axis in red and the y axis in green, as arrows of the given length.
+    pub fn axes(&mut self, origin: [f32; 2], length: f32) {
+        self.segments_mut().axes(origin, length);
+    }
+
+    fn upload_segments(&self) {
+        let records = self.segments.records.as_slice();
+        let byte_len = std::mem::size_of_val(records);
+        unsafe {
+            if records.len() > self.instance_capacity.get() {
+                let capacity = records.len().next_power_of_two();
+                let capacity_bytes = capacity * FLOATS_PER_INSTANCE * std::mem::size_of::<f32>();
+                gl::NamedBufferData(
+                    self.instance_buffer,
+                    capacity_bytes as isize,
+                    std::ptr::null(),
+                    gl::DYNAMIC_DRAW,
+                );
+                self.instance_capacity.set(capacity);
+            }
+
+            gl::NamedBufferSubData(self.instance_buffer, 0, byte_len as isize, records.as_ptr() as *const _);
+        }
+
+        self.dirty.set(false);
+    }
+}
+
+impl Renderer for LineRenderer {
+    fn set_viewport(&mut self, viewport: Viewport) {
+        self.viewport = viewport;
+        self.program.set_builtin("u_viewport_size", [viewport.size[0] as f32, viewport.size[1] as f32]);
+    }
+
+    fn render(&self) {
+        if self.segments.records.is_empty() {
+            return;
+        }
+
+        if self.dirty.get() {
+            self.upload_segments();
+        }
+
+        self.viewport.gl_viewport();
+        let blend_state = BlendState::capture();
+        BlendMode::Alpha.apply();
+        self.program.use_program();
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::DrawArraysInstanced(gl::TRIANGLE_FAN, 0, 4, self.segments.records.len() as i32);
+        }
+        blend_state.restore();
+    }
+}
+
+impl Transformable for LineRenderer {
+    fn set_transform(&mut self, transform: Mat4) {
+        self.program.set_builtin("u_transform", transform);
+    }
+}
+
+impl Drop for LineRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+            gl::DeleteBuffers(1, &self.corner_buffer);
+            gl::DeleteBuffers(1, &self.instance_buffer);
+        }
+    }
+}
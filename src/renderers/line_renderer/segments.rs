@@ -0,0 +1,280 @@
+//! Building the per-segment instance records, without touching OpenGL.
+//!
+//! Each record holds both end points, both colors, the dash pattern and the distance along
+//! the line at each end, so dashes can continue from one segment of a strip to the next.
+
+use super::{Dash, LineVertex};
+
+pub(super) const FLOATS_PER_INSTANCE: usize = 16;
+
+pub(super) type Segment = [f32; FLOATS_PER_INSTANCE];
+
+#[derive(Debug, Default)]
+pub(super) struct Segments {
+    pub records: Vec<Segment>,
+
+    /// Dash pattern of segments added from now on.
+    pub dash: Option<Dash>,
+}
+
+impl Segments {
+    /// Adds a segment whose dashes start `start_distance` along the line, and returns the
+    /// distance at its end.
+    fn push(&mut self, a: LineVertex, b: LineVertex, start_distance: f32) -> f32 {
+        let length = ((b.position[0] - a.position[0]).powi(2) + (b.position[1] - a.position[1]).powi(2)).sqrt();
+        let end_distance = start_distance + length;
+        let (dash, gap) = self.dash.map_or((0.0, 0.0), |dash| (dash.dash, dash.gap));
+
+        self.records.push([
+            a.position[0], a.position[1], b.position[0], b.position[1],
+            a.color[0], a.color[1], a.color[2], a.color[3],
+            b.color[0], b.color[1], b.color[2], b.color[3],
+            dash, gap, start_distance, end_distance,
+        ]);
+
+        end_distance
+    }
+
+    pub fn line(&mut self, from: [f32; 2], to: [f32; 2], color: [f32; 4]) {
+        self.push(LineVertex::new(from, color), LineVertex::new(to, color), 0.0);
+    }
+
+    pub fn line_gradient(&mut self, from: LineVertex, to: LineVertex) {
+        self.push(from, to, 0.0);
+    }
+
+    pub fn line_list(&mut self, vertices: &[LineVertex]) {
+        for pair in vertices.chunks_exact(2) {
+            self.push(pair[0], pair[1], 0.0);
+        }
+    }
+
+    pub fn line_strip(&mut self, vertices: &[LineVertex]) {
+        let mut distance = 0.0;
+        for pair in vertices.windows(2) {
+            distance = self.push(pair[0], pair[1], distance);
+        }
+    }
+
+    pub fn polyline(&mut self, points: &[[f32; 2]], color: [f32; 4]) {
+        let mut distance = 0.0;
+        for pair in points.windows(2) {
+            distance = self.push(LineVertex::new(pair[0], color), LineVertex::new(pair[1], color), distance);
+        }
+    }
+
+    pub fn arrow(&mut self, from: [f32; 2], to: [f32; 2], head_size: f32, color: [f32; 4]) {
+        self.line(from, to, color);
+
+        let d = [to[0] - from[0], to[1] - from[1]];
+        let length = (d[0] * d[0] + d[1] * d[1]).sqrt();
+        if length <= 0.0 {
+            return;
+        }
+
+        // Sides at 30 degrees to the shaft.
+        let (sin, cos) = (0.5f32, 0.75f32.sqrt());
+        let back = [-d[0] / length * head_size, -d[1] / length * head_size];
+        for sign in [1.0, -1.0] {
+            let side = [
+                back[0] * cos - back[1] * sin * sign,
+                back[0] * sin * sign + back[1] * cos,
+            ];
+            self.line(to, [to[0] + side[0], to[1] + side[1]], color);
+        }
+    }
+
+    pub fn grid(&mut self, origin: [f32; 2], cell_size: [f32; 2], cells: [usize; 2], color: [f32; 4]) {
+        let end = [
+            origin[0] + cell_size[0] * cells[0] as f32,
+            origin[1] + cell_size[1] * cells[1] as f32,
+        ];
+
+        for i in 0..=cells[0] {
+            let x = origin[0] + cell_size[0] * i as f32;
+            self.line([x, origin[1]], [x, end[1]], color);
+        }
+
+        for i in 0..=cells[1] {
+            let y = origin[1] + cell_size[1] * i as f32;
+            self.line([origin[0], y], [end[0], y], color);
+        }
+    }
+
+    pub fn axes(&mut self, origin: [f32; 2], length: f32) {
+        let head_size = length * 0.15;
+        self.arrow(origin, [origin[0] + length, origin[1]], head_size, [1.0, 0.2, 0.2, 1.0]);
+        self.arrow(origin, [origin[0], origin[1] + length], head_size, [0.2, 1.0, 0.2, 1.0]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: [f32; 4] = [1.0; 4];
+
+    fn points(segment: &Segment) -> [[f32; 2]; 2] {
+        [[segment[0], segment[1]], [segment[2], segment[3]]]
+    }
+
+    fn distances(segment: &Segment) -> [f32; 2] {
+        [segment[14], segment[15]]
+    }
+
+    fn vertex(position: [f32; 2]) -> LineVertex {
+        LineVertex::new(position, WHITE)
+    }
+
+    #[test]
+    fn record_layout_matches_vertex_attributes() {
+        let mut segments = Segments { dash: Some(Dash::new(2.0, 1.0)), ..Segments::default() };
+        segments.line_gradient(
+            LineVertex::new([0.0, 0.0], [1.0, 0.0, 0.0, 1.0]),
+            LineVertex::new([3.0, 4.0], [0.0, 0.0, 1.0, 0.5]),
+        );
+
+        // in_points, in_color_a, in_color_b and in_dash, in order.
+        assert_eq!(
+            segments.records,
+            [[
+                0.0, 0.0, 3.0, 4.0,
+                1.0, 0.0, 0.0, 1.0,
+                0.0, 0.0, 1.0, 0.5,
+                2.0, 1.0, 0.0, 5.0,
+            ]]
+        );
+    }
+
+    #[test]
+    fn line_strip_carries_distance_over() {
+        let mut segments = Segments::default();
+        segments.line_strip(&[vertex([0.0, 0.0]), vertex([3.0, 4.0]), vertex([3.0, 10.0]), vertex([0.0, 10.0])]);
+
+        let distances: Vec<_> = segments.records.iter().map(distances).collect();
+        assert_eq!(distances, [[0.0, 5.0], [5.0, 11.0], [11.0, 14.0]]);
+    }
+
+    #[test]
+    fn polyline_carries_distance_over() {
+        let mut segments = Segments::default();
+        segments.polyline(&[[0.0, 0.0], [0.0, 2.0], [2.0, 2.0]], WHITE);
+
+        let distances: Vec<_> = segments.records.iter().map(distances).collect();
+        assert_eq!(distances, [[0.0, 2.0], [2.0, 4.0]]);
+    }
+
+    #[test]
+    fn separate_lines_start_their_dashes_over() {
+        let mut segments = Segments::default();
+        segments.line([0.0, 0.0], [0.0, 2.0], WHITE);
+        segments.line_list(&[vertex([0.0, 2.0]), vertex([2.0, 2.0]), vertex([2.0, 2.0]), vertex([2.0, 5.0])]);
+
+        let distances: Vec<_> = segments.records.iter().map(distances).collect();
+        assert_eq!(distances, [[0.0, 2.0], [0.0, 2.0], [0.0, 3.0]]);
+    }
+
+    #[test]
+    fn line_list_drops_unpaired_vertex() {
+        let mut segments = Segments::default();
+        segments.line_list(&[vertex([0.0, 0.0]), vertex([1.0, 0.0]), vertex([2.0, 0.0])]);
+
+        assert_eq!(segments.records.len(), 1);
+        assert_eq!(points(&segments.records[0]), [[0.0, 0.0], [1.0, 0.0]]);
+
+        segments.line_list(&[vertex([5.0, 5.0])]);
+        assert_eq!(segments.records.len(), 1);
+    }
+
+    #[test]
+    fn strips_need_two_vertices() {
+        let mut segments = Segments::default();
+        segments.line_strip(&[vertex([0.0, 0.0])]);
+        segments.polyline(&[], WHITE);
+        assert!(segments.records.is_empty());
+    }
+
+    #[test]
+    fn arrow_has_head_at_target() {
+        let mut segments = Segments::default();
+        segments.arrow([0.0, 0.0], [10.0, 0.0], 2.0, WHITE);
+
+        assert_eq!(segments.records.len(), 3);
+        for head in &segments.records[1..] {
+            let [from, to] = points(head);
+            assert_eq!(from, [10.0, 0.0]);
+            assert!(to[0] < 10.0);
+            assert!((distances(head)[1] - 2.0).abs() < 1e-5);
+        }
+        assert_eq!(points(&segments.records[1])[1][1], -points(&segments.records[2])[1][1]);
+    }
+
+    #[test]
+    fn arrow_with_zero_length_has_no_head() {
+        let mut segments = Segments::default();
+        segments.arrow([3.0, 3.0], [3.0, 3.0], 2.0, WHITE);
+
+        assert_eq!(segments.records.len(), 1);
+        assert_eq!(points(&segments.records[0]), [[3.0, 3.0], [3.0, 3.0]]);
+        assert_eq!(distances(&segments.records[0]), [0.0, 0.0]);
+        assert!(segments.records[0].iter().all(|value| value.is_finite()));
+    }
+
+    #[test]
+    fn grid_has_a_line_per_cell_edge() {
+        let mut segments = Segments::default();
+        segments.grid([0.0, 0.0], [1.0, 2.0], [3, 2], WHITE);
+
+        let lines: Vec<_> = segments.records.iter().map(points).collect();
+        assert_eq!(
+            lines,
+            [
+                [[0.0, 0.0], [0.0, 4.0]],
+                [[1.0, 0.0], [1.0, 4.0]],
+                [[2.0, 0.0], [2.0, 4.0]],
+                [[3.0, 0.0], [3.0, 4.0]],
+                [[0.0, 0.0], [3.0, 0.0]],
+                [[0.0, 2.0], [3.0, 2.0]],
+                [[0.0, 4.0], [3.0, 4.0]],
+            ]
+        );
+    }
+
+    #[test]
+    fn grid_with_negative_cell_size_grows_down_and_left() {
+        let mut segments = Segments::default();
+        segments.grid([10.0, 10.0], [-2.0, -3.0], [1, 2], WHITE);
+
+        let lines: Vec<_> = segments.records.iter().map(points).collect();
+        assert_eq!(
+            lines,
+            [
+                [[10.0, 10.0], [10.0, 4.0]],
+                [[8.0, 10.0], [8.0, 4.0]],
+                [[10.0, 10.0], [8.0, 10.0]],
+                [[10.0, 7.0], [8.0, 7.0]],
+                [[10.0, 4.0], [8.0, 4.0]],
+            ]
+        );
+    }
+
+    #[test]
+    fn grid_without_cells_is_a_point() {
+        let mut segments = Segments::default();
+        segments.grid([1.0, 1.0], [4.0, 4.0], [0, 0], WHITE);
+
+        let lines: Vec<_> = segments.records.iter().map(points).collect();
+        assert_eq!(lines, [[[1.0, 1.0], [1.0, 1.0]], [[1.0, 1.0], [1.0, 1.0]]]);
+    }
+
+    #[test]
+    fn dash_applies_to_segments_added_after_it() {
+        let mut segments = Segments::default();
+        segments.line([0.0, 0.0], [1.0, 0.0], WHITE);
+        segments.dash = Some(Dash::new(4.0, 2.0));
+        segments.line([0.0, 0.0], [1.0, 0.0], WHITE);
+
+        assert_eq!(segments.records[0][12..14], [0.0, 0.0]);
+        assert_eq!(segments.records[1][12..14], [4.0, 2.0]);
+    }
+}
@@ -0,0 +1,357 @@
+use gl::types::*;
+
+use crate::renderers::{
+    render_child,
+    FrameContext,
+    Renderer,
+    Viewport,
+    basic_renderers::{with_scissor, StencilState},
+    blend::{BlendMode, BlendState},
+    render_target::{RenderTarget, TextureUnitState},
+};
+use crate::shader::{ShaderProgram, TextureUnit};
+use crate::Error;
+
+const COMPOSITE_VCODE: &str = r#"
+#version 450 core
+out vec2 v_uv;
+
+void main() {
+    vec2 pos = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+    v_uv = pos;
+    gl_Position = vec4(pos * 2.0 - 1.0, 0.0, 1.0);
+}
+"#;
+
+const COMPOSITE_FCODE: &str = r#"
+#version 450 core
+in vec2 v_uv;
+out vec4 fColor;
+layout (location = 1) out vec4 fNormal;
+uniform sampler2D u_content;
+uniform sampler2D u_mask;
+uniform bool u_inverted;
+uniform float u_softness;
+
+// The mask's alpha, blurred over about u_softness pixels.
+float mask_alpha() {
+    if (u_softness <= 0.0) {
+        return texture(u_mask, v_uv).a;
+    }
+
+    vec2 spacing = u_softness / 4.0 / vec2(textureSize(u_mask, 0));
+    float total = 0.0;
+    float weights = 0.0;
+    for (int y = -4; y <= 4; y++) {
+        for (int x = -4; x <= 4; x++) {
+            float weight = exp(-float(x * x + y * y) / 8.0);
+            total += texture(u_mask, v_uv + vec2(x, y) * spacing).a * weight;
+            weights += weight;
+        }
+    }
+    return total / weights;
+}
+
+void main() {
+    float mask = mask_alpha();
+    if (u_inverted) {
+        mask = 1.0 - mask;
+    }
+    fColor = texture(u_content, v_uv) * mask;
+
+    // A flat surface, for the scene of a lighting renderer with normal mapping.
+    fNormal = vec4(vec3(0.5, 0.5, 1.0) * fColor.a, fColor.a);
+}
+"#;
+
+/// How a [`MaskRenderer`] turns what the mask renderer draws into a mask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MaskMode {
+    /// Every pixel the mask renderer draws is part of the mask, however transparent, so the
+    /// mask should be drawn with solid shapes, such as those of a
+    /// [`ShapeRenderer`](crate::renderers::shape_renderer::ShapeRenderer). The edges are hard.
+    /// The mask is marked in the stencil buffer, so the framebuffer needs one.
+    #[default]
+    Stencil,
+
+    /// The alpha of what the mask renderer draws is the mask, so partly transparent pixels
+    /// partly show the content, and the edges can be softened. Suits mask textures drawn with a
+    /// [`TextureRenderer`](crate::renderers::texture_renderer::TextureRenderer). The mask and
+    /// the content are drawn offscreen first.
+    Alpha,
+}
+
+/// Renders a renderer only where another one draws: the mask renderer is drawn as a mask,
+/// and the content renderer is drawn through it. The mask itself is never visible.
+///
+/// Stencil clips nest in both directions. Inside rounded
+/// [`InsetRenderer`](crate::renderers::basic_renderers::InsetRenderer) corners or another
+/// stencil mask, the content stays inside that clip as well. Rounded `InsetRenderer` corners
+/// and stencil masks in the content stay inside this mask.
+///
+/// With [`MaskMode::Alpha`], if the offscreen buffers can't be resized to a new viewport,
+/// nothing is drawn until a later viewport works, and the error is available from
+/// [`last_error`](Self::last_error).
+pub struct MaskRenderer<Mask: Renderer, Content: Renderer> {
+    viewport: Viewport,
+    mask: Mask,
+    content: Content,
+    inverted: bool,
+    softness: f32,
+    alpha_mask: Option<AlphaMask>,
+
+    /// Why the offscreen buffers couldn't be resized to the current viewport.
+    error: Option<String>,
+}
+
+/// What [`MaskMode::Alpha`] draws with.
+struct AlphaMask {
+    program: ShaderProgram,
+    vao: GLuint,
+    mask_target: RenderTarget,
+    content_target: RenderTarget,
+}
+
+impl AlphaMask {
+    fn new(size: [i32; 2]) -> Result<Self, Error> {
+        let program = ShaderProgram::new(COMPOSITE_VCODE, COMPOSITE_FCODE)?;
+        program.set_builtin("u_content", TextureUnit(0));
+        program.set_builtin("u_mask", TextureUnit(1));
+
+        let mut vao = 0;
+        unsafe {
+            gl::CreateVertexArrays(1, &mut vao);
+        }
+
+        Ok(Self {
+            program,
+            vao,
+            mask_target: RenderTarget::with_depth_stencil(size)?,
+            content_target: RenderTarget::with_depth_stencil(size)?,
+        })
+    }
+}
+
+impl Drop for AlphaMask {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
+
+impl<Mask: Renderer, Content: Renderer> MaskRenderer<Mask, Content> {
+    /// Creates a renderer with a [`MaskMode::Stencil`] mask.
+    pub fn new(mask: Mask, content: Content) -> Self {
+        Self {
+            viewport: Viewport::default(),
+            mask,
+            content,
+            inverted: false,
+            softness: 0.0,
+            alpha_mask: None,
+            error: None,
+        }
+    }
+
+    pub fn get_mask(&self) -> &Mask {
+        &self.mask
+    }
+
+    pub fn get_content(&self) -> &Content {
+        &self.content
+    }
+
+    pub fn get_mask_mut(&mut self) -> &mut Mask {
+        &mut self.mask
+    }
+
+    pub fn get_content_mut(&mut self) -> &mut Content {
+        &mut self.content
+    }
+
+    /// Changes how the mask is made. With [`MaskMode::Alpha`], the mask and the content
+    /// renderers draw into offscreen targets, and their viewports cover those instead of
+    /// the screen.
+    pub fn set_mode(&mut self, mode: MaskMode) -> Result<(), Error> {
+        if mode == self.mode() {
+            return Ok(());
+        }
+
+        self.alpha_mask = match mode {
+            MaskMode::Stencil => None,
+            MaskMode::Alpha => Some(AlphaMask::new(self.viewport.size)?),
+        };
+        self.error = None;
+        self.reset_subrenderer_viewports();
+        Ok(())
+    }
+
+    pub fn mode(&self) -> MaskMode {
+        match self.alpha_mask {
+            Some(_) => MaskMode::Alpha,
+            None => MaskMode::Stencil,
+        }
+    }
+
+    /// Draws the content everywhere except where the mask is, such as around a revealed area.
+    pub fn set_inverted(&mut self, inverted: bool) {
+        self.inverted = inverted;
+    }
+
+    pub fn inverted(&self) -> bool {
+        self.inverted
+    }
+
+    /// Blurs the edges of the mask over about `softness` pixels. Only has an effect with
+    /// [`MaskMode::Alpha`]; stencil masks always have hard edges.
+    pub fn set_softness(&mut self, softness: f32) {
+        self.softness = softness.max(0.0);
+    }
+
+    pub fn softness(&self) -> f32 {
+        self.softness
+    }
+
+    /// Why the offscreen buffers of [`MaskMode::Alpha`] couldn't be resized to the current
+    /// viewport, if they couldn't. Nothing is drawn until this is cleared by a viewport that
+    /// works, or by going back to [`MaskMode::Stencil`].
+    pub fn last_error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    fn reset_subrenderer_viewports(&mut self) {
+        let viewport = match self.alpha_mask {
+            Some(ref alpha_mask) => alpha_mask.mask_target.viewport(),
+            None => self.viewport,
+        };
+
+        self.mask.set_viewport(viewport);
+        self.content.set_viewport(viewport);
+    }
+
+    fn render_parts(&self, ctx: Option<&FrameContext>) {
+        if self.error.is_some() {
+            return;
+        }
+
+        match self.alpha_mask {
+            Some(ref alpha_mask) => self.render_alpha_mask(alpha_mask, ctx),
+            None => self.render_stencil_mask(ctx),
+        }
+    }
+
+    /// Marks the mask by incrementing the stencil buffer where it draws, within the clip in
+    /// effect if there is one, and draws the content where the stencil buffer was, or wasn't,
+    /// incremented. The increments are undone afterwards, for the enclosing clip.
+    fn render_stencil_mask(&self, ctx: Option<&FrameContext>) {
+        let stencil_state = StencilState::capture();
+        let clip = stencil_state.clip_reference();
+        let base = clip.unwrap_or(0);
+
+        unsafe {
+            gl::StencilMask(0xFF);
+            if clip.is_none() {
+                with_scissor(self.viewport, || gl::ClearBufferiv(gl::STENCIL, 0, &0));
+            }
+
+            gl::Enable(gl::STENCIL_TEST);
+            gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+            gl::StencilFunc(gl::EQUAL, base, 0xFF);
+            gl::StencilOp(gl::KEEP, gl::KEEP, gl::INCR);
+        }
+        render_child(&self.mask, ctx);
+
+        unsafe {
+            gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+            gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
+            gl::StencilFunc(gl::EQUAL, if self.inverted { base } else { base + 1 }, 0xFF);
+        }
+        render_child(&self.content, ctx);
+
+        if clip.is_some() {
+            unsafe {
+                gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+                gl::StencilFunc(gl::EQUAL, base + 1, 0xFF);
+                gl::StencilOp(gl::KEEP, gl::KEEP, gl::DECR);
+            }
+            render_child(&self.mask, ctx);
+            unsafe {
+                gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+            }
+        }
+
+        stencil_state.restore();
+    }
+
+    /// Draws the mask and the content offscreen, then the content onto the screen with its
+    /// alpha multiplied by the mask's.
+    fn render_alpha_mask(&self, alpha_mask: &AlphaMask, ctx: Option<&FrameContext>) {
+        if self.viewport.is_empty() {
+            return;
+        }
+
+        for (target, renderer) in [
+            (&alpha_mask.mask_target, &self.mask as &dyn Renderer),
+            (&alpha_mask.content_target, &self.content as &dyn Renderer),
+        ] {
+            target.draw_into(|| {
+                target.clear([0.0; 4]);
+                render_child(renderer, ctx);
+            });
+        }
+
+        let blend_state = BlendState::capture();
+        let texture_units = [TextureUnitState::capture(0), TextureUnitState::capture(1)];
+
+        // The targets were cleared to transparent, so the content is premultiplied.
+        self.viewport.gl_viewport();
+        BlendMode::PremultipliedAlpha.apply();
+        TextureUnitState::bind(0, alpha_mask.content_target.texture());
+        TextureUnitState::bind(1, alpha_mask.mask_target.texture());
+
+        let program = &alpha_mask.program;
+        program.set_builtin("u_inverted", self.inverted);
+        program.set_builtin("u_softness", self.softness);
+        program.use_program();
+        unsafe {
+            gl::BindVertexArray(alpha_mask.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+
+        for texture_unit in texture_units {
+            texture_unit.restore();
+        }
+        blend_state.restore();
+    }
+}
+
+impl<Mask: Renderer, Content: Renderer> Renderer for MaskRenderer<Mask, Content> {
+    fn set_viewport(&mut self, viewport: Viewport) {
+        self.viewport = viewport;
+        if let Some(ref mut alpha_mask) = self.alpha_mask
+            && !viewport.is_empty()
+        {
+            self.error = [&mut alpha_mask.mask_target, &mut alpha_mask.content_target]
+                .into_iter()
+                .try_for_each(|target| target.resize(viewport.size))
+                .err()
+                .map(|e| format!("MaskRenderer::set_viewport: {}", e));
+        }
+
+        self.reset_subrenderer_viewports();
+    }
+
+    fn render(&self) {
+        self.render_parts(None);
+    }
+
+    fn update(&mut self, ctx: &mut FrameContext) {
+        self.mask.update(ctx);
+        self.content.update(ctx);
+    }
+
+    fn render_with_context(&self, ctx: &FrameContext) {
+        self.render_parts(Some(ctx));
+    }
+}
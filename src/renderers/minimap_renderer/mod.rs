@@ -0,0 +1,494 @@
+use std::cell::{Cell, RefCell};
+
+use gl::types::*;
+
+use crate::renderers::{
+    Renderer,
+    Viewport,
+    basic_renderers::with_scissor,
+    blend::{BlendMode, BlendState},
+    render_target::TextureUnitState,
+    shape_renderer::ShapeRenderer,
+};
+use crate::shader::{ShaderProgram, TextureUnit};
+use crate::Error;
+
+const VCODE: &str = r#"
+#version 450 core
+out vec2 v_uv;
+
+void main() {
+    vec2 pos = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+    // The first row of the map is at the top.
+    v_uv = vec2(pos.x, 1.0 - pos.y);
+    gl_Position = vec4(pos * 2.0 - 1.0, 0.0, 1.0);
+}
+"#;
+
+const FCODE: &str = r#"
+#version 450 core
+in vec2 v_uv;
+out vec4 fColor;
+layout (location = 1) out vec4 fNormal;
+uniform sampler2D u_map;
+
+void main() {
+    fColor = texture(u_map, v_uv);
+
+    // A flat surface, for the scene of a lighting renderer with normal mapping.
+    fNormal = vec4(0.5, 0.5, 1.0, fColor.a);
+}
+"#;
+
+/// A dot on a [`MinimapRenderer`], such as a unit or a point of interest.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinimapMarker {
+    /// Position in tiles from the top-left corner of the map, so the center of the top-left
+    /// tile is `[0.5, 0.5]`.
+    pub position: [f32; 2],
+    pub color: [f32; 4],
+
+    /// Width and height of the dot in pixels.
+    pub size: f32,
+}
+
+impl MinimapMarker {
+    pub fn new(position: [f32; 2], color: [f32; 4]) -> Self {
+        Self {
+            position,
+            color,
+            size: 3.0,
+        }
+    }
+
+    pub fn with_size(mut self, size: f32) -> Self {
+        self.size = size;
+        self
+    }
+}
+
+/// The outline of the part of the map in view, on a [`MinimapRenderer`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraRect {
+    /// Top-left corner in tiles from the top-left corner of the map.
+    pub pos: [f32; 2],
+
+    /// Size in tiles.
+    pub size: [f32; 2],
+
+    pub color: [f32; 4],
+
+    /// Thickness of the outline in pixels.
+    pub thickness: f32,
+}
+
+impl CameraRect {
+    pub fn new(pos: [f32; 2], size: [f32; 2]) -> Self {
+        Self {
+            pos,
+            size,
+            color: [1.0, 1.0, 1.0, 1.0],
+            thickness: 1.0,
+        }
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_thickness(mut self, thickness: f32) -> Self {
+        self.thickness = thickness;
+        self
+    }
+}
+
+/// Draws an overview of a tilemap, one color per tile, with markers and the camera's
+/// rectangle on top.
+///
+/// The map is given like a [`TilemapRenderer`](crate::renderers::tilemap_renderer::TilemapRenderer)
+/// map, as tile indices row by row starting from the top, together with a table of the color
+/// of each tile index. It is kept in a texture with one texel per tile, so changing tiles only
+/// uploads those tiles. The map is centered in the viewport and, unless
+/// [`set_tile_pixels`](Self::set_tile_pixels) is used, scaled to fit it, keeping its aspect ratio.
+pub struct MinimapRenderer {
+    layout: MapLayout,
+    program: ShaderProgram,
+    vao: GLuint,
+    texture: GLuint,
+
+    map_size: [usize; 2],
+    tile_indices: Vec<u16>,
+    colors: Vec<[u8; 4]>,
+    tile_pixels: Option<i32>,
+
+    markers: Vec<MinimapMarker>,
+    camera: Option<CameraRect>,
+
+    /// The camera rectangle and the markers, rebuilt before rendering after they change.
+    overlay: RefCell<ShapeRenderer>,
+    overlay_dirty: Cell<bool>,
+}
+
+impl MinimapRenderer {
+    /// Creates a minimap of `map_size` tiles. `colors` holds the color of each tile index;
+    /// tiles with indices past its end are transparent.
+    pub fn new(map_size: [usize; 2], tile_indices: &[u16], colors: &[[u8; 4]]) -> Result<Self, Error> {
+        if map_size[0] == 0 || map_size[1] == 0 {
+            return Err(format!("MinimapRenderer::new: Invalid map size {:?}", map_size).into());
+        }
+        if tile_indices.len() != map_size[0] * map_size[1] {
+            return Err(format!(
+                "MinimapRenderer::new: {} tile indices don't match the map size {:?}",
+                tile_indices.len(), map_size
+            ).into());
+        }
+
+        let program = ShaderProgram::new(VCODE, FCODE)?;
+        program.set_builtin("u_map", TextureUnit(0));
+
+        let mut vao = 0;
+        let mut texture = 0;
+        unsafe {
+            gl::CreateVertexArrays(1, &mut vao);
+            gl::CreateTextures(gl::TEXTURE_2D, 1, &mut texture);
+            gl::TextureStorage2D(texture, 1, gl::RGBA8, map_size[0] as GLsizei, map_size[1] as GLsizei);
+            gl::TextureParameteri(texture, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+            gl::TextureParameteri(texture, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+            gl::TextureParameteri(texture, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TextureParameteri(texture, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+        }
+
+        let minimap = Self {
+            layout: MapLayout::new(Viewport::default(), map_size, None),
+            program,
+            vao,
+            texture,
+            map_size,
+            tile_indices: tile_indices.to_vec(),
+            colors: colors.to_vec(),
+            tile_pixels: None,
+            markers: Vec::new(),
+            camera: None,
+            overlay: RefCell::new(ShapeRenderer::new()?),
+            overlay_dirty: Cell::new(false),
+        };
+
+        minimap.upload_all();
+        Ok(minimap)
+    }
+
+    pub fn map_size(&self) -> [usize; 2] {
+        self.map_size
+    }
+
+    pub fn tile_indices(&self) -> &[u16] {
+        &self.tile_indices
+    }
+
+    /// Changes the tile at `[column, row]`, uploading only that tile.
+    pub fn set_tile(&mut self, tile: [usize; 2], tile_index: u16) -> Result<(), Error> {
+        let [x, y] = tile;
+        if x >= self.map_size[0] || y >= self.map_size[1] {
+            return Err(format!("MinimapRenderer::set_tile: Tile {:?} is outside the map", tile).into());
+        }
+
+        self.tile_indices[y * self.map_size[0] + x] = tile_index;
+        let color = self.color(tile_index);
+        unsafe {
+            gl::TextureSubImage2D(
+                self.texture,
+                0,
+                x as GLint,
+                y as GLint,
+                1,
+                1,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                color.as_ptr() as *const _,
+            );
+        }
+        Ok(())
+    }
+
+    /// Replaces every tile.
+    pub fn set_tiles(&mut self, tile_indices: &[u16]) -> Result<(), Error> {
+        if tile_indices.len() != self.tile_indices.len() {
+            return Err(format!(
+                "MinimapRenderer::set_tiles: {} tile indices don't match the map size {:?}",
+                tile_indices.len(), self.map_size
+            ).into());
+        }
+
+        self.tile_indices.copy_from_slice(tile_indices);
+        self.upload_all();
+        Ok(())
+    }
+
+    pub fn colors(&self) -> &[[u8; 4]] {
+        &self.colors
+    }
+
+    /// Replaces the color table.
+    pub fn set_colors(&mut self, colors: &[[u8; 4]]) {
+        self.colors.clear();
+        self.colors.extend_from_slice(colors);
+        self.upload_all();
+    }
+
+    /// Draws each tile as a block of `pixels` by `pixels`, centered in the viewport and cut
+    /// off at its edges if it doesn't fit, or scales the map to fit the viewport with `None`,
+    /// which is the default.
+    pub fn set_tile_pixels(&mut self, pixels: Option<i32>) {
+        self.tile_pixels = pixels.map(|pixels| pixels.max(1));
+        self.update_layout(self.layout.viewport);
+    }
+
+    pub fn tile_pixels(&self) -> Option<i32> {
+        self.tile_pixels
+    }
+
+    pub fn markers(&self) -> &[MinimapMarker] {
+        &self.markers
+    }
+
+    pub fn set_markers(&mut self, markers: &[MinimapMarker]) {
+        self.markers.clear();
+        self.markers.extend_from_slice(markers);
+        self.overlay_dirty.set(true);
+    }
+
+    pub fn push_marker(&mut self, marker: MinimapMarker) {
+        self.markers.push(marker);
+        self.overlay_dirty.set(true);
+    }
+
+    pub fn clear_markers(&mut self) {
+        self.markers.clear();
+        self.overlay_dirty.set(true);
+    }
+
+    /// Sets the rectangle showing the part of the map in view, or removes it with `None`.
+    pub fn set_camera(&mut self, camera: Option<CameraRect>) {
+        self.camera = camera;
+        self.overlay_dirty.set(true);
+    }
+
+    pub fn camera(&self) -> Option<CameraRect> {
+        self.camera
+    }
+
+    /// The position in tiles from the top-left corner of the map of a point in window pixels,
+    /// such as a click to move the camera, or `None` if the point is outside the map.
+    pub fn tile_at(&self, point: [i32; 2]) -> Option<[f32; 2]> {
+        self.layout.tile_at(point)
+    }
+
+    /// The color of a tile index.
+    fn color(&self, tile_index: u16) -> [u8; 4] {
+        self.colors.get(tile_index as usize).copied().unwrap_or([0; 4])
+    }
+
+    fn upload_all(&self) {
+        let texels: Vec<[u8; 4]> = self.tile_indices.iter().map(|&index| self.color(index)).collect();
+        unsafe {
+            gl::TextureSubImage2D(
+                self.texture,
+                0,
+                0,
+                0,
+                self.map_size[0] as GLsizei,
+                self.map_size[1] as GLsizei,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                texels.as_ptr() as *const _,
+            );
+        }
+    }
+
+    fn update_layout(&mut self, viewport: Viewport) {
+        self.layout = MapLayout::new(viewport, self.map_size, self.tile_pixels);
+        self.overlay.get_mut().set_viewport(self.layout.map);
+        self.overlay_dirty.set(true);
+    }
+
+    /// Adds the camera rectangle and the markers to the overlay, in pixels within the map.
+    fn rebuild_overlay(&self, overlay: &mut ShapeRenderer) {
+        let scale = self.layout.scale();
+
+        overlay.clear();
+        if let Some(camera) = self.camera {
+            let [x, top] = self.layout.pixel_position(camera.pos);
+            let size = [camera.size[0] * scale[0], camera.size[1] * scale[1]];
+            overlay.stroke_rect([x, top - size[1]], size, camera.thickness, camera.color);
+        }
+
+        for marker in &self.markers {
+            let [x, y] = self.layout.pixel_position(marker.position);
+            let half = marker.size * 0.5;
+            overlay.fill_rect([x - half, y - half], [marker.size, marker.size], marker.color);
+        }
+    }
+}
+
+/// Where the map is drawn within the viewport, and the conversions between tiles and pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MapLayout {
+    viewport: Viewport,
+
+    /// The whole map, which may overhang the viewport.
+    map: Viewport,
+    map_size: [usize; 2],
+}
+
+impl MapLayout {
+    /// Centers the map in the viewport, with tiles of `tile_pixels` or scaled to fit with `None`.
+    fn new(viewport: Viewport, map_size: [usize; 2], tile_pixels: Option<i32>) -> Self {
+        let [columns, rows] = map_size.map(|tiles| tiles as i32);
+        let map = match tile_pixels {
+            Some(pixels) => viewport.place([columns * pixels, rows * pixels], [0.5, 0.5]),
+            None => viewport.aspect_fit(columns as f32 / rows as f32),
+        };
+
+        Self { viewport, map, map_size }
+    }
+
+    /// Pixels per tile on screen.
+    fn scale(&self) -> [f32; 2] {
+        [
+            self.map.size[0] as f32 / self.map_size[0] as f32,
+            self.map.size[1] as f32 / self.map_size[1] as f32,
+        ]
+    }
+
+    /// A position in tiles from the top-left corner of the map, in pixels from the bottom-left
+    /// corner of the map.
+    fn pixel_position(&self, [x, y]: [f32; 2]) -> [f32; 2] {
+        let scale = self.scale();
+        [x * scale[0], self.map.size[1] as f32 - y * scale[1]]
+    }
+
+    /// The position in tiles of the center of a window pixel, if it is on the visible map.
+    fn tile_at(&self, point: [i32; 2]) -> Option<[f32; 2]> {
+        if !self.map.contains(point) || !self.viewport.contains(point) {
+            return None;
+        }
+
+        let scale = self.scale();
+        let x = (point[0] - self.map.pos[0]) as f32 + 0.5;
+        let y = (self.map.top() - point[1]) as f32 - 0.5;
+        Some([x / scale[0], y / scale[1]])
+    }
+}
+
+impl Drop for MinimapRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+            gl::DeleteTextures(1, &self.texture);
+        }
+    }
+}
+
+impl Renderer for MinimapRenderer {
+    fn set_viewport(&mut self, viewport: Viewport) {
+        self.update_layout(viewport);
+    }
+
+    fn render(&self) {
+        // Markers are cut off at the edge of the map, and the map at the edge of the viewport.
+        let Some(clip) = self.layout.map.intersect(&self.layout.viewport) else {
+            return;
+        };
+
+        with_scissor(clip, || self.render_map());
+    }
+}
+
+impl MinimapRenderer {
+    fn render_map(&self) {
+        let blend_state = BlendState::capture();
+        let texture_unit = TextureUnitState::capture(0);
+
+        self.layout.map.gl_viewport();
+        BlendMode::Alpha.apply();
+        TextureUnitState::bind(0, self.texture);
+        self.program.use_program();
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+
+        texture_unit.restore();
+        blend_state.restore();
+
+        if self.overlay_dirty.replace(false) {
+            self.rebuild_overlay(&mut self.overlay.borrow_mut());
+        }
+        self.overlay.borrow().render();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fits_the_map_into_the_viewport() {
+        let layout = MapLayout::new(Viewport::new([10, 20], [200, 100]), [4, 2], None);
+        assert_eq!(layout.map, Viewport::new([10, 20], [200, 100]));
+        assert_eq!(layout.scale(), [50.0, 50.0]);
+
+        // A square map in a wide viewport is centered horizontally.
+        let layout = MapLayout::new(Viewport::new([0, 0], [300, 100]), [8, 8], None);
+        assert_eq!(layout.map, Viewport::new([100, 0], [100, 100]));
+        assert_eq!(layout.scale(), [12.5, 12.5]);
+    }
+
+    #[test]
+    fn centers_fixed_size_tiles() {
+        let layout = MapLayout::new(Viewport::new([0, 0], [100, 100]), [4, 2], Some(10));
+        assert_eq!(layout.map, Viewport::new([30, 40], [40, 20]));
+        assert_eq!(layout.scale(), [10.0, 10.0]);
+
+        // Too big to fit, so it overhangs on both sides.
+        let layout = MapLayout::new(Viewport::new([0, 0], [100, 100]), [30, 2], Some(5));
+        assert_eq!(layout.map, Viewport::new([-25, 45], [150, 10]));
+    }
+
+    #[test]
+    fn converts_tiles_to_pixels_from_the_top() {
+        let layout = MapLayout::new(Viewport::new([0, 0], [40, 20]), [4, 2], None);
+        assert_eq!(layout.pixel_position([0.0, 0.0]), [0.0, 20.0]);
+        assert_eq!(layout.pixel_position([0.5, 0.5]), [5.0, 15.0]);
+        assert_eq!(layout.pixel_position([4.0, 2.0]), [40.0, 0.0]);
+    }
+
+    #[test]
+    fn finds_the_tile_under_a_pixel() {
+        let layout = MapLayout::new(Viewport::new([100, 50], [40, 20]), [4, 2], None);
+
+        // The top-left pixel, and the bottom-right one.
+        assert_eq!(layout.tile_at([100, 69]), Some([0.05, 0.05]));
+        assert_eq!(layout.tile_at([139, 50]), Some([3.95, 1.95]));
+
+        assert_eq!(layout.tile_at([125, 55]), Some([2.55, 1.45]));
+        assert_eq!(layout.tile_at([99, 60]), None);
+        assert_eq!(layout.tile_at([140, 60]), None);
+        assert_eq!(layout.tile_at([120, 70]), None);
+        assert_eq!(layout.tile_at([120, 49]), None);
+    }
+
+    #[test]
+    fn ignores_the_map_outside_the_viewport() {
+        let layout = MapLayout::new(Viewport::new([0, 0], [100, 100]), [30, 2], Some(5));
+        assert_eq!(layout.tile_at([-10, 50]), None);
+        assert_eq!(layout.tile_at([0, 50]), Some([5.1, 0.9]));
+    }
+
+    #[test]
+    fn empty_viewports_have_no_tiles() {
+        let layout = MapLayout::new(Viewport::default(), [4, 2], None);
+        assert_eq!(layout.tile_at([0, 0]), None);
+    }
+}
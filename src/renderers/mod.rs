@@ -1,73 +1,58 @@
+pub mod animation;
 pub mod basic_renderers;
+pub mod blend;
+pub mod context;
+pub mod lighting_renderer;
+pub mod line_renderer;
+pub mod mask_renderer;
+pub mod minimap_renderer;
+pub mod nine_slice_renderer;
+pub mod palette;
+pub mod particle_renderer;
+pub mod post_process_renderer;
+pub mod render_target;
+pub mod sampler;
+pub mod shape_renderer;
+pub mod sprite_batch;
 pub mod system_text;
 pub mod texture_renderer;
 pub mod tilemap_renderer;
+mod viewport;
 
-use crate::gl;
+pub use context::{FrameClock, FrameContext, ResourceCache};
+pub use viewport::Viewport;
 
 use nalgebra::Matrix4;
 pub type Mat4 = Matrix4<f32>;
 
+pub trait Renderer {
+    fn set_viewport(&mut self, viewport: Viewport);
+    fn render(&self);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct Viewport {
-    pub pos: [i32; 2],
-    pub size: [i32; 2],
-}
-
-impl Viewport {
-    pub fn new(pos: [i32; 2], size: [i32; 2]) -> Self {
-        Self { pos, size }
-    }
-
-    pub fn gl_viewport(&self) {
-        unsafe {
-            gl::Viewport(self.pos[0], self.pos[1], self.size[0], self.size[1]);
-        }
-    }
-}
-
-impl Default for Viewport {
-    fn default() -> Self {
-        Self::new([0, 0], [0, 0])
-    }
-}
-
-impl From<[[i32; 2]; 2]> for Viewport {
-    fn from(arr: [[i32; 2]; 2]) -> Self {
-        Self::new(arr[0], arr[1])
-    }
-}
-
-impl From<[i32; 4]> for Viewport {
-    fn from(arr: [i32; 4]) -> Self {
-        Self::new([arr[0], arr[1]], [arr[2], arr[3]])
-    }
-}
+    /// Advances time-based state, such as layout animations, by `ctx.delta_time` seconds.
+    /// Called once per frame, before rendering. This is also the place to load shared
+    /// resources into `ctx.resources`, since rendering only sees the context immutably.
+    /// Renderers that contain other renderers should forward this to them.
+    #[allow(unused_variables)]
+    fn update(&mut self, ctx: &mut FrameContext) {}
 
-impl From<(i32, i32, i32, i32)> for Viewport {
-    fn from(arr: (i32, i32, i32, i32)) -> Self {
-        Self::new([arr.0, arr.1], [arr.2, arr.3])
+    /// Renders with access to the frame context.
+    /// The default implementation ignores the context and calls `render`.
+    /// Renderers that contain other renderers should forward the context to them.
+    #[allow(unused_variables)]
+    fn render_with_context(&self, ctx: &FrameContext) {
+        self.render();
     }
 }
 
-impl From<[i32;2]> for Viewport {
-    fn from(arr: [i32;2]) -> Self {
-        Self::new([0, 0], arr)
+/// Renders a child renderer, passing the frame context along if there is one.
+pub(crate) fn render_child<R: Renderer + ?Sized>(renderer: &R, ctx: Option<&FrameContext>) {
+    match ctx {
+        Some(ctx) => renderer.render_with_context(ctx),
+        None => renderer.render(),
     }
 }
 
-impl From<(i32, i32)> for Viewport {
-    fn from(arr: (i32, i32)) -> Self {
-        Self::new([0, 0], [arr.0, arr.1])
-    }
-}
-
-pub trait Renderer {
-    fn set_viewport(&mut self, viewport: Viewport);
-    fn render(&self);
-}
-
 pub trait Transformable {
     fn set_transform(&mut self, transform: Mat4);
 
@@ -0,0 +1,543 @@
+use crate::Error;
+use gl;
+use gl::types::*;
+
+use crate::renderers::{
+    Renderer,
+    Viewport,
+    basic_renderers::Insets,
+    blend::{BlendMode, BlendState},
+};
+use crate::shader::{ShaderProgram, TextureUnit};
+
+const VCODE : &str = r#"
+#version 450 core
+layout (location = 0) in vec2 in_pos;
+layout (location = 1) in vec2 in_uv;
+out vec2 v_uv;
+
+void main() {
+    gl_Position = vec4(in_pos, 0.0, 1.0);
+    v_uv = in_uv;
+}
+"#;
+
+const FCODE : &str = r#"
+#version 450 core
+in vec2 v_uv;
+out vec4 fColor;
+layout (location = 1) out vec4 fNormal;
+uniform sampler2D u_tex1;
+uniform vec4 u_tint;
+uniform float u_opacity;
+
+void main() {
+    vec4 color = texture(u_tex1, v_uv) * u_tint;
+    color.a *= u_opacity;
+    fColor = color;
+
+    // A flat surface, for the scene of a lighting renderer with normal mapping.
+    fNormal = vec4(0.5, 0.5, 1.0, color.a);
+}
+"#;
+
+/// How the edges or the center of a nine-slice fill the space between the corners.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SliceMode {
+    /// Scaled to fit.
+    #[default]
+    Stretch,
+
+    /// Repeated at the border scale, starting from the top-left. The last tile is cut off.
+    Tile,
+}
+
+/// Draws a texture as a resizable panel: the corners keep their size, the edges and the
+/// center are stretched or tiled to fill the rest of the viewport.
+///
+/// The geometry is rebuilt whenever the viewport changes, so the renderer can be used
+/// directly inside layout renderers such as
+/// [`InsetRenderer`](crate::renderers::basic_renderers::InsetRenderer). Like
+/// [`TextureRenderer`](crate::renderers::texture_renderer::TextureRenderer), the texture must
+/// be bound to the configured texture unit before rendering.
+pub struct NineSliceRenderer {
+    viewport: Viewport,
+    program: ShaderProgram,
+    vao: u32,
+    buffer: u32,
+    vertex_count: i32,
+    slice: NineSlice,
+}
+
+impl NineSliceRenderer {
+    /// `texture_size` is the size of the texture in texels. `borders` are measured in texels
+    /// of the source region, or as ratios of its size.
+    pub fn new<I: Into<Insets>>(texture_size: [i32; 2], borders: I) -> Result<Self, Error> {
+        if texture_size[0] <= 0 || texture_size[1] <= 0 {
+            return Err(format!("NineSliceRenderer::new: Invalid texture size {:?}", texture_size).into());
+        }
+
+        let program = ShaderProgram::new(VCODE, FCODE)?;
+
+        let mut buffer = 0;
+        let mut vao = 0;
+        unsafe {
+            gl::CreateBuffers(1, &mut buffer);
+            gl::GenVertexArrays(1, &mut vao);
+        }
+
+        glh::enable_interleaved_vertex_array_attributes(
+            vao,
+            buffer,
+            gl::FLOAT,
+            false,
+            0,
+            &[2, 2],
+        )?;
+
+        let mut self_ = Self {
+            viewport: Viewport::default(),
+            program,
+            vao,
+            buffer,
+            vertex_count: 0,
+            slice: NineSlice {
+                texture_size,
+                source_rect: [0.0, 0.0, 1.0, 1.0],
+                borders: borders.into(),
+                border_scale: 1.0,
+                edge_mode: SliceMode::Stretch,
+                center_mode: SliceMode::Stretch,
+            },
+        };
+
+        self_.set_texture_unit(0);
+        self_.set_tint([1.0, 1.0, 1.0, 1.0]);
+        self_.set_opacity(1.0);
+
+        Ok(self_)
+    }
+
+    /// Sets the texture unit to sample from. The texture must be bound
+    /// to that unit separately, before rendering.
+    pub fn set_texture_unit(&mut self, texture_unit: GLint) {
+        self.program.set_builtin("u_tex1", TextureUnit(texture_unit));
+    }
+
+    /// Uses only part of the texture, given as `[u, v, width, height]` in texture
+    /// coordinates, for example an atlas region from
+    /// [`AtlasRegion::uv_rect`](crate::texture_atlas::AtlasRegion::uv_rect).
+    pub fn set_source_rect(&mut self, rect: [f32; 4]) {
+        self.slice.source_rect = rect;
+        self.rebuild();
+    }
+
+    /// Uses the whole texture again.
+    pub fn clear_source_rect(&mut self) {
+        self.set_source_rect([0.0, 0.0, 1.0, 1.0]);
+    }
+
+    pub fn set_borders<I: Into<Insets>>(&mut self, borders: I) {
+        self.slice.borders = borders.into();
+        self.rebuild();
+    }
+
+    /// Sets how many pixels each texel of the borders covers on screen. Defaults to 1.0.
+    /// If the borders don't fit in the viewport, they are scaled down further.
+    pub fn set_border_scale(&mut self, scale: f32) {
+        self.slice.border_scale = scale.max(0.0);
+        self.rebuild();
+    }
+
+    pub fn set_edge_mode(&mut self, mode: SliceMode) {
+        self.slice.edge_mode = mode;
+        self.rebuild();
+    }
+
+    pub fn set_center_mode(&mut self, mode: SliceMode) {
+        self.slice.center_mode = mode;
+        self.rebuild();
+    }
+
+    /// Sets a color the texture is multiplied with. Defaults to opaque white.
+    pub fn set_tint(&mut self, tint: [f32; 4]) {
+        self.program.set_builtin("u_tint", tint);
+    }
+
+    /// Sets the overall opacity, from 0.0 (invisible) to 1.0 (the default).
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.program.set_builtin("u_opacity", opacity.clamp(0.0, 1.0));
+    }
+
+    fn rebuild(&mut self) {
+        let vertices = self.slice.build_vertices(self.viewport.size);
+        self.vertex_count = (vertices.len() / 4) as i32;
+        if vertices.is_empty() {
+            return;
+        }
+
+        unsafe {
+            gl::NamedBufferData(
+                self.buffer,
+                std::mem::size_of_val(vertices.as_slice()) as isize,
+                vertices.as_ptr() as *const _,
+                gl::DYNAMIC_DRAW,
+            );
+        }
+    }
+}
+
+/// Where the nine-slice's cells come from in the texture and how they fill the viewport.
+#[derive(Debug, Clone, Copy)]
+struct NineSlice {
+    texture_size: [i32; 2],
+    source_rect: [f32; 4],
+    borders: Insets,
+    border_scale: f32,
+    edge_mode: SliceMode,
+    center_mode: SliceMode,
+}
+
+impl NineSlice {
+    /// Triangles covering a viewport of `viewport_size`, as NDC positions and texture
+    /// coordinates. Empty if the viewport is.
+    fn build_vertices(&self, viewport_size: [i32; 2]) -> Vec<f32> {
+        let size = [viewport_size[0] as f32, viewport_size[1] as f32];
+        if size[0] <= 0.0 || size[1] <= 0.0 {
+            return Vec::new();
+        }
+
+        let [su, sv, sw, sh] = self.source_rect;
+        let texel = [1.0 / self.texture_size[0] as f32, 1.0 / self.texture_size[1] as f32];
+        let region_texels = [sw * self.texture_size[0] as f32, sh * self.texture_size[1] as f32];
+        let [left, right, top, bottom] = self
+            .borders
+            .to_absolute(region_texels.map(|texels| texels.round() as i32))
+            .map(|b| b.max(0) as f32);
+
+        // Shrink the corners if they don't fit next to each other.
+        let fit = |a: f32, b: f32, space: f32| {
+            let total = (a + b) * self.border_scale;
+            if total > space { space / (a + b) } else { self.border_scale }
+        };
+        let scale_x = fit(left, right, size[0]);
+        let scale_y = fit(top, bottom, size[1]);
+
+        // Screen positions from the left and from the bottom, in pixels.
+        let xs = [0.0, left * scale_x, size[0] - right * scale_x, size[0]];
+        let ys = [0.0, bottom * scale_y, size[1] - top * scale_y, size[1]];
+
+        // Texture coordinates matching them. v runs from the top of the region down.
+        let us = [su, su + left * texel[0], su + sw - right * texel[0], su + sw];
+        let vs = [sv + sh, sv + sh - bottom * texel[1], sv + top * texel[1], sv];
+
+        // Tiles are the size of the source cell, at the border scale. Measured in texels rather
+        // than from the texture coordinates, which would leave a sliver of a tile at the end.
+        let tile_width = (region_texels[0] - left - right) * self.border_scale;
+        let tile_height = (region_texels[1] - top - bottom) * self.border_scale;
+
+        let mut vertices = Vec::new();
+        for row in 0..3 {
+            for column in 0..3 {
+                let mode = match (column, row) {
+                    (1, 1) => self.center_mode,
+                    (1, _) | (_, 1) => self.edge_mode,
+                    _ => SliceMode::Stretch,
+                };
+
+                let tile = [
+                    if mode == SliceMode::Tile && column == 1 { tile_width } else { 0.0 },
+                    if mode == SliceMode::Tile && row == 1 { tile_height } else { 0.0 },
+                ];
+
+                push_cell(
+                    &mut vertices,
+                    [xs[column], ys[row], xs[column + 1], ys[row + 1]],
+                    [us[column], vs[row], us[column + 1], vs[row + 1]],
+                    tile,
+                    size,
+                );
+            }
+        }
+
+        vertices
+    }
+}
+
+/// Adds one cell of the nine-slice, repeated along the axes with a non-zero tile size.
+/// `rect` is `[x1, y1, x2, y2]` in pixels with y up; `uv` holds the matching texture coordinates.
+fn push_cell(vertices: &mut Vec<f32>, rect: [f32; 4], uv: [f32; 4], tile: [f32; 2], size: [f32; 2]) {
+    let [x1, y1, x2, y2] = rect;
+    if x2 <= x1 || y2 <= y1 {
+        return;
+    }
+
+    let step_x = if tile[0] > 0.5 { tile[0] } else { x2 - x1 };
+    let step_y = if tile[1] > 0.5 { tile[1] } else { y2 - y1 };
+
+    for column in 0..tile_count(x2 - x1, step_x) {
+        let x = x1 + step_x * column as f32;
+        let width = step_x.min(x2 - x);
+        let u_end = uv[0] + (uv[2] - uv[0]) * width / step_x;
+
+        // Tiles hang from the top of the cell.
+        for row in 0..tile_count(y2 - y1, step_y) {
+            let y = y2 - step_y * row as f32;
+            let height = step_y.min(y - y1);
+            let v_start = uv[3] + (uv[1] - uv[3]) * height / step_y;
+            push_quad(vertices, [x, y - height, x + width, y], [uv[0], v_start, u_end, uv[3]], size);
+        }
+    }
+}
+
+/// The number of tiles of size `step` covering `extent`, the last one possibly cut off.
+/// Leaves out a last tile so thin that only rounding errors could have made it.
+fn tile_count(extent: f32, step: f32) -> usize {
+    (extent / step - 1e-3).ceil().max(1.0) as usize
+}
+
+fn push_quad(vertices: &mut Vec<f32>, rect: [f32; 4], uv: [f32; 4], size: [f32; 2]) {
+    let to_ndc = |x: f32, y: f32| [x / size[0] * 2.0 - 1.0, y / size[1] * 2.0 - 1.0];
+    let bl = to_ndc(rect[0], rect[1]);
+    let tr = to_ndc(rect[2], rect[3]);
+
+    #[rustfmt::skip]
+    vertices.extend_from_slice(&[
+        bl[0], bl[1], uv[0], uv[1],
+        tr[0], bl[1], uv[2], uv[1],
+        tr[0], tr[1], uv[2], uv[3],
+        bl[0], bl[1], uv[0], uv[1],
+        tr[0], tr[1], uv[2], uv[3],
+        bl[0], tr[1], uv[0], uv[3],
+    ]);
+}
+
+impl Renderer for NineSliceRenderer {
+    fn set_viewport(&mut self, viewport: Viewport) {
+        let resized = viewport.size != self.viewport.size;
+        self.viewport = viewport;
+        if resized {
+            self.rebuild();
+        }
+    }
+
+    fn render(&self) {
+        if self.vertex_count == 0 {
+            return;
+        }
+
+        self.viewport.gl_viewport();
+        let blend_state = BlendState::capture();
+        BlendMode::Alpha.apply();
+        self.program.use_program();
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, self.vertex_count);
+        }
+        blend_state.restore();
+    }
+}
+
+impl Drop for NineSliceRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+            gl::DeleteBuffers(1, &self.buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderers::basic_renderers::SplitPoint;
+
+    /// A 30x30 texture with 10 texel borders, so every cell of the source is 10x10 texels.
+    fn slice(edge_mode: SliceMode, center_mode: SliceMode) -> NineSlice {
+        NineSlice {
+            texture_size: [30, 30],
+            source_rect: [0.0, 0.0, 1.0, 1.0],
+            borders: Insets::absolute(10),
+            border_scale: 1.0,
+            edge_mode,
+            center_mode,
+        }
+    }
+
+    /// A quad as `[x1, y1, x2, y2]` in pixels and `[u1, v1, u2, v2]`, from its bottom-left
+    /// to its top-right corner.
+    #[derive(Debug, PartialEq)]
+    struct Quad {
+        rect: [f32; 4],
+        uv: [f32; 4],
+    }
+
+    fn quads(vertices: &[f32], size: [i32; 2]) -> Vec<Quad> {
+        assert_eq!(vertices.len() % 24, 0);
+        let to_pixels = |ndc: f32, size: i32| (ndc + 1.0) * 0.5 * size as f32;
+        vertices
+            .chunks(24)
+            .map(|quad| {
+                let (bl, tr) = (&quad[0..4], &quad[8..12]);
+                Quad {
+                    rect: [
+                        to_pixels(bl[0], size[0]),
+                        to_pixels(bl[1], size[1]),
+                        to_pixels(tr[0], size[0]),
+                        to_pixels(tr[1], size[1]),
+                    ],
+                    uv: [bl[2], bl[3], tr[2], tr[3]],
+                }
+            })
+            .collect()
+    }
+
+    fn assert_close(actual: [f32; 4], expected: [f32; 4]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-4, "{:?} is not close to {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn stretch_draws_one_quad_per_cell() {
+        let size = [100, 60];
+        let quads = quads(&slice(SliceMode::Stretch, SliceMode::Stretch).build_vertices(size), size);
+        assert_eq!(quads.len(), 9);
+
+        // The bottom-left corner keeps its size and shows the bottom-left of the texture.
+        assert_close(quads[0].rect, [0.0, 0.0, 10.0, 10.0]);
+        assert_close(quads[0].uv, [0.0, 1.0, 1.0 / 3.0, 2.0 / 3.0]);
+
+        // The center fills the rest.
+        assert_close(quads[4].rect, [10.0, 10.0, 90.0, 50.0]);
+        assert_close(quads[4].uv, [1.0 / 3.0, 2.0 / 3.0, 2.0 / 3.0, 1.0 / 3.0]);
+    }
+
+    #[test]
+    fn tile_repeats_edges_and_center() {
+        let size = [100, 100];
+
+        // 80 pixels between the corners, so 8 tiles of 10 pixels along each edge.
+        let edges = quads(&slice(SliceMode::Tile, SliceMode::Stretch).build_vertices(size), size);
+        assert_eq!(edges.len(), 4 + 4 * 8 + 1);
+
+        let all = quads(&slice(SliceMode::Tile, SliceMode::Tile).build_vertices(size), size);
+        assert_eq!(all.len(), 4 + 4 * 8 + 8 * 8);
+
+        let center = quads(&slice(SliceMode::Stretch, SliceMode::Tile).build_vertices(size), size);
+        assert_eq!(center.len(), 4 + 4 + 8 * 8);
+    }
+
+    #[test]
+    fn tile_size_follows_border_scale() {
+        let size = [100, 100];
+        let mut slice = slice(SliceMode::Tile, SliceMode::Stretch);
+        slice.border_scale = 2.0;
+
+        // 60 pixels between the 20 pixel corners, in tiles of 20 pixels.
+        let quads = quads(&slice.build_vertices(size), size);
+        assert_eq!(quads.len(), 4 + 4 * 3 + 1);
+        assert_close(quads[1].rect, [20.0, 0.0, 40.0, 20.0]);
+    }
+
+    #[test]
+    fn last_tile_is_cut_off() {
+        // 75 pixels between the corners: 7 whole tiles and half of one.
+        let size = [95, 30];
+        let quads = quads(&slice(SliceMode::Tile, SliceMode::Stretch).build_vertices(size), size);
+
+        // The bottom edge comes right after the bottom-left corner.
+        let bottom_edge = &quads[1..9];
+        assert_close(bottom_edge[0].rect, [10.0, 0.0, 20.0, 10.0]);
+        assert_close(bottom_edge[0].uv, [1.0 / 3.0, 1.0, 2.0 / 3.0, 2.0 / 3.0]);
+        assert_close(bottom_edge[7].rect, [80.0, 0.0, 85.0, 10.0]);
+        assert_close(bottom_edge[7].uv, [1.0 / 3.0, 1.0, 0.5, 2.0 / 3.0]);
+    }
+
+    #[test]
+    fn tiles_hang_from_the_top() {
+        // 75 pixels between the corners, so the lowest tile of the side edges is cut off.
+        let size = [30, 95];
+        let quads = quads(&slice(SliceMode::Tile, SliceMode::Stretch).build_vertices(size), size);
+
+        // The left edge starts the middle row, after the three cells of the bottom row.
+        let left_edge = &quads[3..11];
+        assert_close(left_edge[0].rect, [0.0, 75.0, 10.0, 85.0]);
+        assert_close(left_edge[0].uv, [0.0, 2.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0]);
+
+        // The cut-off tile shows the top half of the source cell, since v runs downwards.
+        assert_close(left_edge[7].rect, [0.0, 10.0, 10.0, 15.0]);
+        assert_close(left_edge[7].uv, [0.0, 0.5, 1.0 / 3.0, 1.0 / 3.0]);
+    }
+
+    #[test]
+    fn tiles_below_half_a_pixel_are_stretched() {
+        let size = [100, 100];
+        let mut slice = slice(SliceMode::Tile, SliceMode::Tile);
+        slice.border_scale = 0.04;
+
+        // Tiles would be 0.4 pixels wide.
+        let quads = quads(&slice.build_vertices(size), size);
+        assert_eq!(quads.len(), 9);
+        assert_close(quads[4].rect, [0.4, 0.4, 99.6, 99.6]);
+    }
+
+    #[test]
+    fn corners_shrink_to_fit() {
+        // 10 texel borders at twice the size don't fit next to each other in 30 pixels.
+        let size = [30, 100];
+        let mut slice = slice(SliceMode::Stretch, SliceMode::Stretch);
+        slice.border_scale = 2.0;
+
+        // The left and right corners take 15 pixels each, leaving no room for the center column.
+        let quads = quads(&slice.build_vertices(size), size);
+        assert_eq!(quads.len(), 6);
+        assert_close(quads[0].rect, [0.0, 0.0, 15.0, 20.0]);
+        assert_close(quads[1].rect, [15.0, 0.0, 30.0, 20.0]);
+        assert_close(quads[5].rect, [15.0, 80.0, 30.0, 100.0]);
+    }
+
+    #[test]
+    fn borders_wider_than_viewport_leave_only_corners() {
+        let size = [10, 10];
+        for mode in [SliceMode::Stretch, SliceMode::Tile] {
+            let quads = quads(&slice(mode, mode).build_vertices(size), size);
+            let rects: Vec<_> = quads.iter().map(|quad| quad.rect).collect();
+            assert_eq!(
+                rects,
+                [
+                    [0.0, 0.0, 5.0, 5.0],
+                    [5.0, 0.0, 10.0, 5.0],
+                    [0.0, 5.0, 5.0, 10.0],
+                    [5.0, 5.0, 10.0, 10.0],
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn uneven_borders_shrink_in_proportion() {
+        let size = [20, 100];
+        let mut slice = slice(SliceMode::Stretch, SliceMode::Stretch);
+        slice.borders = Insets::new(
+            SplitPoint::Absolute(10),
+            SplitPoint::Absolute(30),
+            SplitPoint::Absolute(0),
+            SplitPoint::Absolute(0),
+        );
+        slice.texture_size = [60, 30];
+
+        // 40 texels in 20 pixels: the left border gets 5 pixels and the right one 15.
+        let quads = quads(&slice.build_vertices(size), size);
+        assert_close(quads[0].rect, [0.0, 0.0, 5.0, 100.0]);
+        assert_close(quads[1].rect, [5.0, 0.0, 20.0, 100.0]);
+    }
+
+    #[test]
+    fn empty_viewport_has_no_vertices() {
+        let slice = slice(SliceMode::Tile, SliceMode::Tile);
+        assert!(slice.build_vertices([0, 100]).is_empty());
+        assert!(slice.build_vertices([100, 0]).is_empty());
+        assert!(slice.build_vertices([-5, -5]).is_empty());
+    }
+}
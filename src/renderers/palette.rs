@@ -0,0 +1,460 @@
+use std::ops::RangeInclusive;
+
+use gl::types::*;
+
+use crate::shader::{ShaderProgram, TextureUnit};
+use crate::Error;
+
+/// Number of colors in each row of a [`Palette`].
+pub const PALETTE_SIZE: usize = 256;
+
+/// How a renderer draws an 8-bit index texture through a palette.
+///
+/// The index texture has a single red channel, such as one created with
+/// [`create_index_texture`], and the palette texture is one or more rows of 256 colors,
+/// such as a [`Palette`]. Both must be bound to their texture units before rendering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaletteSettings {
+    /// Texture unit the palette texture is bound to.
+    pub texture_unit: GLint,
+
+    /// Row of the palette texture to use, for palettes holding several variants of the
+    /// same colors, such as one per team. Rows out of range use the first or last row.
+    pub row: i32,
+
+    /// An index drawn fully transparent regardless of its color, usually 0.
+    pub transparent_index: Option<u8>,
+}
+
+impl PaletteSettings {
+    pub fn new(texture_unit: GLint) -> Self {
+        Self {
+            texture_unit,
+            row: 0,
+            transparent_index: None,
+        }
+    }
+
+    pub fn with_row(mut self, row: i32) -> Self {
+        self.row = row;
+        self
+    }
+
+    pub fn with_transparent_index(mut self, index: u8) -> Self {
+        self.transparent_index = Some(index);
+        self
+    }
+}
+
+/// The colors of a [`Palette`]: one or more rows of 256 RGBA colors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaletteColors {
+    colors: Vec<[u8; 4]>,
+    rows: usize,
+}
+
+impl PaletteColors {
+    /// Creates `rows` rows of transparent black.
+    pub fn new(rows: usize) -> Result<Self, Error> {
+        if rows == 0 {
+            return Err("PaletteColors::new: A palette needs at least one row".into());
+        }
+
+        Ok(Self {
+            colors: vec![[0; 4]; PALETTE_SIZE * rows],
+            rows,
+        })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// The 256 colors of a row.
+    pub fn row(&self, row: usize) -> Option<&[[u8; 4]]> {
+        (row < self.rows).then(|| &self.colors[row * PALETTE_SIZE..(row + 1) * PALETTE_SIZE])
+    }
+
+    /// All rows, one after the other.
+    pub fn as_slice(&self) -> &[[u8; 4]] {
+        &self.colors
+    }
+
+    /// Replaces the colors of a row starting at index `start`.
+    pub fn set_colors(&mut self, row: usize, start: u8, colors: &[[u8; 4]]) -> Result<(), Error> {
+        if row >= self.rows {
+            return Err(format!("PaletteColors::set_colors: Row {} is out of range", row).into());
+        }
+        if start as usize + colors.len() > PALETTE_SIZE {
+            return Err(format!(
+                "PaletteColors::set_colors: {} colors starting at index {} don't fit in the palette",
+                colors.len(), start
+            ).into());
+        }
+
+        let offset = row * PALETTE_SIZE + start as usize;
+        self.colors[offset..offset + colors.len()].copy_from_slice(colors);
+        Ok(())
+    }
+
+    /// Rotates the colors of `range` within a row by `steps`. Positive steps move each
+    /// color to a higher index, and the last color of the range around to the first.
+    /// Ranges of less than two colors are left as they are.
+    pub fn rotate(&mut self, row: usize, range: RangeInclusive<u8>, steps: i32) -> Result<(), Error> {
+        if row >= self.rows {
+            return Err(format!("PaletteColors::rotate: Row {} is out of range", row).into());
+        }
+
+        let (first, last) = (*range.start() as usize, *range.end() as usize);
+        if first >= last {
+            return Ok(());
+        }
+
+        let offset = row * PALETTE_SIZE;
+        let colors = &mut self.colors[offset + first..=offset + last];
+        let steps = steps.rem_euclid(colors.len() as i32) as usize;
+        colors.rotate_right(steps);
+        Ok(())
+    }
+}
+
+/// A texture of one or more rows of 256 RGBA colors, for drawing index textures.
+///
+/// A copy of the colors is kept, so single colors or ranges can be changed, and ranges
+/// rotated for color cycling, by uploading only the row that changed. The index
+/// textures themselves never need to be uploaded again.
+pub struct Palette {
+    texture: GLuint,
+    colors: PaletteColors,
+}
+
+impl Palette {
+    /// Creates a palette with `rows` rows, all transparent black.
+    pub fn new(rows: usize) -> Result<Self, Error> {
+        Self::with_colors(PaletteColors::new(rows)?)
+    }
+
+    /// Creates a single-row palette. Colors past the ones given are transparent black.
+    pub fn from_colors(colors: &[[u8; 4]]) -> Result<Self, Error> {
+        let mut palette_colors = PaletteColors::new(1)?;
+        palette_colors.set_colors(0, 0, colors)?;
+        Self::with_colors(palette_colors)
+    }
+
+    pub fn with_colors(colors: PaletteColors) -> Result<Self, Error> {
+        let rows = GLsizei::try_from(colors.rows())
+            .map_err(|_| format!("Palette::with_colors: {} rows is too many", colors.rows()))?;
+
+        let mut texture = 0;
+        unsafe {
+            gl::CreateTextures(gl::TEXTURE_2D, 1, &mut texture);
+            gl::TextureStorage2D(texture, 1, gl::RGBA8, PALETTE_SIZE as GLsizei, rows);
+            gl::TextureParameteri(texture, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+            gl::TextureParameteri(texture, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+            gl::TextureParameteri(texture, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TextureParameteri(texture, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+        }
+
+        let palette = Self { texture, colors };
+        for row in 0..palette.rows() {
+            palette.upload_row(row);
+        }
+
+        Ok(palette)
+    }
+
+    pub fn texture(&self) -> GLuint {
+        self.texture
+    }
+
+    pub fn colors(&self) -> &PaletteColors {
+        &self.colors
+    }
+
+    pub fn rows(&self) -> usize {
+        self.colors.rows()
+    }
+
+    /// The 256 colors of a row.
+    pub fn row(&self, row: usize) -> Option<&[[u8; 4]]> {
+        self.colors.row(row)
+    }
+
+    /// Binds the palette texture to a texture unit (0 for `GL_TEXTURE0`, and so on).
+    pub fn bind(&self, texture_unit: GLuint) {
+        unsafe {
+            gl::BindTextureUnit(texture_unit, self.texture);
+        }
+    }
+
+    pub fn set_color(&mut self, row: usize, index: u8, color: [u8; 4]) -> Result<(), Error> {
+        self.set_colors(row, index, &[color])
+    }
+
+    /// Replaces the colors of a row starting at index `start`.
+    pub fn set_colors(&mut self, row: usize, start: u8, colors: &[[u8; 4]]) -> Result<(), Error> {
+        self.colors.set_colors(row, start, colors)?;
+        self.upload_row(row);
+        Ok(())
+    }
+
+    /// Rotates the colors of `range` within a row by `steps`, as [`PaletteColors::rotate`] does.
+    pub fn rotate(&mut self, row: usize, range: RangeInclusive<u8>, steps: i32) -> Result<(), Error> {
+        self.colors.rotate(row, range, steps)?;
+        self.upload_row(row);
+        Ok(())
+    }
+
+    fn upload_row(&self, row: usize) {
+        let Some(colors) = self.colors.row(row) else {
+            return;
+        };
+
+        unsafe {
+            gl::TextureSubImage2D(
+                self.texture,
+                0,
+                0,
+                row as GLint,
+                PALETTE_SIZE as GLsizei,
+                1,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                colors.as_ptr() as *const _,
+            );
+        }
+    }
+}
+
+impl Drop for Palette {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.texture);
+        }
+    }
+}
+
+/// Rotates a range of palette colors at a steady rate, for animated water, fire and
+/// similar effects.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaletteCycle {
+    pub row: usize,
+    pub range: RangeInclusive<u8>,
+
+    /// Rotation speed in colors per second. Negative values rotate the other way.
+    pub steps_per_second: f32,
+
+    elapsed: f32,
+}
+
+impl PaletteCycle {
+    pub fn new(row: usize, range: RangeInclusive<u8>, steps_per_second: f32) -> Self {
+        Self {
+            row,
+            range,
+            steps_per_second,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Advances time by `dt` seconds and rotates the palette by the whole steps that passed.
+    pub fn advance(&mut self, palette: &mut Palette, dt: f32) -> Result<(), Error> {
+        match self.whole_steps(dt) {
+            0 => Ok(()),
+            steps => palette.rotate(self.row, self.range.clone(), steps),
+        }
+    }
+
+    /// Like [`advance`](Self::advance), for colors that aren't in a texture.
+    pub fn advance_colors(&mut self, colors: &mut PaletteColors, dt: f32) -> Result<(), Error> {
+        match self.whole_steps(dt) {
+            0 => Ok(()),
+            steps => colors.rotate(self.row, self.range.clone(), steps),
+        }
+    }
+
+    /// Advances time by `dt` seconds and takes the whole steps that passed, keeping the fraction.
+    fn whole_steps(&mut self, dt: f32) -> i32 {
+        self.elapsed += dt * self.steps_per_second;
+        let steps = self.elapsed.trunc();
+        self.elapsed -= steps;
+        steps as i32
+    }
+}
+
+/// Creates a texture with one 8-bit palette index per pixel, rows starting from the top
+/// of the image.
+pub fn create_index_texture(size: [i32; 2], indices: &[u8]) -> Result<GLuint, Error> {
+    if size[0] <= 0 || size[1] <= 0 || indices.len() != (size[0] * size[1]) as usize {
+        return Err(format!(
+            "create_index_texture: {} indices don't match the size {:?}",
+            indices.len(), size
+        ).into());
+    }
+
+    let mut texture = 0;
+    unsafe {
+        gl::CreateTextures(gl::TEXTURE_2D, 1, &mut texture);
+        gl::TextureStorage2D(texture, 1, gl::R8, size[0], size[1]);
+        gl::TextureParameteri(texture, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+        gl::TextureParameteri(texture, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+
+        // Rows of single bytes aren't 4-byte aligned.
+        let mut alignment = 0;
+        gl::GetIntegerv(gl::UNPACK_ALIGNMENT, &mut alignment);
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        gl::TextureSubImage2D(
+            texture,
+            0,
+            0,
+            0,
+            size[0],
+            size[1],
+            gl::RED,
+            gl::UNSIGNED_BYTE,
+            indices.as_ptr() as *const _,
+        );
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, alignment);
+    }
+
+    Ok(texture)
+}
+
+/// GLSL shared by the renderers with a palette mode. Declares the palette uniforms and
+/// `palette_color`, which looks up the color for the index at a texel of an index texture.
+pub(crate) const PALETTE_GLSL: &str = r#"
+uniform bool u_indexed;
+uniform sampler2D u_palette;
+uniform int u_palette_row;
+uniform int u_transparent_index;
+
+vec4 palette_color(sampler2D index_texture, vec2 uv) {
+    ivec2 size = textureSize(index_texture, 0);
+    ivec2 texel = clamp(ivec2(floor(uv * vec2(size))), ivec2(0), size - 1);
+    int index = int(texelFetch(index_texture, texel, 0).r * 255.0 + 0.5);
+    if (index == u_transparent_index) {
+        return vec4(0.0);
+    }
+    int row = clamp(u_palette_row, 0, textureSize(u_palette, 0).y - 1);
+    return texelFetch(u_palette, ivec2(index, row), 0);
+}
+"#;
+
+/// Inserts [`PALETTE_GLSL`] after the `#version` line of a fragment shader.
+pub(crate) fn with_palette_glsl(fragment: &str) -> String {
+    let line_end = fragment
+        .find("#version")
+        .and_then(|start| fragment[start..].find('\n').map(|end| start + end + 1))
+        .unwrap_or(0);
+
+    format!("{}{}{}", &fragment[..line_end], PALETTE_GLSL, &fragment[line_end..])
+}
+
+/// Sets the palette uniforms declared by [`PALETTE_GLSL`] in a renderer's program.
+pub(crate) fn apply_palette_uniforms(program: &ShaderProgram, settings: Option<PaletteSettings>) {
+    let palette = settings.unwrap_or(PaletteSettings::new(0));
+    program.set_builtin("u_indexed", settings.is_some());
+    program.set_builtin("u_palette", TextureUnit(palette.texture_unit));
+    program.set_builtin("u_palette_row", palette.row);
+    program.set_builtin("u_transparent_index", palette.transparent_index.map_or(-1, GLint::from));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A row whose colors are their own indices in the red channel.
+    fn indexed_colors() -> PaletteColors {
+        let mut colors = PaletteColors::new(2).unwrap();
+        let row: Vec<[u8; 4]> = (0..=255).map(|i| [i, 0, 0, 255]).collect();
+        colors.set_colors(1, 0, &row).unwrap();
+        colors
+    }
+
+    fn reds(colors: &PaletteColors, range: RangeInclusive<usize>) -> Vec<u8> {
+        colors.row(1).unwrap()[range].iter().map(|color| color[0]).collect()
+    }
+
+    #[test]
+    fn rotates_within_the_range() {
+        let mut colors = indexed_colors();
+        colors.rotate(1, 2..=5, 1).unwrap();
+        assert_eq!(reds(&colors, 0..=6), [0, 1, 5, 2, 3, 4, 6]);
+        assert_eq!(colors.row(0).unwrap(), &[[0; 4]; PALETTE_SIZE][..]);
+    }
+
+    #[test]
+    fn rotates_backwards_with_negative_steps() {
+        let mut colors = indexed_colors();
+        colors.rotate(1, 2..=5, -1).unwrap();
+        assert_eq!(reds(&colors, 2..=5), [3, 4, 5, 2]);
+
+        // Whole turns, in either direction, change nothing.
+        colors.rotate(1, 2..=5, -8).unwrap();
+        assert_eq!(reds(&colors, 2..=5), [3, 4, 5, 2]);
+        colors.rotate(1, 2..=5, 8).unwrap();
+        assert_eq!(reds(&colors, 2..=5), [3, 4, 5, 2]);
+
+        // Anything past a whole turn wraps around.
+        colors.rotate(1, 2..=5, -9).unwrap();
+        assert_eq!(reds(&colors, 2..=5), [4, 5, 2, 3]);
+    }
+
+    #[test]
+    fn leaves_single_color_and_reversed_ranges_alone() {
+        let mut colors = indexed_colors();
+        colors.rotate(1, 3..=3, 1).unwrap();
+        #[allow(clippy::reversed_empty_ranges)]
+        colors.rotate(1, 5..=2, 1).unwrap();
+        assert_eq!(colors, indexed_colors());
+    }
+
+    #[test]
+    fn rotates_the_whole_row() {
+        let mut colors = indexed_colors();
+        colors.rotate(1, 0..=255, 1).unwrap();
+        assert_eq!(reds(&colors, 0..=2), [255, 0, 1]);
+        assert_eq!(reds(&colors, 255..=255), [254]);
+    }
+
+    #[test]
+    fn rejects_rows_and_ranges_out_of_bounds() {
+        let mut colors = indexed_colors();
+        assert!(colors.rotate(2, 0..=1, 1).is_err());
+        assert!(colors.set_colors(2, 0, &[[1; 4]]).is_err());
+        assert!(colors.set_colors(0, 255, &[[1; 4]; 2]).is_err());
+        assert!(colors.set_colors(0, 255, &[[1; 4]]).is_ok());
+        assert!(PaletteColors::new(0).is_err());
+    }
+
+    #[test]
+    fn cycle_accumulates_fractional_steps() {
+        let mut colors = indexed_colors();
+        let mut cycle = PaletteCycle::new(1, 0..=3, 4.0);
+
+        cycle.advance_colors(&mut colors, 0.125).unwrap();
+        assert_eq!(reds(&colors, 0..=3), [0, 1, 2, 3]);
+
+        // 0.5 + 0.75 steps: one whole step, with 0.25 left over.
+        cycle.advance_colors(&mut colors, 0.1875).unwrap();
+        assert_eq!(reds(&colors, 0..=3), [3, 0, 1, 2]);
+
+        cycle.advance_colors(&mut colors, 0.125).unwrap();
+        assert_eq!(reds(&colors, 0..=3), [3, 0, 1, 2]);
+
+        // 0.75 + 2.0 steps: two whole steps at once.
+        cycle.advance_colors(&mut colors, 0.5).unwrap();
+        assert_eq!(reds(&colors, 0..=3), [1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn cycle_runs_backwards_with_negative_speed() {
+        let mut colors = indexed_colors();
+        let mut cycle = PaletteCycle::new(1, 0..=3, -1.5);
+
+        cycle.advance_colors(&mut colors, 0.5).unwrap();
+        assert_eq!(reds(&colors, 0..=3), [0, 1, 2, 3]);
+        cycle.advance_colors(&mut colors, 1.0).unwrap();
+        assert_eq!(reds(&colors, 0..=3), [2, 3, 0, 1]);
+    }
+}
@@ -0,0 +1,598 @@
+use std::cell::Cell;
+use std::f32::consts::PI;
+
+use crate::Error;
+use gl;
+use gl::types::*;
+
+use crate::renderers::{
+    FrameContext,
+    Renderer,
+    Viewport,
+    Transformable,
+    Mat4,
+    animation::lerp,
+    blend::{BlendMode, BlendState},
+};
+use crate::shader::{ShaderProgram, TextureUnit};
+
+mod simulation;
+
+use simulation::{simulate_cpu, GpuSimulation, Particle, Rng, Step};
+
+const MAX_CURVE_KEYS: usize = 8;
+
+const VCODE : &str = r#"
+#version 450 core
+layout (location = 0) in vec2 in_corner;
+layout (location = 1) in vec4 in_position_velocity;
+layout (location = 2) in vec4 in_age_lifetime_rotation_spin;
+out vec2 v_uv;
+out vec2 v_local;
+out vec4 v_color;
+uniform mat4 u_transform;
+uniform vec4 u_source_rect;
+
+uniform float u_size_times[8];
+uniform float u_size_values[8];
+uniform int u_size_count;
+uniform float u_color_times[8];
+uniform vec4 u_color_values[8];
+uniform int u_color_count;
+
+float size_at(float t) {
+    if (t <= u_size_times[0]) {
+        return u_size_values[0];
+    }
+    for (int i = 1; i < u_size_count; i++) {
+        if (t <= u_size_times[i]) {
+            float span = max(u_size_times[i] - u_size_times[i - 1], 1e-6);
+            return mix(u_size_values[i - 1], u_size_values[i], (t - u_size_times[i - 1]) / span);
+        }
+    }
+    return u_size_values[u_size_count - 1];
+}
+
+vec4 color_at(float t) {
+    if (t <= u_color_times[0]) {
+        return u_color_values[0];
+    }
+    for (int i = 1; i < u_color_count; i++) {
+        if (t <= u_color_times[i]) {
+            float span = max(u_color_times[i] - u_color_times[i - 1], 1e-6);
+            return mix(u_color_values[i - 1], u_color_values[i], (t - u_color_times[i - 1]) / span);
+        }
+    }
+    return u_color_values[u_color_count - 1];
+}
+
+void main() {
+    float age = in_age_lifetime_rotation_spin.x;
+    float lifetime = in_age_lifetime_rotation_spin.y;
+    if (age >= lifetime) {
+        // Dead; put all corners outside the clip volume.
+        gl_Position = vec4(2.0, 2.0, 2.0, 1.0);
+        return;
+    }
+
+    float t = age / lifetime;
+    float rotation = in_age_lifetime_rotation_spin.z;
+    vec2 local = in_corner * size_at(t);
+    float c = cos(rotation);
+    float s = sin(rotation);
+    vec2 rotated = vec2(c * local.x - s * local.y, s * local.x + c * local.y);
+
+    gl_Position = u_transform * vec4(in_position_velocity.xy + rotated, 0.0, 1.0);
+    v_uv = u_source_rect.xy + vec2(in_corner.x + 0.5, 0.5 - in_corner.y) * u_source_rect.zw;
+    v_local = in_corner * 2.0;
+    v_color = color_at(t);
+}
+"#;
+
+const FCODE : &str = r#"
+#version 450 core
+in vec2 v_uv;
+in vec2 v_local;
+in vec4 v_color;
+out vec4 fColor;
+layout (location = 1) out vec4 fNormal;
+uniform sampler2D u_tex1;
+uniform bool u_textured;
+
+void main() {
+    if (u_textured) {
+        fColor = texture(u_tex1, v_uv) * v_color;
+    } else {
+        // A soft round dot.
+        float falloff = 1.0 - smoothstep(0.5, 1.0, length(v_local));
+        fColor = vec4(v_color.rgb, v_color.a * falloff);
+    }
+
+    // A flat surface, for the scene of a lighting renderer with normal mapping.
+    fNormal = vec4(0.5, 0.5, 1.0, fColor.a);
+}
+"#;
+
+/// A value that can be interpolated by a [`Curve`].
+pub trait CurveValue: Copy {
+    fn interpolate(a: Self, b: Self, t: f32) -> Self;
+}
+
+impl CurveValue for f32 {
+    fn interpolate(a: Self, b: Self, t: f32) -> Self {
+        lerp(a, b, t)
+    }
+}
+
+impl CurveValue for [f32; 4] {
+    fn interpolate(a: Self, b: Self, t: f32) -> Self {
+        [0, 1, 2, 3].map(|i| lerp(a[i], b[i], t))
+    }
+}
+
+/// A value that changes over a particle's lifetime, from 0.0 at its birth to 1.0 at its death,
+/// interpolated linearly between keys. Particle curves can have at most 8 keys.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve<T> {
+    keys: Vec<(f32, T)>,
+}
+
+impl<T: CurveValue> Curve<T> {
+    /// Keys are `(time, value)` pairs, and must be in increasing order of time.
+    pub fn new(keys: Vec<(f32, T)>) -> Result<Self, Error> {
+        if keys.is_empty() {
+            return Err("Curve::new: A curve needs at least one key".into());
+        }
+
+        if keys.windows(2).any(|pair| pair[1].0 < pair[0].0) {
+            return Err("Curve::new: Keys must be in increasing order of time".into());
+        }
+
+        Ok(Self { keys })
+    }
+
+    pub fn constant(value: T) -> Self {
+        Self { keys: vec![(0.0, value)] }
+    }
+
+    /// Goes from `from` at birth to `to` at death.
+    pub fn linear(from: T, to: T) -> Self {
+        Self { keys: vec![(0.0, from), (1.0, to)] }
+    }
+
+    pub fn keys(&self) -> &[(f32, T)] {
+        &self.keys
+    }
+
+    pub fn evaluate(&self, t: f32) -> T {
+        let (first_time, first_value) = self.keys[0];
+        if t <= first_time {
+            return first_value;
+        }
+
+        for pair in self.keys.windows(2) {
+            let ((t0, v0), (t1, v1)) = (pair[0], pair[1]);
+            if t <= t1 {
+                let span = (t1 - t0).max(1e-6);
+                return T::interpolate(v0, v1, (t - t0) / span);
+            }
+        }
+
+        self.keys[self.keys.len() - 1].1
+    }
+}
+
+/// Where particles come from and how they move and look.
+///
+/// Positions, speeds and sizes are in the coordinates the transform is applied to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmitterSettings {
+    /// Where new particles appear.
+    pub position: [f32; 2],
+
+    /// New particles per second, while emitting.
+    pub spawn_rate: f32,
+
+    /// The most particles alive at once. New particles aren't spawned while all are in use.
+    /// Must fit in an `i32`, the instance count OpenGL takes.
+    pub max_particles: usize,
+
+    /// Lifetime range in seconds; each particle gets a random value in between.
+    pub lifetime: [f32; 2],
+
+    /// Initial speed range.
+    pub speed: [f32; 2],
+
+    /// Direction of the initial velocity, in radians, counter-clockwise from the positive x axis.
+    pub direction: f32,
+
+    /// Full angle of the cone that initial velocities are spread over, in radians.
+    pub spread: f32,
+
+    /// Acceleration applied to every particle.
+    pub gravity: [f32; 2],
+
+    /// Rotation speed range, in radians per second.
+    pub angular_velocity: [f32; 2],
+
+    /// Starts each particle at a random rotation.
+    pub random_rotation: bool,
+
+    pub size_over_lifetime: Curve<f32>,
+    pub color_over_lifetime: Curve<[f32; 4]>,
+
+    /// The region of the texture to draw, as `[u, v, width, height]` in texture coordinates,
+    /// for example from [`TextureAtlas::uv_rect`](crate::texture_atlas::TextureAtlas::uv_rect).
+    pub source_rect: [f32; 4],
+}
+
+impl Default for EmitterSettings {
+    fn default() -> Self {
+        Self {
+            position: [0.0, 0.0],
+            spawn_rate: 50.0,
+            max_particles: 1000,
+            lifetime: [1.0, 2.0],
+            speed: [0.2, 0.4],
+            direction: 0.5 * PI,
+            spread: 0.5,
+            gravity: [0.0, -0.2],
+            angular_velocity: [0.0, 0.0],
+            random_rotation: false,
+            size_over_lifetime: Curve::linear(0.05, 0.0),
+            color_over_lifetime: Curve::linear([1.0, 1.0, 1.0, 1.0], [1.0, 1.0, 1.0, 0.0]),
+            source_rect: [0.0, 0.0, 1.0, 1.0],
+        }
+    }
+}
+
+/// Where the particles are simulated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SimulationMode {
+    /// On the CPU, uploading all particles every frame.
+    #[default]
+    Cpu,
+
+    /// In a compute shader, keeping the particles on the GPU. Handles many more particles.
+    Gpu,
+}
+
+enum Simulation {
+    Cpu {
+        particles: Vec<Particle>,
+        dirty: Cell<bool>,
+    },
+    Gpu(GpuSimulation),
+}
+
+/// Simulates and draws the particles of one emitter.
+///
+/// Particles are simulated in [`update`](Renderer::update), or [`advance`](Self::advance)
+/// when there is no frame context. Both simulation modes draw from the same buffer with the
+/// same shader, so they look alike. Without a texture, each particle is a soft round dot.
+pub struct ParticleRenderer {
+    viewport: Viewport,
+    program: ShaderProgram,
+    vao: u32,
+    corner_buffer: u32,
+    particle_buffer: u32,
+
+    simulation: Simulation,
+    settings: EmitterSettings,
+    blend_mode: BlendMode,
+    emitting: bool,
+
+    /// Fractional particles carried over to the next frame.
+    spawn_accumulator: f32,
+    pending_burst: usize,
+    rng: Rng,
+}
+
+impl ParticleRenderer {
+    pub fn new(settings: EmitterSettings, mode: SimulationMode) -> Result<Self, Error> {
+        validate_settings(&settings)?;
+
+        let program = ShaderProgram::new(VCODE, FCODE)?;
+
+        let simulation = match mode {
+            SimulationMode::Cpu => Simulation::Cpu {
+                particles: Vec::new(),
+                dirty: Cell::new(false),
+            },
+            SimulationMode::Gpu => Simulation::Gpu(GpuSimulation::new()?),
+        };
+
+        #[rustfmt::skip]
+        let corners: &[f32] = &[
+            -0.5, -0.5,
+             0.5, -0.5,
+             0.5,  0.5,
+            -0.5,  0.5,
+        ];
+
+        let corner_buffer = glh::create_buffer(corners, gl::STATIC_DRAW)?;
+        let mut particle_buffer = 0;
+        let mut vao = 0;
+        unsafe {
+            gl::CreateBuffers(1, &mut particle_buffer);
+            gl::GenVertexArrays(1, &mut vao);
+        }
+
+        glh::enable_interleaved_vertex_array_attributes(
+            vao,
+            corner_buffer,
+            gl::FLOAT,
+            false,
+            0,
+            &[2],
+        )?;
+
+        glh::enable_interleaved_vertex_array_attributes(
+            vao,
+            particle_buffer,
+            gl::FLOAT,
+            false,
+            1,
+            &[4, 4],
+        )?;
+
+        unsafe {
+            for index in 1..=2 {
+                gl::VertexAttribDivisor(index, 1);
+            }
+        }
+
+        let mut self_ = Self {
+            viewport: Viewport::default(),
+            program,
+            vao,
+            corner_buffer,
+            particle_buffer,
+            simulation,
+            settings,
+            blend_mode: BlendMode::Alpha,
+            emitting: true,
+            spawn_accumulator: 0.0,
+            pending_burst: 0,
+            rng: Rng::new(0x9e3779b9),
+        };
+
+        self_.apply_settings();
+        self_.clear();
+        self_.set_texture_unit(None);
+        self_.clear_transform();
+
+        Ok(self_)
+    }
+
+    pub fn settings(&self) -> &EmitterSettings {
+        &self.settings
+    }
+
+    /// Changes the emitter. Particles already alive keep moving as they were,
+    /// unless `max_particles` changes, which removes them all.
+    pub fn set_settings(&mut self, settings: EmitterSettings) -> Result<(), Error> {
+        validate_settings(&settings)?;
+
+        let reallocate = settings.max_particles != self.settings.max_particles;
+        self.settings = settings;
+        self.apply_settings();
+        if reallocate {
+            self.clear();
+        }
+
+        Ok(())
+    }
+
+    /// Uploads the curves and source rect of the current settings.
+    fn apply_settings(&self) {
+        let settings = &self.settings;
+        let size_keys = settings.size_over_lifetime.keys();
+        let color_keys = settings.color_over_lifetime.keys();
+        let size_times: Vec<f32> = size_keys.iter().map(|key| key.0).collect();
+        let size_values: Vec<f32> = size_keys.iter().map(|key| key.1).collect();
+        let color_times: Vec<f32> = color_keys.iter().map(|key| key.0).collect();
+        let color_values: Vec<[f32; 4]> = color_keys.iter().map(|key| key.1).collect();
+
+        let program = &self.program;
+        program.set_builtin("u_size_times", size_times.as_slice());
+        program.set_builtin("u_size_values", size_values.as_slice());
+        program.set_builtin("u_size_count", size_keys.len() as i32);
+        program.set_builtin("u_color_times", color_times.as_slice());
+        program.set_builtin("u_color_values", color_values.as_slice());
+        program.set_builtin("u_color_count", color_keys.len() as i32);
+        program.set_builtin("u_source_rect", settings.source_rect);
+    }
+
+    /// Moves the emitter. Particles already alive aren't affected.
+    pub fn set_position(&mut self, position: [f32; 2]) {
+        self.settings.position = position;
+    }
+
+    /// Starts or stops spawning particles at the spawn rate. Particles already alive live on.
+    pub fn set_emitting(&mut self, emitting: bool) {
+        self.emitting = emitting;
+    }
+
+    pub fn is_emitting(&self) -> bool {
+        self.emitting
+    }
+
+    /// Spawns `count` extra particles in the next update.
+    pub fn burst(&mut self, count: usize) {
+        self.pending_burst += count;
+    }
+
+    /// Removes all particles.
+    pub fn clear(&mut self) {
+        let dead = vec![Particle::default(); self.settings.max_particles];
+        unsafe {
+            gl::NamedBufferData(
+                self.particle_buffer,
+                std::mem::size_of_val(dead.as_slice()) as isize,
+                dead.as_ptr() as *const _,
+                gl::DYNAMIC_DRAW,
+            );
+        }
+
+        if let Simulation::Cpu { particles, dirty } = &mut self.simulation {
+            *particles = dead;
+            dirty.set(false);
+        }
+
+        self.spawn_accumulator = 0.0;
+        self.pending_burst = 0;
+    }
+
+    /// Sets the texture unit to sample from, or `None` to draw soft dots without a texture.
+    /// The texture must be bound to that unit separately, before rendering.
+    pub fn set_texture_unit(&mut self, texture_unit: Option<GLint>) {
+        self.program.set_builtin("u_tex1", TextureUnit(texture_unit.unwrap_or(0)));
+        self.program.set_builtin("u_textured", texture_unit.is_some());
+    }
+
+    /// Sets the blend mode used while rendering. `Additive` suits fire and sparks.
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
+
+    /// Simulates `dt` seconds.
+    pub fn advance(&mut self, dt: f32) {
+        let count = self.settings.max_particles;
+        if count == 0 || dt <= 0.0 {
+            return;
+        }
+
+        let mut spawn_count = self.pending_burst;
+        if self.emitting {
+            self.spawn_accumulator += self.settings.spawn_rate * dt;
+            spawn_count += self.spawn_accumulator as usize;
+            self.spawn_accumulator = self.spawn_accumulator.fract();
+        }
+        self.pending_burst = 0;
+
+        let step = Step {
+            dt,
+            spawn_count: spawn_count.min(count),
+            seed: self.rng.next_u32(),
+        };
+
+        match &mut self.simulation {
+            Simulation::Cpu { particles, dirty } => {
+                simulate_cpu(particles, &self.settings, &step, &mut self.rng);
+                dirty.set(true);
+            }
+            Simulation::Gpu(gpu) => gpu.step(self.particle_buffer, count, &self.settings, &step),
+        }
+    }
+}
+
+fn validate_settings(settings: &EmitterSettings) -> Result<(), Error> {
+    let size_keys = settings.size_over_lifetime.keys();
+    let color_keys = settings.color_over_lifetime.keys();
+    if size_keys.len() > MAX_CURVE_KEYS || color_keys.len() > MAX_CURVE_KEYS {
+        return Err(format!(
+            "ParticleRenderer::set_settings: Curves can have at most {} keys",
+            MAX_CURVE_KEYS
+        ).into());
+    }
+
+    if GLsizei::try_from(settings.max_particles).is_err() {
+        return Err(format!(
+            "ParticleRenderer::set_settings: max_particles is {}, but can be at most {}",
+            settings.max_particles,
+            GLsizei::MAX
+        ).into());
+    }
+
+    Ok(())
+}
+
+impl Renderer for ParticleRenderer {
+    fn set_viewport(&mut self, viewport: Viewport) {
+        self.viewport = viewport;
+    }
+
+    fn update(&mut self, ctx: &mut FrameContext) {
+        self.advance(ctx.delta_time);
+    }
+
+    fn render(&self) {
+        if let Simulation::Cpu { particles, dirty } = &self.simulation
+            && dirty.get()
+        {
+            unsafe {
+                gl::NamedBufferSubData(
+                    self.particle_buffer,
+                    0,
+                    std::mem::size_of_val(particles.as_slice()) as isize,
+                    particles.as_ptr() as *const _,
+                );
+            }
+            dirty.set(false);
+        }
+
+        self.viewport.gl_viewport();
+        let blend_state = BlendState::capture();
+        self.blend_mode.apply();
+        self.program.use_program();
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            // Checked to fit by `validate_settings`.
+            gl::DrawArraysInstanced(gl::TRIANGLE_FAN, 0, 4, self.settings.max_particles as GLsizei);
+        }
+        blend_state.restore();
+    }
+}
+
+impl Transformable for ParticleRenderer {
+    fn set_transform(&mut self, transform: Mat4) {
+        self.program.set_builtin("u_transform", transform);
+    }
+}
+
+impl Drop for ParticleRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+            gl::DeleteBuffers(1, &self.corner_buffer);
+            gl::DeleteBuffers(1, &self.particle_buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_settings_limits_curve_keys() {
+        let keys: Vec<(f32, f32)> = (0..=MAX_CURVE_KEYS).map(|i| (i as f32, 1.0)).collect();
+        let settings = EmitterSettings {
+            size_over_lifetime: Curve::new(keys).unwrap(),
+            ..EmitterSettings::default()
+        };
+        assert!(validate_settings(&settings).is_err());
+        assert!(validate_settings(&EmitterSettings::default()).is_ok());
+    }
+
+    #[test]
+    fn validate_settings_limits_max_particles() {
+        let settings = |max_particles| EmitterSettings { max_particles, ..EmitterSettings::default() };
+        assert!(validate_settings(&settings(i32::MAX as usize)).is_ok());
+        assert!(validate_settings(&settings(i32::MAX as usize + 1)).is_err());
+        assert!(validate_settings(&settings(usize::MAX)).is_err());
+    }
+
+    #[test]
+    fn curve_evaluates_between_keys() {
+        let curve = Curve::new(vec![(0.0, 0.0), (0.5, 1.0), (1.0, 0.0)]).unwrap();
+        assert_eq!(curve.evaluate(-1.0), 0.0);
+        assert_eq!(curve.evaluate(0.25), 0.5);
+        assert_eq!(curve.evaluate(0.5), 1.0);
+        assert_eq!(curve.evaluate(0.75), 0.5);
+        assert_eq!(curve.evaluate(2.0), 0.0);
+        assert!(Curve::new(vec![(1.0, 0.0), (0.0, 1.0)]).is_err());
+        assert!(Curve::<f32>::new(Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,315 @@
+use std::f32::consts::PI;
+
+use gl::types::*;
+
+use crate::shader::ShaderProgram;
+use crate::Error;
+
+use super::EmitterSettings;
+
+/// The state of one particle, laid out to match both the vertex attributes
+/// of the render shader and the storage buffer of the compute shader.
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub(super) struct Particle {
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+    pub age: f32,
+
+    /// A particle is dead once its age reaches its lifetime.
+    pub lifetime: f32,
+    pub rotation: f32,
+    pub angular_velocity: f32,
+}
+
+impl Particle {
+    fn is_alive(&self) -> bool {
+        self.age < self.lifetime
+    }
+}
+
+/// What happens to the particles in one simulation step.
+pub(super) struct Step {
+    pub dt: f32,
+
+    /// How many dead particles are spawned again. Live particles are never replaced,
+    /// so fewer are spawned if there aren't enough dead ones.
+    pub spawn_count: usize,
+    pub seed: u32,
+}
+
+/// A small xorshift generator; particles don't need anything better.
+#[derive(Debug, Clone)]
+pub(super) struct Rng(u32);
+
+impl Rng {
+    pub fn new(seed: u32) -> Self {
+        Self(seed.max(1))
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+
+    /// A number in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    fn range(&mut self, range: [f32; 2]) -> f32 {
+        range[0] + (range[1] - range[0]) * self.next_f32()
+    }
+}
+
+pub(super) fn simulate_cpu(particles: &mut [Particle], settings: &EmitterSettings, step: &Step, rng: &mut Rng) {
+    let mut spawned = 0;
+    for particle in particles.iter_mut() {
+        if !particle.is_alive() && spawned < step.spawn_count {
+            spawned += 1;
+            let angle = settings.direction + (rng.next_f32() - 0.5) * settings.spread;
+            let speed = rng.range(settings.speed);
+            *particle = Particle {
+                position: settings.position,
+                velocity: [angle.cos() * speed, angle.sin() * speed],
+                age: 0.0,
+                lifetime: rng.range(settings.lifetime),
+                rotation: if settings.random_rotation { rng.next_f32() * 2.0 * PI } else { 0.0 },
+                angular_velocity: rng.range(settings.angular_velocity),
+            };
+        } else if particle.is_alive() {
+            particle.velocity[0] += settings.gravity[0] * step.dt;
+            particle.velocity[1] += settings.gravity[1] * step.dt;
+            particle.position[0] += particle.velocity[0] * step.dt;
+            particle.position[1] += particle.velocity[1] * step.dt;
+            particle.rotation += particle.angular_velocity * step.dt;
+            particle.age += step.dt;
+        }
+    }
+}
+
+const LOCAL_SIZE: u32 = 64;
+
+const CCODE: &str = r#"
+#version 450 core
+layout (local_size_x = 64) in;
+
+struct Particle {
+    vec4 position_velocity;
+    vec4 age_lifetime_rotation_spin;
+};
+
+layout (std430, binding = 0) buffer Particles {
+    Particle particles[];
+};
+
+// Number of dead particles that claimed a spawn this step, reset before each dispatch.
+layout (std430, binding = 1) buffer Spawned {
+    uint spawned;
+};
+
+uniform uint u_count;
+uniform uint u_spawn_count;
+uniform uint u_seed;
+uniform float u_dt;
+uniform vec2 u_emitter_position;
+uniform vec2 u_gravity;
+uniform vec2 u_lifetime;
+uniform vec2 u_speed;
+uniform vec2 u_angular_velocity;
+uniform float u_direction;
+uniform float u_spread;
+uniform bool u_random_rotation;
+
+float random(uint index, uint salt) {
+    uint h = index * 747796405u + u_seed * 2891336453u + salt * 277803737u;
+    h ^= h >> 16;
+    h *= 0x7feb352du;
+    h ^= h >> 15;
+    h *= 0x846ca68bu;
+    h ^= h >> 16;
+    return float(h >> 8) / 16777216.0;
+}
+
+void main() {
+    uint i = gl_GlobalInvocationID.x;
+    if (i >= u_count) {
+        return;
+    }
+
+    Particle p = particles[i];
+    bool alive = p.age_lifetime_rotation_spin.x < p.age_lifetime_rotation_spin.y;
+
+    if (!alive && u_spawn_count > 0u && atomicAdd(spawned, 1u) < u_spawn_count) {
+        float angle = u_direction + (random(i, 0u) - 0.5) * u_spread;
+        float speed = mix(u_speed.x, u_speed.y, random(i, 1u));
+        p.position_velocity = vec4(u_emitter_position, vec2(cos(angle), sin(angle)) * speed);
+        p.age_lifetime_rotation_spin = vec4(
+            0.0,
+            mix(u_lifetime.x, u_lifetime.y, random(i, 2u)),
+            u_random_rotation ? random(i, 3u) * 6.28318530718 : 0.0,
+            mix(u_angular_velocity.x, u_angular_velocity.y, random(i, 4u))
+        );
+    } else if (alive) {
+        p.position_velocity.zw += u_gravity * u_dt;
+        p.position_velocity.xy += p.position_velocity.zw * u_dt;
+        p.age_lifetime_rotation_spin.z += p.age_lifetime_rotation_spin.w * u_dt;
+        p.age_lifetime_rotation_spin.x += u_dt;
+    }
+
+    particles[i] = p;
+}
+"#;
+
+/// Runs the simulation in a compute shader, directly on the buffer the particles are drawn from.
+pub(super) struct GpuSimulation {
+    program: ShaderProgram,
+    spawned_buffer: u32,
+}
+
+impl GpuSimulation {
+    pub fn new() -> Result<Self, Error> {
+        let program = ShaderProgram::compute(CCODE)?;
+        let spawned_buffer = glh::create_buffer(&[0u32], gl::DYNAMIC_DRAW)?;
+
+        Ok(Self {
+            program,
+            spawned_buffer,
+        })
+    }
+
+    pub fn step(&self, buffer: GLuint, count: usize, settings: &EmitterSettings, step: &Step) {
+        if count == 0 {
+            return;
+        }
+
+        let program = &self.program;
+        program.set_builtin("u_count", count as u32);
+        program.set_builtin("u_spawn_count", step.spawn_count as u32);
+        program.set_builtin("u_seed", step.seed);
+        program.set_builtin("u_dt", step.dt);
+        program.set_builtin("u_emitter_position", settings.position);
+        program.set_builtin("u_gravity", settings.gravity);
+        program.set_builtin("u_lifetime", settings.lifetime);
+        program.set_builtin("u_speed", settings.speed);
+        program.set_builtin("u_angular_velocity", settings.angular_velocity);
+        program.set_builtin("u_direction", settings.direction);
+        program.set_builtin("u_spread", settings.spread);
+        program.set_builtin("u_random_rotation", settings.random_rotation);
+
+        program.use_program();
+        unsafe {
+            let zero = 0u32;
+            gl::NamedBufferSubData(
+                self.spawned_buffer,
+                0,
+                std::mem::size_of::<u32>() as isize,
+                &zero as *const u32 as *const _,
+            );
+
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, 0, buffer);
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, 1, self.spawned_buffer);
+            gl::DispatchCompute((count as u32).div_ceil(LOCAL_SIZE), 1, 1);
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, 0, 0);
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, 1, 0);
+
+            // The buffer is read as vertex attributes next.
+            gl::MemoryBarrier(gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT);
+        }
+    }
+}
+
+impl Drop for GpuSimulation {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.spawned_buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alive(age: f32) -> Particle {
+        Particle { age, lifetime: 1.0, ..Particle::default() }
+    }
+
+    fn settings() -> EmitterSettings {
+        EmitterSettings {
+            position: [5.0, 6.0],
+            lifetime: [2.0, 2.0],
+            gravity: [0.0, 0.0],
+            speed: [0.0, 0.0],
+            ..EmitterSettings::default()
+        }
+    }
+
+    fn step(dt: f32, spawn_count: usize) -> Step {
+        Step { dt, spawn_count, seed: 1 }
+    }
+
+    #[test]
+    fn spawns_into_dead_slots_only() {
+        let mut particles = [alive(0.5), Particle::default(), alive(0.25), Particle::default()];
+        simulate_cpu(&mut particles, &settings(), &step(0.125, 2), &mut Rng::new(1));
+
+        assert_eq!(particles[0].age, 0.625);
+        assert_eq!(particles[2].age, 0.375);
+        for i in [1, 3] {
+            assert_eq!(particles[i].age, 0.0);
+            assert_eq!(particles[i].lifetime, 2.0);
+            assert_eq!(particles[i].position, [5.0, 6.0]);
+        }
+    }
+
+    #[test]
+    fn never_replaces_live_particles() {
+        let mut particles = [alive(0.5), alive(0.0), Particle::default()];
+        simulate_cpu(&mut particles, &settings(), &step(0.125, 3), &mut Rng::new(1));
+
+        assert_eq!(particles[0].lifetime, 1.0);
+        assert_eq!(particles[1].lifetime, 1.0);
+        assert_eq!(particles[2].lifetime, 2.0);
+    }
+
+    #[test]
+    fn spawns_at_most_spawn_count() {
+        let mut particles = [Particle::default(); 5];
+        simulate_cpu(&mut particles, &settings(), &step(0.125, 2), &mut Rng::new(1));
+        assert_eq!(particles.iter().filter(|p| p.is_alive()).count(), 2);
+
+        simulate_cpu(&mut particles, &settings(), &step(0.125, 0), &mut Rng::new(1));
+        assert_eq!(particles.iter().filter(|p| p.is_alive()).count(), 2);
+    }
+
+    #[test]
+    fn particles_move_and_die() {
+        let mut particles = [Particle { velocity: [1.0, 0.0], lifetime: 1.0, ..Particle::default() }];
+        let settings = EmitterSettings { gravity: [0.0, -2.0], ..settings() };
+
+        simulate_cpu(&mut particles, &settings, &step(0.5, 0), &mut Rng::new(1));
+        assert_eq!(particles[0].velocity, [1.0, -1.0]);
+        assert_eq!(particles[0].position, [0.5, -0.5]);
+
+        simulate_cpu(&mut particles, &settings, &step(0.5, 0), &mut Rng::new(1));
+        assert!(!particles[0].is_alive());
+
+        // Dead particles stay where they are.
+        let position = particles[0].position;
+        simulate_cpu(&mut particles, &settings, &step(0.5, 0), &mut Rng::new(1));
+        assert_eq!(particles[0].position, position);
+    }
+
+    #[test]
+    fn rng_stays_in_unit_range() {
+        let mut rng = Rng::new(0);
+        for _ in 0..1000 {
+            assert!((0.0..1.0).contains(&rng.next_f32()));
+        }
+    }
+}
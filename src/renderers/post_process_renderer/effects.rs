@@ -0,0 +1,168 @@
+use gl::types::*;
+
+/// A Gaussian blur.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Blur {
+    /// Radius in pixels. Up to 32 pixels are sampled on each side.
+    pub radius: f32,
+}
+
+impl Default for Blur {
+    fn default() -> Self {
+        Self { radius: 4.0 }
+    }
+}
+
+/// A glow around bright areas: the parts brighter than the threshold are blurred at half
+/// resolution and added back on top.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bloom {
+    /// Luminance above which a pixel glows, from 0.0 to 1.0.
+    pub threshold: f32,
+
+    /// Strength of the glow added back.
+    pub intensity: f32,
+
+    /// Blur radius of the glow in pixels.
+    pub radius: f32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            threshold: 0.7,
+            intensity: 1.0,
+            radius: 8.0,
+        }
+    }
+}
+
+/// Remaps colors through a 3D lookup table stored as a 2D texture strip.
+///
+/// The strip is `size * size` pixels wide and `size` pixels high: `size` square slices side
+/// by side, one per blue level, each with red increasing to the right and green increasing
+/// downwards from the first row. An identity table of that layout is a common starting point
+/// for grading in an image editor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorGrading {
+    /// The lookup table texture. It stays owned by the application.
+    pub lut: GLuint,
+
+    /// Number of levels per channel, 16 or 32 in most tables.
+    pub size: i32,
+
+    /// How much of the graded color is used, from 0.0 (none) to 1.0.
+    pub intensity: f32,
+}
+
+impl ColorGrading {
+    pub fn new(lut: GLuint, size: i32) -> Self {
+        Self {
+            lut,
+            size,
+            intensity: 1.0,
+        }
+    }
+}
+
+/// Darkens the edges of the image towards a color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vignette {
+    /// Opacity of the color at the corners.
+    pub intensity: f32,
+
+    /// Distance from the center where the darkening starts, as a ratio of the distance
+    /// to the corners.
+    pub radius: f32,
+
+    /// Width of the transition, as a ratio of the distance to the corners.
+    pub softness: f32,
+
+    pub color: [f32; 3],
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self {
+            intensity: 0.5,
+            radius: 0.6,
+            softness: 0.4,
+            color: [0.0, 0.0, 0.0],
+        }
+    }
+}
+
+/// Old monitor look: dark horizontal scanlines and an optional bulge of the screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CrtScanlines {
+    /// How dark the gaps between the lines are, from 0.0 to 1.0.
+    pub intensity: f32,
+
+    /// Height of one line in pixels.
+    pub line_height: f32,
+
+    /// Barrel distortion of the screen. 0.0 is flat.
+    pub curvature: f32,
+}
+
+impl Default for CrtScanlines {
+    fn default() -> Self {
+        Self {
+            intensity: 0.4,
+            line_height: 3.0,
+            curvature: 0.0,
+        }
+    }
+}
+
+/// Reduces the resolution to blocks of the same color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pixelate {
+    /// Size of the blocks in pixels.
+    pub pixel_size: f32,
+}
+
+impl Default for Pixelate {
+    fn default() -> Self {
+        Self { pixel_size: 4.0 }
+    }
+}
+
+/// Removes the color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Grayscale {
+    /// From 0.0 (the original colors) to 1.0 (fully gray).
+    pub amount: f32,
+}
+
+impl Default for Grayscale {
+    fn default() -> Self {
+        Self { amount: 1.0 }
+    }
+}
+
+/// One step of a [`PostProcessRenderer`](super::PostProcessRenderer) chain.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Effect {
+    Blur(Blur),
+    Bloom(Bloom),
+    ColorGrading(ColorGrading),
+    Vignette(Vignette),
+    CrtScanlines(CrtScanlines),
+    Pixelate(Pixelate),
+    Grayscale(Grayscale),
+}
+
+macro_rules! impl_from_effect {
+    ($($name:ident),+) => {
+        $(
+            impl From<$name> for Effect {
+                fn from(effect: $name) -> Self {
+                    Effect::$name(effect)
+                }
+            }
+        )+
+    };
+}
+
+impl_from_effect!(Blur, Bloom, ColorGrading, Vignette, CrtScanlines, Pixelate, Grayscale);
@@ -0,0 +1,594 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use gl::types::*;
+
+use crate::renderers::{
+    render_child,
+    FrameContext,
+    Renderer,
+    Viewport,
+    blend::{BlendMode, BlendState},
+    render_target::{FramebufferState, RenderTarget, TextureUnitState},
+};
+use crate::shader::{ShaderProgram, TextureUnit};
+use crate::Error;
+
+mod effects;
+pub use effects::{Bloom, Blur, ColorGrading, CrtScanlines, Effect, Grayscale, Pixelate, Vignette};
+
+const VCODE: &str = r#"
+#version 450 core
+out vec2 v_uv;
+
+void main() {
+    // One triangle covering the whole viewport.
+    vec2 pos = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+    v_uv = pos;
+    gl_Position = vec4(pos * 2.0 - 1.0, 0.0, 1.0);
+}
+"#;
+
+/// Shared by all passes. Colors in the source are premultiplied by alpha.
+const FCODE_HEADER: &str = r#"
+#version 450 core
+in vec2 v_uv;
+out vec4 fColor;
+layout (location = 1) out vec4 fNormal;
+uniform sampler2D u_source;
+uniform vec2 u_texel;
+
+const vec3 LUMA = vec3(0.2126, 0.7152, 0.0722);
+"#;
+
+/// Runs the pass. The normal is only kept when drawing the last pass into the scene of
+/// a lighting renderer with normal mapping, where the result is a flat surface.
+const FCODE_FOOTER: &str = r#"
+void main() {
+    run_pass();
+    fNormal = vec4(vec3(0.5, 0.5, 1.0) * fColor.a, fColor.a);
+}
+"#;
+
+const COPY_FCODE: &str = r#"
+void run_pass() {
+    fColor = texture(u_source, v_uv);
+}
+"#;
+
+const BLUR_FCODE: &str = r#"
+uniform vec2 u_direction;
+uniform float u_radius;
+
+void run_pass() {
+    float sigma = max(u_radius * 0.5, 0.001);
+    int taps = int(min(ceil(u_radius), 32.0));
+
+    vec4 sum = texture(u_source, v_uv);
+    float total = 1.0;
+    for (int i = 1; i <= taps; i++) {
+        float weight = exp(-0.5 * float(i * i) / (sigma * sigma));
+        vec2 offset = u_direction * float(i);
+        sum += (texture(u_source, v_uv + offset) + texture(u_source, v_uv - offset)) * weight;
+        total += 2.0 * weight;
+    }
+
+    fColor = sum / total;
+}
+"#;
+
+const BLOOM_EXTRACT_FCODE: &str = r#"
+uniform float u_threshold;
+
+void run_pass() {
+    vec4 color = texture(u_source, v_uv);
+    float luma = dot(color.rgb, LUMA);
+    fColor = color * smoothstep(u_threshold - 0.1, u_threshold + 0.1, luma);
+}
+"#;
+
+const BLOOM_COMBINE_FCODE: &str = r#"
+uniform sampler2D u_bloom;
+uniform float u_intensity;
+
+void run_pass() {
+    vec4 color = texture(u_source, v_uv);
+    vec4 bloom = texture(u_bloom, v_uv) * u_intensity;
+    fColor = vec4(color.rgb + bloom.rgb, min(color.a + bloom.a, 1.0));
+}
+"#;
+
+const COLOR_GRADING_FCODE: &str = r#"
+uniform sampler2D u_lut;
+uniform float u_lut_size;
+uniform float u_intensity;
+
+vec3 lookup(vec3 color) {
+    float n = u_lut_size;
+    float blue = color.b * (n - 1.0);
+    float slice = floor(blue);
+    float next_slice = min(slice + 1.0, n - 1.0);
+
+    vec2 uv = vec2((color.r * (n - 1.0) + 0.5) / (n * n), (color.g * (n - 1.0) + 0.5) / n);
+    vec3 a = texture(u_lut, uv + vec2(slice / n, 0.0)).rgb;
+    vec3 b = texture(u_lut, uv + vec2(next_slice / n, 0.0)).rgb;
+    return mix(a, b, blue - slice);
+}
+
+void run_pass() {
+    vec4 color = texture(u_source, v_uv);
+    if (color.a <= 0.0) {
+        fColor = color;
+        return;
+    }
+
+    vec3 straight = clamp(color.rgb / color.a, 0.0, 1.0);
+    vec3 graded = mix(straight, lookup(straight), u_intensity);
+    fColor = vec4(graded * color.a, color.a);
+}
+"#;
+
+const VIGNETTE_FCODE: &str = r#"
+uniform float u_intensity;
+uniform float u_radius;
+uniform float u_softness;
+uniform vec3 u_color;
+
+void run_pass() {
+    vec4 color = texture(u_source, v_uv);
+
+    // Round regardless of the aspect ratio, 1.0 at the corners.
+    vec2 scale = vec2(u_texel.y / u_texel.x, 1.0);
+    float distance = length((v_uv - 0.5) * scale) / length(0.5 * scale);
+
+    float amount = smoothstep(u_radius, u_radius + max(u_softness, 0.0001), distance) * u_intensity;
+    fColor = vec4(mix(color.rgb, u_color * color.a, amount), color.a);
+}
+"#;
+
+const CRT_SCANLINES_FCODE: &str = r#"
+uniform float u_intensity;
+uniform float u_line_height;
+uniform float u_curvature;
+
+void run_pass() {
+    vec2 centered = v_uv * 2.0 - 1.0;
+    centered *= 1.0 + u_curvature * dot(centered, centered) * 0.25;
+    vec2 uv = centered * 0.5 + 0.5;
+    if (any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0)))) {
+        fColor = vec4(0.0);
+        return;
+    }
+
+    float line = uv.y / u_texel.y / max(u_line_height, 1.0);
+    float brightness = 0.5 - 0.5 * cos(fract(line) * 6.2831853);
+    fColor = texture(u_source, uv) * (1.0 - u_intensity * (1.0 - brightness));
+}
+"#;
+
+const PIXELATE_FCODE: &str = r#"
+uniform float u_pixel_size;
+
+void run_pass() {
+    vec2 block = max(u_pixel_size, 1.0) * u_texel;
+    fColor = texture(u_source, (floor(v_uv / block) + 0.5) * block);
+}
+"#;
+
+const GRAYSCALE_FCODE: &str = r#"
+uniform float u_amount;
+
+void run_pass() {
+    vec4 color = texture(u_source, v_uv);
+    fColor = vec4(mix(color.rgb, vec3(dot(color.rgb, LUMA)), u_amount), color.a);
+}
+"#;
+
+/// A full-screen shader; effects are made of one or more.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Pass {
+    Copy,
+    Blur,
+    BloomExtract,
+    BloomCombine,
+    ColorGrading,
+    Vignette,
+    CrtScanlines,
+    Pixelate,
+    Grayscale,
+}
+
+impl Pass {
+    fn fragment_code(self) -> &'static str {
+        match self {
+            Pass::Copy => COPY_FCODE,
+            Pass::Blur => BLUR_FCODE,
+            Pass::BloomExtract => BLOOM_EXTRACT_FCODE,
+            Pass::BloomCombine => BLOOM_COMBINE_FCODE,
+            Pass::ColorGrading => COLOR_GRADING_FCODE,
+            Pass::Vignette => VIGNETTE_FCODE,
+            Pass::CrtScanlines => CRT_SCANLINES_FCODE,
+            Pass::Pixelate => PIXELATE_FCODE,
+            Pass::Grayscale => GRAYSCALE_FCODE,
+        }
+    }
+
+    fn build(self) -> Result<ShaderProgram, Error> {
+        let program = ShaderProgram::new(VCODE, &format!("{}{}{}", FCODE_HEADER, self.fragment_code(), FCODE_FOOTER))?;
+        program.set_builtin("u_source", TextureUnit(0));
+        program.set_builtin("u_bloom", TextureUnit(1));
+        program.set_builtin("u_lut", TextureUnit(1));
+        Ok(program)
+    }
+
+    fn for_effect(effect: &Effect) -> &'static [Pass] {
+        match effect {
+            Effect::Blur(_) => &[Pass::Blur],
+            Effect::Bloom(_) => &[Pass::BloomExtract, Pass::Blur, Pass::BloomCombine],
+            Effect::ColorGrading(_) => &[Pass::ColorGrading],
+            Effect::Vignette(_) => &[Pass::Vignette],
+            Effect::CrtScanlines(_) => &[Pass::CrtScanlines],
+            Effect::Pixelate(_) => &[Pass::Pixelate],
+            Effect::Grayscale(_) => &[Pass::Grayscale],
+        }
+    }
+}
+
+/// Renders its child into an offscreen buffer, then runs a chain of full-screen effects over
+/// the result, in order, before drawing it to the viewport.
+///
+/// The child is rendered into a buffer the size of the viewport, with a depth and stencil
+/// buffer, cleared to the [clear color](Self::set_clear_color) first. The end result is blended
+/// over what is already in the viewport with premultiplied alpha, so a transparent clear color
+/// lets the background show through.
+///
+/// Effects bind their textures to texture units 0 and 1 while rendering; the previous bindings
+/// are restored afterwards.
+///
+/// If the offscreen buffers can't be resized to a new viewport, nothing is drawn until a later
+/// viewport works, and the error is available from [`last_error`](Self::last_error).
+pub struct PostProcessRenderer<R: Renderer> {
+    viewport: Viewport,
+    child: R,
+    effects: Vec<Effect>,
+    clear_color: [f32; 4],
+    programs: HashMap<Pass, ShaderProgram>,
+    vao: GLuint,
+
+    /// The child is rendered into the first, then effects go back and forth between them.
+    targets: [RenderTarget; 2],
+
+    /// Half resolution, for bloom. Only created while there is a bloom.
+    bloom_targets: Option<[RenderTarget; 2]>,
+
+    /// Why the buffers couldn't be resized to the current viewport.
+    error: Option<String>,
+}
+
+impl<R: Renderer> PostProcessRenderer<R> {
+    pub fn new(child: R) -> Result<Self, Error> {
+        let mut programs = HashMap::new();
+        programs.insert(Pass::Copy, Pass::Copy.build()?);
+
+        let mut vao = 0;
+        unsafe {
+            gl::CreateVertexArrays(1, &mut vao);
+        }
+
+        Ok(Self {
+            viewport: Viewport::default(),
+            child,
+            effects: Vec::new(),
+            clear_color: [0.0; 4],
+            programs,
+            vao,
+            targets: [RenderTarget::with_depth_stencil([1, 1])?, RenderTarget::new([1, 1])?],
+            bloom_targets: None,
+            error: None,
+        })
+    }
+
+    pub fn get_child(&self) -> &R {
+        &self.child
+    }
+
+    pub fn get_child_mut(&mut self) -> &mut R {
+        &mut self.child
+    }
+
+    /// Sets the color the offscreen buffer is cleared to before the child renders.
+    /// Defaults to transparent black.
+    pub fn set_clear_color(&mut self, color: [f32; 4]) {
+        self.clear_color = color;
+    }
+
+    pub fn effects(&self) -> &[Effect] {
+        &self.effects
+    }
+
+    /// Adds an effect at the end of the chain. Fails if its shaders don't compile.
+    pub fn push_effect<E: Into<Effect>>(&mut self, effect: E) -> Result<(), Error> {
+        let effect = effect.into();
+        self.prepare(&effect)?;
+        self.effects.push(effect);
+        Ok(())
+    }
+
+    /// Adds an effect before the one at `index`, or at the end if `index` is the length
+    /// of the chain.
+    pub fn insert_effect<E: Into<Effect>>(&mut self, index: usize, effect: E) -> Result<(), Error> {
+        if index > self.effects.len() {
+            return Err(format!("PostProcessRenderer::insert_effect: Index {} is out of range", index).into());
+        }
+
+        let effect = effect.into();
+        self.prepare(&effect)?;
+        self.effects.insert(index, effect);
+        Ok(())
+    }
+
+    /// Replaces the effect at `index`, for example to change its parameters.
+    pub fn set_effect<E: Into<Effect>>(&mut self, index: usize, effect: E) -> Result<(), Error> {
+        if index >= self.effects.len() {
+            return Err(format!("PostProcessRenderer::set_effect: Index {} is out of range", index).into());
+        }
+
+        let effect = effect.into();
+        self.prepare(&effect)?;
+        self.effects[index] = effect;
+        self.release_unused_targets();
+        Ok(())
+    }
+
+    pub fn remove_effect(&mut self, index: usize) -> Option<Effect> {
+        let effect = (index < self.effects.len()).then(|| self.effects.remove(index));
+        self.release_unused_targets();
+        effect
+    }
+
+    pub fn clear_effects(&mut self) {
+        self.effects.clear();
+        self.release_unused_targets();
+    }
+
+    /// Why the offscreen buffers couldn't be resized to the current viewport, if they
+    /// couldn't. Nothing is drawn until this is cleared by a viewport that works.
+    pub fn last_error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Compiles the passes of an effect and creates the buffers it needs, if that wasn't done yet.
+    fn prepare(&mut self, effect: &Effect) -> Result<(), Error> {
+        for &pass in Pass::for_effect(effect) {
+            if let Entry::Vacant(entry) = self.programs.entry(pass) {
+                entry.insert(pass.build()?);
+            }
+        }
+
+        if matches!(effect, Effect::Bloom(_)) && self.bloom_targets.is_none() {
+            let size = half_size(self.viewport.size);
+            self.bloom_targets = Some([RenderTarget::new(size)?, RenderTarget::new(size)?]);
+        }
+
+        Ok(())
+    }
+
+    /// Frees the bloom buffers once there is no bloom left.
+    fn release_unused_targets(&mut self) {
+        if !self.effects.iter().any(|effect| matches!(effect, Effect::Bloom(_))) {
+            self.bloom_targets = None;
+        }
+    }
+
+    fn resize_targets(&mut self) -> Result<(), Error> {
+        for target in &mut self.targets {
+            target.resize(self.viewport.size)?;
+        }
+
+        if let Some(ref mut bloom_targets) = self.bloom_targets {
+            for target in bloom_targets {
+                target.resize(half_size(self.viewport.size))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn render_frame(&self, ctx: Option<&FrameContext>) {
+        if self.viewport.is_empty() || self.error.is_some() {
+            return;
+        }
+
+        let screen = FramebufferState::capture();
+        let blend_state = BlendState::capture();
+        let texture_units = [TextureUnitState::capture(0), TextureUnitState::capture(1)];
+
+        let scene = &self.targets[0];
+        scene.bind();
+        scene.clear(self.clear_color);
+        render_child(&self.child, ctx);
+
+        unsafe {
+            gl::BindVertexArray(self.vao);
+        }
+
+        if self.effects.is_empty() {
+            self.draw_pass(Pass::Copy, scene, None, &screen, |_| {});
+        }
+
+        let mut current = 0;
+        for (i, effect) in self.effects.iter().enumerate() {
+            let last = i + 1 == self.effects.len();
+            current = self.apply_effect(effect, current, last, &screen);
+        }
+
+        for unit in &texture_units {
+            unit.restore();
+        }
+        blend_state.restore();
+        screen.restore();
+    }
+
+    /// Runs an effect on `targets[current]`, into the other target or onto the screen if it's
+    /// the last one. Returns the index of the target holding the result.
+    fn apply_effect(&self, effect: &Effect, current: usize, last: bool, screen: &FramebufferState) -> usize {
+        let other = 1 - current;
+        let source = &self.targets[current];
+        let destination = (!last).then_some(&self.targets[other]);
+
+        match *effect {
+            Effect::Blur(blur) => {
+                // Horizontally into the other target, then vertically back.
+                let texel = texel_size(source);
+                self.draw_pass(Pass::Blur, source, Some(&self.targets[other]), screen, |program| {
+                    program.set_builtin("u_direction", [texel[0], 0.0]);
+                    program.set_builtin("u_radius", blur.radius);
+                });
+                let destination = (!last).then_some(source);
+                self.draw_pass(Pass::Blur, &self.targets[other], destination, screen, |program| {
+                    program.set_builtin("u_direction", [0.0, texel[1]]);
+                    program.set_builtin("u_radius", blur.radius);
+                });
+                return current;
+            }
+            Effect::Bloom(bloom) => {
+                let Some([ref glow, ref scratch]) = self.bloom_targets else {
+                    return current;
+                };
+
+                // The glow is blurred at half resolution, so half the radius in its texels.
+                let texel = texel_size(glow);
+                self.draw_pass(Pass::BloomExtract, source, Some(glow), screen, |program| {
+                    program.set_builtin("u_threshold", bloom.threshold);
+                });
+                self.draw_pass(Pass::Blur, glow, Some(scratch), screen, |program| {
+                    program.set_builtin("u_direction", [texel[0], 0.0]);
+                    program.set_builtin("u_radius", bloom.radius * 0.5);
+                });
+                self.draw_pass(Pass::Blur, scratch, Some(glow), screen, |program| {
+                    program.set_builtin("u_direction", [0.0, texel[1]]);
+                    program.set_builtin("u_radius", bloom.radius * 0.5);
+                });
+
+                TextureUnitState::bind(1, glow.texture());
+                self.draw_pass(Pass::BloomCombine, source, destination, screen, |program| {
+                    program.set_builtin("u_intensity", bloom.intensity);
+                });
+            }
+            Effect::ColorGrading(grading) => {
+                TextureUnitState::bind(1, grading.lut);
+                self.draw_pass(Pass::ColorGrading, source, destination, screen, |program| {
+                    program.set_builtin("u_lut_size", grading.size.max(2) as f32);
+                    program.set_builtin("u_intensity", grading.intensity.clamp(0.0, 1.0));
+                });
+            }
+            Effect::Vignette(vignette) => {
+                self.draw_pass(Pass::Vignette, source, destination, screen, |program| {
+                    program.set_builtin("u_intensity", vignette.intensity.clamp(0.0, 1.0));
+                    program.set_builtin("u_radius", vignette.radius);
+                    program.set_builtin("u_softness", vignette.softness);
+                    program.set_builtin("u_color", vignette.color);
+                });
+            }
+            Effect::CrtScanlines(crt) => {
+                self.draw_pass(Pass::CrtScanlines, source, destination, screen, |program| {
+                    program.set_builtin("u_intensity", crt.intensity.clamp(0.0, 1.0));
+                    program.set_builtin("u_line_height", crt.line_height);
+                    program.set_builtin("u_curvature", crt.curvature.max(0.0));
+                });
+            }
+            Effect::Pixelate(pixelate) => {
+                self.draw_pass(Pass::Pixelate, source, destination, screen, |program| {
+                    program.set_builtin("u_pixel_size", pixelate.pixel_size);
+                });
+            }
+            Effect::Grayscale(grayscale) => {
+                self.draw_pass(Pass::Grayscale, source, destination, screen, |program| {
+                    program.set_builtin("u_amount", grayscale.amount.clamp(0.0, 1.0));
+                });
+            }
+        }
+
+        other
+    }
+
+    /// Draws `source` through a pass into `destination`, or onto the screen if it's `None`.
+    fn draw_pass<F: FnOnce(&ShaderProgram)>(
+        &self,
+        pass: Pass,
+        source: &RenderTarget,
+        destination: Option<&RenderTarget>,
+        screen: &FramebufferState,
+        set_uniforms: F,
+    ) {
+        let Some(program) = self.programs.get(&pass) else {
+            return;
+        };
+
+        match destination {
+            Some(target) => {
+                target.bind();
+                BlendMode::Opaque.apply();
+            }
+            None => {
+                screen.restore();
+                self.viewport.gl_viewport();
+                BlendMode::PremultipliedAlpha.apply();
+            }
+        }
+
+        program.set_builtin("u_texel", texel_size(source));
+        set_uniforms(program);
+        TextureUnitState::bind(0, source.texture());
+
+        program.use_program();
+        unsafe {
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+    }
+}
+
+fn half_size(size: [i32; 2]) -> [i32; 2] {
+    size.map(|s| (s / 2).max(1))
+}
+
+fn texel_size(target: &RenderTarget) -> [f32; 2] {
+    let size = target.size();
+    [1.0 / size[0] as f32, 1.0 / size[1] as f32]
+}
+
+impl<R: Renderer> Renderer for PostProcessRenderer<R> {
+    fn set_viewport(&mut self, viewport: Viewport) {
+        self.viewport = viewport;
+        if viewport.is_empty() {
+            return;
+        }
+
+        match self.resize_targets() {
+            Ok(()) => {
+                self.error = None;
+                self.child.set_viewport(self.targets[0].viewport());
+            }
+            Err(e) => self.error = Some(format!("PostProcessRenderer::set_viewport: {}", e)),
+        }
+    }
+
+    fn render(&self) {
+        self.render_frame(None);
+    }
+
+    fn update(&mut self, ctx: &mut FrameContext) {
+        self.child.update(ctx);
+    }
+
+    fn render_with_context(&self, ctx: &FrameContext) {
+        self.render_frame(Some(ctx));
+    }
+}
+
+impl<R: Renderer> Drop for PostProcessRenderer<R> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
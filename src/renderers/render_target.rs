@@ -0,0 +1,296 @@
+use gl::types::*;
+
+use crate::renderers::Viewport;
+use crate::Error;
+
+/// An offscreen framebuffer with a color texture, to render into and then sample from.
+///
+/// The color textures are RGBA8 with linear filtering, clamped at the edges. Targets created
+/// with [`with_depth_stencil`](Self::with_depth_stencil) also have a depth and stencil buffer,
+/// for renderers that clip with the stencil buffer such as rounded
+/// [`InsetRenderer`](crate::renderers::basic_renderers::InsetRenderer) corners.
+pub struct RenderTarget {
+    framebuffer: GLuint,
+    textures: Vec<GLuint>,
+    depth_stencil: Option<GLuint>,
+    size: [i32; 2],
+}
+
+impl RenderTarget {
+    pub fn new(size: [i32; 2]) -> Result<Self, Error> {
+        Self::with_attachments(size, 1, false)
+    }
+
+    pub fn with_depth_stencil(size: [i32; 2]) -> Result<Self, Error> {
+        Self::with_attachments(size, 1, true)
+    }
+
+    /// A target with several color textures, which shaders write to with
+    /// `layout(location = n) out vec4`.
+    pub fn with_attachments(size: [i32; 2], color_attachments: usize, depth_stencil: bool) -> Result<Self, Error> {
+        if color_attachments == 0 {
+            return Err("RenderTarget::with_attachments: At least one color attachment is needed".into());
+        }
+
+        let mut framebuffer = 0;
+        unsafe {
+            gl::CreateFramebuffers(1, &mut framebuffer);
+        }
+
+        let draw_buffers: Vec<GLenum> = (0..color_attachments as GLenum).map(|i| gl::COLOR_ATTACHMENT0 + i).collect();
+        unsafe {
+            gl::NamedFramebufferDrawBuffers(framebuffer, draw_buffers.len() as GLsizei, draw_buffers.as_ptr());
+        }
+
+        let mut self_ = Self {
+            framebuffer,
+            textures: vec![0; color_attachments],
+            depth_stencil: depth_stencil.then_some(0),
+            size: [0, 0],
+        };
+
+        self_.allocate(size)?;
+        Ok(self_)
+    }
+
+    /// Size in pixels. Never smaller than 1x1.
+    pub fn size(&self) -> [i32; 2] {
+        self.size
+    }
+
+    /// The first color texture, to bind for sampling what was rendered.
+    pub fn texture(&self) -> GLuint {
+        self.textures[0]
+    }
+
+    /// The color texture of an attachment, or `None` if there are fewer attachments.
+    pub fn attachment_texture(&self, index: usize) -> Option<GLuint> {
+        self.textures.get(index).copied()
+    }
+
+    pub fn framebuffer(&self) -> GLuint {
+        self.framebuffer
+    }
+
+    /// The whole target, for the viewport of renderers drawing into it.
+    pub fn viewport(&self) -> Viewport {
+        Viewport::new([0, 0], self.size)
+    }
+
+    /// Changes the size, discarding the contents. Does nothing if the size is the same.
+    /// On failure the target keeps its previous size and contents.
+    pub fn resize(&mut self, size: [i32; 2]) -> Result<(), Error> {
+        if size.map(|s| s.max(1)) == self.size {
+            return Ok(());
+        }
+
+        self.allocate(size)
+    }
+
+    /// Clears all color attachments to `color`, and the depth and stencil buffers if there
+    /// are any.
+    pub fn clear(&self, color: [f32; 4]) {
+        unsafe {
+            for index in 0..self.textures.len() {
+                gl::ClearNamedFramebufferfv(self.framebuffer, gl::COLOR, index as GLint, color.as_ptr());
+            }
+            if self.depth_stencil.is_some() {
+                gl::ClearNamedFramebufferfi(self.framebuffer, gl::DEPTH_STENCIL, 0, 1.0, 0);
+            }
+        }
+    }
+
+    /// Clears a single color attachment. Does nothing if there is no such attachment.
+    pub fn clear_attachment(&self, index: usize, color: [f32; 4]) {
+        if index < self.textures.len() {
+            unsafe {
+                gl::ClearNamedFramebufferfv(self.framebuffer, gl::COLOR, index as GLint, color.as_ptr());
+            }
+        }
+    }
+
+    /// Runs `f` with this target bound for drawing and the OpenGL viewport covering it.
+    /// The scissor and stencil tests are turned off in the meantime, since they apply to the
+    /// previous framebuffer. All of it is restored afterwards.
+    pub fn draw_into<F: FnOnce()>(&self, f: F) {
+        let previous = FramebufferState::capture();
+        self.bind();
+        f();
+        previous.restore();
+    }
+
+    /// Binds the target for drawing, sets the OpenGL viewport to cover it and turns off
+    /// the scissor and stencil tests.
+    pub(crate) fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.framebuffer);
+            gl::Disable(gl::SCISSOR_TEST);
+            gl::Disable(gl::STENCIL_TEST);
+        }
+        self.viewport().gl_viewport();
+    }
+
+    /// Creates new attachments of `size`. The previous ones are only deleted once the new ones
+    /// are complete, so on failure the target keeps its previous size and contents.
+    fn allocate(&mut self, size: [i32; 2]) -> Result<(), Error> {
+        let size = size.map(|s| s.max(1));
+        let previous_textures = self.textures.clone();
+        let previous_depth_stencil = self.depth_stencil;
+
+        unsafe {
+            for texture in &mut self.textures {
+                gl::CreateTextures(gl::TEXTURE_2D, 1, texture);
+                gl::TextureStorage2D(*texture, 1, gl::RGBA8, size[0], size[1]);
+                gl::TextureParameteri(*texture, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+                gl::TextureParameteri(*texture, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+                gl::TextureParameteri(*texture, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+                gl::TextureParameteri(*texture, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+            }
+
+            if let Some(ref mut renderbuffer) = self.depth_stencil {
+                gl::CreateRenderbuffers(1, renderbuffer);
+                gl::NamedRenderbufferStorage(*renderbuffer, gl::DEPTH24_STENCIL8, size[0], size[1]);
+            }
+        }
+
+        self.attach();
+        let status = unsafe { gl::CheckNamedFramebufferStatus(self.framebuffer, gl::DRAW_FRAMEBUFFER) };
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            let textures = std::mem::replace(&mut self.textures, previous_textures);
+            let depth_stencil = std::mem::replace(&mut self.depth_stencil, previous_depth_stencil);
+            self.attach();
+            delete_attachments(&textures, depth_stencil);
+            return Err(format!("RenderTarget: Framebuffer is incomplete (status 0x{:X})", status).into());
+        }
+
+        delete_attachments(&previous_textures, previous_depth_stencil);
+        self.size = size;
+        Ok(())
+    }
+
+    fn attach(&self) {
+        unsafe {
+            for (index, &texture) in self.textures.iter().enumerate() {
+                gl::NamedFramebufferTexture(self.framebuffer, gl::COLOR_ATTACHMENT0 + index as GLenum, texture, 0);
+            }
+
+            if let Some(renderbuffer) = self.depth_stencil {
+                gl::NamedFramebufferRenderbuffer(
+                    self.framebuffer,
+                    gl::DEPTH_STENCIL_ATTACHMENT,
+                    gl::RENDERBUFFER,
+                    renderbuffer,
+                );
+            }
+        }
+    }
+}
+
+/// Deletes the attachments of a target. OpenGL ignores the name 0 of ones never created.
+fn delete_attachments(textures: &[GLuint], depth_stencil: Option<GLuint>) {
+    unsafe {
+        gl::DeleteTextures(textures.len() as GLsizei, textures.as_ptr());
+        if let Some(renderbuffer) = depth_stencil {
+            gl::DeleteRenderbuffers(1, &renderbuffer);
+        }
+    }
+}
+
+impl Drop for RenderTarget {
+    fn drop(&mut self) {
+        delete_attachments(&self.textures, self.depth_stencil);
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.framebuffer);
+        }
+    }
+}
+
+/// The bound draw framebuffer, the OpenGL viewport and whether the scissor and stencil tests
+/// are on, so renderers drawing offscreen can put them back.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FramebufferState {
+    framebuffer: GLint,
+    viewport: [GLint; 4],
+    scissor_test: bool,
+    stencil_test: bool,
+}
+
+impl FramebufferState {
+    pub fn capture() -> Self {
+        let mut state = Self {
+            framebuffer: 0,
+            viewport: [0; 4],
+            scissor_test: false,
+            stencil_test: false,
+        };
+
+        unsafe {
+            gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut state.framebuffer);
+            gl::GetIntegerv(gl::VIEWPORT, state.viewport.as_mut_ptr());
+            state.scissor_test = gl::IsEnabled(gl::SCISSOR_TEST) == gl::TRUE;
+            state.stencil_test = gl::IsEnabled(gl::STENCIL_TEST) == gl::TRUE;
+        }
+
+        state
+    }
+
+    pub fn restore(&self) {
+        let [x, y, width, height] = self.viewport;
+        unsafe {
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.framebuffer as GLuint);
+            gl::Viewport(x, y, width, height);
+            for (cap, enabled) in [(gl::SCISSOR_TEST, self.scissor_test), (gl::STENCIL_TEST, self.stencil_test)] {
+                if enabled {
+                    gl::Enable(cap);
+                } else {
+                    gl::Disable(cap);
+                }
+            }
+        }
+    }
+}
+
+/// The 2D texture and sampler bound to a texture unit, for renderers that bind textures
+/// of their own to units the application may be using.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TextureUnitState {
+    unit: GLuint,
+    texture: GLint,
+    sampler: GLint,
+}
+
+impl TextureUnitState {
+    pub fn capture(unit: GLuint) -> Self {
+        let mut state = Self {
+            unit,
+            texture: 0,
+            sampler: 0,
+        };
+
+        unsafe {
+            let mut active = 0;
+            gl::GetIntegerv(gl::ACTIVE_TEXTURE, &mut active);
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::GetIntegerv(gl::TEXTURE_BINDING_2D, &mut state.texture);
+            gl::GetIntegerv(gl::SAMPLER_BINDING, &mut state.sampler);
+            gl::ActiveTexture(active as GLenum);
+        }
+
+        state
+    }
+
+    /// Binds `texture` to the unit, without any sampler object.
+    pub fn bind(unit: GLuint, texture: GLuint) {
+        unsafe {
+            gl::BindTextureUnit(unit, texture);
+            gl::BindSampler(unit, 0);
+        }
+    }
+
+    pub fn restore(&self) {
+        unsafe {
+            gl::BindTextureUnit(self.unit, self.texture as GLuint);
+            gl::BindSampler(self.unit, self.sampler as GLuint);
+        }
+    }
+}
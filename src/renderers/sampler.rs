@@ -0,0 +1,236 @@
+use std::ffi::CStr;
+
+use gl::types::*;
+
+use crate::Error;
+
+// Core in OpenGL 4.6 and available through the ARB and EXT texture_filter_anisotropic
+// extensions before that, with the same values. The generated 4.5 bindings don't include them.
+const TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FilterMode {
+    Nearest,
+    #[default]
+    Linear,
+}
+
+/// Which mipmap levels are sampled when minifying.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MipmapMode {
+    /// Only the base level is used.
+    #[default]
+    None,
+
+    /// The closest level is used.
+    Nearest,
+
+    /// The two closest levels are blended.
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WrapMode {
+    #[default]
+    ClampToEdge,
+    ClampToBorder,
+    Repeat,
+    MirroredRepeat,
+}
+
+impl WrapMode {
+    fn to_gl(self) -> GLenum {
+        match self {
+            WrapMode::ClampToEdge => gl::CLAMP_TO_EDGE,
+            WrapMode::ClampToBorder => gl::CLAMP_TO_BORDER,
+            WrapMode::Repeat => gl::REPEAT,
+            WrapMode::MirroredRepeat => gl::MIRRORED_REPEAT,
+        }
+    }
+}
+
+/// Describes how a texture is filtered and wrapped when sampled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerSettings {
+    pub min_filter: FilterMode,
+    pub mag_filter: FilterMode,
+    pub mipmap: MipmapMode,
+    pub wrap_s: WrapMode,
+    pub wrap_t: WrapMode,
+
+    /// Maximum anisotropy, clamped to what the driver supports. 1.0 disables anisotropic filtering.
+    /// Ignored on drivers without anisotropic filtering.
+    pub anisotropy: f32,
+
+    /// Color returned outside the texture with `WrapMode::ClampToBorder`.
+    pub border_color: [f32; 4],
+}
+
+impl SamplerSettings {
+    /// Linear filtering, clamped to the edge.
+    pub fn linear() -> Self {
+        Self {
+            min_filter: FilterMode::Linear,
+            mag_filter: FilterMode::Linear,
+            mipmap: MipmapMode::None,
+            wrap_s: WrapMode::ClampToEdge,
+            wrap_t: WrapMode::ClampToEdge,
+            anisotropy: 1.0,
+            border_color: [0.0, 0.0, 0.0, 0.0],
+        }
+    }
+
+    /// Nearest filtering, clamped to the edge.
+    pub fn nearest() -> Self {
+        Self {
+            min_filter: FilterMode::Nearest,
+            mag_filter: FilterMode::Nearest,
+            ..Self::linear()
+        }
+    }
+
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap_s = wrap;
+        self.wrap_t = wrap;
+        self
+    }
+
+    fn min_filter_gl(&self) -> GLenum {
+        use FilterMode::*;
+        match (self.min_filter, self.mipmap) {
+            (Nearest, MipmapMode::None) => gl::NEAREST,
+            (Linear, MipmapMode::None) => gl::LINEAR,
+            (Nearest, MipmapMode::Nearest) => gl::NEAREST_MIPMAP_NEAREST,
+            (Linear, MipmapMode::Nearest) => gl::LINEAR_MIPMAP_NEAREST,
+            (Nearest, MipmapMode::Linear) => gl::NEAREST_MIPMAP_LINEAR,
+            (Linear, MipmapMode::Linear) => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+}
+
+impl Default for SamplerSettings {
+    fn default() -> Self {
+        Self::linear()
+    }
+}
+
+/// An OpenGL sampler object. While bound to a texture unit, it overrides the
+/// filtering and wrapping parameters of whatever texture is bound there.
+pub struct Sampler {
+    sampler: GLuint,
+    settings: SamplerSettings,
+
+    /// The largest anisotropy the driver supports, 1.0 if it has no anisotropic filtering.
+    max_anisotropy: f32,
+}
+
+impl Sampler {
+    pub fn new(settings: SamplerSettings) -> Result<Self, Error> {
+        let mut sampler = 0;
+        unsafe {
+            gl::CreateSamplers(1, &mut sampler);
+        }
+
+        if sampler == 0 {
+            return Err("gl::CreateSamplers returned an invalid sampler ID (0)".into());
+        }
+
+        let mut self_ = Self {
+            sampler,
+            settings,
+            max_anisotropy: max_supported_anisotropy(),
+        };
+        self_.set_settings(settings);
+        Ok(self_)
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.sampler
+    }
+
+    pub fn settings(&self) -> &SamplerSettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: SamplerSettings) {
+        self.settings = settings;
+
+        let mag_filter = match settings.mag_filter {
+            FilterMode::Nearest => gl::NEAREST,
+            FilterMode::Linear => gl::LINEAR,
+        };
+
+        unsafe {
+            gl::SamplerParameteri(self.sampler, gl::TEXTURE_MIN_FILTER, settings.min_filter_gl() as GLint);
+            gl::SamplerParameteri(self.sampler, gl::TEXTURE_MAG_FILTER, mag_filter as GLint);
+            gl::SamplerParameteri(self.sampler, gl::TEXTURE_WRAP_S, settings.wrap_s.to_gl() as GLint);
+            gl::SamplerParameteri(self.sampler, gl::TEXTURE_WRAP_T, settings.wrap_t.to_gl() as GLint);
+            gl::SamplerParameterfv(self.sampler, gl::TEXTURE_BORDER_COLOR, settings.border_color.as_ptr());
+
+            if self.max_anisotropy > 1.0 {
+                let anisotropy = settings.anisotropy.clamp(1.0, self.max_anisotropy);
+                gl::SamplerParameterf(self.sampler, TEXTURE_MAX_ANISOTROPY, anisotropy);
+            }
+        }
+    }
+
+    /// Binds the sampler to a texture unit (0 for `GL_TEXTURE0`, and so on).
+    pub fn bind(&self, texture_unit: GLuint) {
+        unsafe {
+            gl::BindSampler(texture_unit, self.sampler);
+        }
+    }
+
+    /// Removes any sampler from a texture unit, so the texture's own parameters apply again.
+    pub fn unbind(texture_unit: GLuint) {
+        unsafe {
+            gl::BindSampler(texture_unit, 0);
+        }
+    }
+}
+
+/// Queries the maximum anisotropy, or returns 1.0 if the context has neither
+/// OpenGL 4.6 nor one of the anisotropic filtering extensions.
+fn max_supported_anisotropy() -> f32 {
+    if !has_anisotropic_filtering() {
+        return 1.0;
+    }
+
+    let mut max_anisotropy = 1.0;
+    unsafe {
+        gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max_anisotropy);
+    }
+    max_anisotropy.max(1.0)
+}
+
+fn has_anisotropic_filtering() -> bool {
+    let (mut major, mut minor, mut count) = (0, 0, 0);
+    unsafe {
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+        gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+    }
+
+    if (major, minor) >= (4, 6) {
+        return true;
+    }
+
+    (0..count.max(0) as GLuint).any(|i| {
+        let name = unsafe { gl::GetStringi(gl::EXTENSIONS, i) };
+        if name.is_null() {
+            return false;
+        }
+
+        let name = unsafe { CStr::from_ptr(name as *const _) }.to_bytes();
+        name == b"GL_ARB_texture_filter_anisotropic" || name == b"GL_EXT_texture_filter_anisotropic"
+    })
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteSamplers(1, &self.sampler);
+        }
+    }
+}
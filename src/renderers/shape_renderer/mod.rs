@@ -0,0 +1,392 @@
+use std::cell::Cell;
+use std::f32::consts::{PI, TAU};
+
+use crate::Error;
+use gl;
+
+use crate::renderers::{
+    Renderer,
+    Viewport,
+    Transformable,
+    Mat4,
+    blend::{BlendMode, BlendState},
+};
+use crate::shader::ShaderProgram;
+
+mod tessellate;
+
+use tessellate::{arc_points, ShapeVertex, Tessellator, FLOATS_PER_VERTEX};
+
+const VCODE : &str = r#"
+#version 450 core
+layout (location = 0) in vec2 in_pos;
+layout (location = 1) in vec4 in_color;
+layout (location = 2) in vec2 in_aa;
+out vec4 v_color;
+out vec2 v_aa;
+uniform mat4 u_transform;
+uniform vec2 u_viewport_size;
+
+void main() {
+    vec2 pixel = (u_transform * vec4(in_pos, 0.0, 1.0)).xy;
+    gl_Position = vec4(pixel / u_viewport_size * 2.0 - 1.0, 0.0, 1.0);
+    v_color = in_color;
+    v_aa = in_aa;
+}
+"#;
+
+const FCODE : &str = r#"
+#version 450 core
+in vec4 v_color;
+in vec2 v_aa;
+out vec4 fColor;
+layout (location = 1) out vec4 fNormal;
+
+void main() {
+    float coverage = clamp((1.0 - abs(v_aa.x)) * v_aa.y, 0.0, 1.0);
+    fColor = vec4(v_color.rgb, v_color.a * coverage);
+
+    // A flat surface, for the scene of a lighting renderer with normal mapping.
+    fNormal = vec4(0.5, 0.5, 1.0, fColor.a);
+}
+"#;
+
+/// How the segments of a stroke are connected at corners.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineJoin {
+    /// The outer edges are extended until they meet. Falls back to `Bevel`
+    /// past the stroke's miter limit.
+    #[default]
+    Miter,
+
+    /// The corner is cut off straight.
+    Bevel,
+
+    /// The corner is rounded off.
+    Round,
+}
+
+/// How the ends of an open stroke are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineCap {
+    /// The stroke ends exactly at the end point.
+    #[default]
+    Butt,
+
+    /// The stroke extends past the end point by half its thickness.
+    Square,
+
+    /// The stroke ends in a half circle around the end point.
+    Round,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StrokeStyle {
+    /// Width of the stroke, in pixels.
+    pub thickness: f32,
+    pub join: LineJoin,
+    pub cap: LineCap,
+
+    /// The longest a miter join can get, as a multiple of half the thickness.
+    pub miter_limit: f32,
+}
+
+impl StrokeStyle {
+    pub fn new(thickness: f32) -> Self {
+        Self {
+            thickness,
+            join: LineJoin::Miter,
+            cap: LineCap::Butt,
+            miter_limit: 4.0,
+        }
+    }
+
+    pub fn with_join(mut self, join: LineJoin) -> Self {
+        self.join = join;
+        self
+    }
+
+    pub fn with_cap(mut self, cap: LineCap) -> Self {
+        self.cap = cap;
+        self
+    }
+}
+
+impl Default for StrokeStyle {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl From<f32> for StrokeStyle {
+    fn from(thickness: f32) -> Self {
+        Self::new(thickness)
+    }
+}
+
+/// Draws filled and stroked shapes in an immediate-mode style.
+///
+/// Shapes are given in pixels, with `[0.0, 0.0]` at the bottom-left corner of the viewport,
+/// and are tessellated on the CPU as they are added. Everything added since the last
+/// [`clear`](Self::clear) is drawn in a single call, so a typical frame clears the renderer,
+/// adds its shapes and renders. Edges are anti-aliased unless that is turned off.
+pub struct ShapeRenderer {
+    viewport: Viewport,
+    program: ShaderProgram,
+    vao: u32,
+    buffer: u32,
+
+    vertices: Vec<ShapeVertex>,
+    anti_aliasing: bool,
+
+    /// Number of vertices the buffer has room for.
+    vertex_capacity: Cell<usize>,
+    dirty: Cell<bool>,
+}
+
+impl ShapeRenderer {
+    pub fn new() -> Result<Self, Error> {
+        let program = ShaderProgram::new(VCODE, FCODE)?;
+
+        let mut buffer = 0;
+        let mut vao = 0;
+        unsafe {
+            gl::CreateBuffers(1, &mut buffer);
+            gl::GenVertexArrays(1, &mut vao);
+        }
+
+        glh::enable_interleaved_vertex_array_attributes(
+            vao,
+            buffer,
+            gl::FLOAT,
+            false,
+            0,
+            &[2, 4, 2],
+        )?;
+
+        let mut self_ = Self {
+            viewport: Viewport::default(),
+            program,
+            vao,
+            buffer,
+            vertices: Vec::new(),
+            anti_aliasing: true,
+            vertex_capacity: Cell::new(0),
+            dirty: Cell::new(false),
+        };
+
+        self_.clear_transform();
+
+        Ok(self_)
+    }
+
+    /// Turns anti-aliasing on or off for shapes added from now on. On by default.
+    pub fn set_anti_aliasing(&mut self, enabled: bool) {
+        self.anti_aliasing = enabled;
+    }
+
+    /// Removes all shapes.
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.dirty.set(true);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    fn tessellator(&mut self) -> Tessellator<'_> {
+        self.dirty.set(true);
+        Tessellator {
+            out: &mut self.vertices,
+            anti_aliasing: self.anti_aliasing,
+        }
+    }
+
+    /// `pos` is the bottom-left corner.
+    pub fn fill_rect(&mut self, pos: [f32; 2], size: [f32; 2], color: [f32; 4]) {
+        self.fill_polygon(&rect_points(pos, size), color);
+    }
+
+    pub fn stroke_rect<S: Into<StrokeStyle>>(&mut self, pos: [f32; 2], size: [f32; 2], style: S, color: [f32; 4]) {
+        self.stroke_polygon(&rect_points(pos, size), style, color);
+    }
+
+    pub fn fill_rounded_rect(&mut self, pos: [f32; 2], size: [f32; 2], radius: f32, color: [f32; 4]) {
+        self.fill_polygon(&rounded_rect_points(pos, size, radius), color);
+    }
+
+    pub fn stroke_rounded_rect<S: Into<StrokeStyle>>(
+        &mut self,
+        pos: [f32; 2],
+        size: [f32; 2],
+        radius: f32,
+        style: S,
+        color: [f32; 4],
+    ) {
+        self.stroke_polygon(&rounded_rect_points(pos, size, radius), style, color);
+    }
+
+    pub fn fill_circle(&mut self, center: [f32; 2], radius: f32, color: [f32; 4]) {
+        self.fill_ellipse(center, [radius, radius], color);
+    }
+
+    pub fn stroke_circle<S: Into<StrokeStyle>>(&mut self, center: [f32; 2], radius: f32, style: S, color: [f32; 4]) {
+        self.stroke_ellipse(center, [radius, radius], style, color);
+    }
+
+    pub fn fill_ellipse(&mut self, center: [f32; 2], radii: [f32; 2], color: [f32; 4]) {
+        self.fill_polygon(&ellipse_points(center, radii), color);
+    }
+
+    pub fn stroke_ellipse<S: Into<StrokeStyle>>(&mut self, center: [f32; 2], radii: [f32; 2], style: S, color: [f32; 4]) {
+        self.stroke_polygon(&ellipse_points(center, radii), style, color);
+    }
+
+    /// Fills a pie slice. Angles are in radians, counter-clockwise from the positive x axis.
+    /// A sweep of a full turn or more fills the whole circle.
+    pub fn fill_arc(&mut self, center: [f32; 2], radius: f32, start_angle: f32, end_angle: f32, color: [f32; 4]) {
+        if (end_angle - start_angle).abs() >= TAU {
+            self.fill_circle(center, radius, color);
+            return;
+        }
+
+        let mut points = vec![center];
+        arc_points(center, [radius, radius], start_angle, end_angle, &mut points);
+        self.fill_polygon(&points, color);
+    }
+
+    /// Strokes part of a circle. Angles are in radians, counter-clockwise from the positive x axis.
+    pub fn stroke_arc<S: Into<StrokeStyle>>(
+        &mut self,
+        center: [f32; 2],
+        radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+        style: S,
+        color: [f32; 4],
+    ) {
+        let mut points = Vec::new();
+        arc_points(center, [radius, radius], start_angle, end_angle, &mut points);
+        self.polyline(&points, style, color);
+    }
+
+    /// Fills a simple polygon, which may be concave but must not intersect itself.
+    pub fn fill_polygon(&mut self, points: &[[f32; 2]], color: [f32; 4]) {
+        self.tessellator().fill_polygon(points, color);
+    }
+
+    /// Strokes the outline of a polygon, joining the last point back to the first.
+    pub fn stroke_polygon<S: Into<StrokeStyle>>(&mut self, points: &[[f32; 2]], style: S, color: [f32; 4]) {
+        self.tessellator().stroke(points, true, &style.into(), color);
+    }
+
+    /// Strokes an open line through the given points.
+    pub fn polyline<S: Into<StrokeStyle>>(&mut self, points: &[[f32; 2]], style: S, color: [f32; 4]) {
+        self.tessellator().stroke(points, false, &style.into(), color);
+    }
+
+    pub fn line<S: Into<StrokeStyle>>(&mut self, from: [f32; 2], to: [f32; 2], style: S, color: [f32; 4]) {
+        self.polyline(&[from, to], style, color);
+    }
+
+    fn upload_vertices(&self) {
+        let byte_len = std::mem::size_of_val(self.vertices.as_slice());
+        unsafe {
+            if self.vertices.len() > self.vertex_capacity.get() {
+                let capacity = self.vertices.len().next_power_of_two();
+                let capacity_bytes = capacity * FLOATS_PER_VERTEX * std::mem::size_of::<f32>();
+                gl::NamedBufferData(
+                    self.buffer,
+                    capacity_bytes as isize,
+                    std::ptr::null(),
+                    gl::DYNAMIC_DRAW,
+                );
+                self.vertex_capacity.set(capacity);
+            }
+
+            gl::NamedBufferSubData(self.buffer, 0, byte_len as isize, self.vertices.as_ptr() as *const _);
+        }
+
+        self.dirty.set(false);
+    }
+}
+
+fn rect_points(pos: [f32; 2], size: [f32; 2]) -> [[f32; 2]; 4] {
+    let [x, y] = pos;
+    let [w, h] = size;
+    [[x, y], [x + w, y], [x + w, y + h], [x, y + h]]
+}
+
+fn rounded_rect_points(pos: [f32; 2], size: [f32; 2], radius: f32) -> Vec<[f32; 2]> {
+    let r = radius.min(size[0].abs() * 0.5).min(size[1].abs() * 0.5);
+    if r <= 0.0 {
+        return rect_points(pos, size).to_vec();
+    }
+
+    let [x, y] = pos;
+    let [w, h] = size;
+    let corners = [
+        ([x + w - r, y + r], -0.5 * PI),
+        ([x + w - r, y + h - r], 0.0),
+        ([x + r, y + h - r], 0.5 * PI),
+        ([x + r, y + r], PI),
+    ];
+
+    let mut points = Vec::new();
+    for (center, start) in corners {
+        arc_points(center, [r, r], start, start + 0.5 * PI, &mut points);
+    }
+    points
+}
+
+fn ellipse_points(center: [f32; 2], radii: [f32; 2]) -> Vec<[f32; 2]> {
+    let mut points = Vec::new();
+    arc_points(center, radii, 0.0, 2.0 * PI, &mut points);
+    points.pop(); // Same as the first.
+    points
+}
+
+impl Renderer for ShapeRenderer {
+    fn set_viewport(&mut self, viewport: Viewport) {
+        self.viewport = viewport;
+        self.program.set_builtin("u_viewport_size", [viewport.size[0] as f32, viewport.size[1] as f32]);
+    }
+
+    fn render(&self) {
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        if self.dirty.get() {
+            self.upload_vertices();
+        }
+
+        self.viewport.gl_viewport();
+        let blend_state = BlendState::capture();
+        BlendMode::Alpha.apply();
+        self.program.use_program();
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, self.vertices.len() as i32);
+        }
+        blend_state.restore();
+    }
+}
+
+/// The transform maps shape coordinates to pixels within the viewport.
+/// It is the identity by default.
+impl Transformable for ShapeRenderer {
+    fn set_transform(&mut self, transform: Mat4) {
+        self.program.set_builtin("u_transform", transform);
+    }
+}
+
+impl Drop for ShapeRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+            gl::DeleteBuffers(1, &self.buffer);
+        }
+    }
+}
@@ -0,0 +1,565 @@
+//! CPU tessellation of shapes into triangles.
+//!
+//! Anti-aliasing works by pushing every edge half a pixel outwards and storing, per vertex,
+//! where it sits across the shape's edge (`aa`). The fragment shader turns that into coverage,
+//! fading the outermost pixel. Interior vertices get full coverage.
+
+use std::f32::consts::PI;
+
+use super::{LineCap, LineJoin, StrokeStyle};
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub(super) struct ShapeVertex {
+    pub pos: [f32; 2],
+    pub color: [f32; 4],
+
+    /// `x` is the position across the edge, where 0.0 is fully inside and ±1.0 is the
+    /// outer boundary; `y` is the distance in pixels that range covers.
+    pub aa: [f32; 2],
+}
+
+pub(super) const FLOATS_PER_VERTEX: usize = 8;
+
+type Vec2 = [f32; 2];
+
+fn add(a: Vec2, b: Vec2) -> Vec2 {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+fn sub(a: Vec2, b: Vec2) -> Vec2 {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn scale(a: Vec2, s: f32) -> Vec2 {
+    [a[0] * s, a[1] * s]
+}
+
+fn dot(a: Vec2, b: Vec2) -> f32 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+fn cross(a: Vec2, b: Vec2) -> f32 {
+    a[0] * b[1] - a[1] * b[0]
+}
+
+fn length(a: Vec2) -> f32 {
+    dot(a, a).sqrt()
+}
+
+fn normalize(a: Vec2) -> Vec2 {
+    let len = length(a);
+    if len > 0.0 { scale(a, 1.0 / len) } else { [0.0, 0.0] }
+}
+
+/// Perpendicular pointing to the left of `a`.
+fn perp(a: Vec2) -> Vec2 {
+    [-a[1], a[0]]
+}
+
+fn from_angle(angle: f32) -> Vec2 {
+    [angle.cos(), angle.sin()]
+}
+
+/// Number of segments needed to keep a circular arc within a quarter pixel of the true curve.
+pub(super) fn arc_segments(radius: f32, sweep: f32) -> usize {
+    let r = radius.max(0.5);
+    let step = 2.0 * (1.0 - 0.25 / r).clamp(-1.0, 1.0).acos();
+    ((sweep.abs() / step).ceil() as usize).clamp(2, 512)
+}
+
+/// Points along an elliptical arc, both ends included.
+pub(super) fn arc_points(center: Vec2, radii: Vec2, start: f32, end: f32, out: &mut Vec<Vec2>) {
+    let segments = arc_segments(radii[0].max(radii[1]), end - start);
+    for i in 0..=segments {
+        let angle = start + (end - start) * i as f32 / segments as f32;
+        out.push([center[0] + radii[0] * angle.cos(), center[1] + radii[1] * angle.sin()]);
+    }
+}
+
+/// Removes repeated points, including a last point equal to the first.
+fn dedup_points(points: &[Vec2]) -> Vec<Vec2> {
+    let mut result: Vec<Vec2> = Vec::with_capacity(points.len());
+    for &p in points {
+        if result.last().is_none_or(|&last| length(sub(p, last)) > 1e-4) {
+            result.push(p);
+        }
+    }
+
+    while result.len() > 1 && length(sub(result[0], result[result.len() - 1])) <= 1e-4 {
+        result.pop();
+    }
+
+    result
+}
+
+fn signed_area(points: &[Vec2]) -> f32 {
+    let n = points.len();
+    (0..n)
+        .map(|i| cross(points[i], points[(i + 1) % n]))
+        .sum::<f32>()
+        * 0.5
+}
+
+fn point_in_triangle(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    let d1 = cross(sub(b, a), sub(p, a));
+    let d2 = cross(sub(c, b), sub(p, b));
+    let d3 = cross(sub(a, c), sub(p, c));
+    d1 >= 0.0 && d2 >= 0.0 && d3 >= 0.0
+}
+
+/// Ear-clipping triangulation of a simple, counter-clockwise polygon.
+/// Falls back to a fan for whatever is left if the polygon turns out not to be simple.
+fn triangulate(points: &[Vec2]) -> Vec<[usize; 3]> {
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len().saturating_sub(2));
+
+    while remaining.len() > 3 {
+        let m = remaining.len();
+        let ear = (0..m).find(|&i| {
+            let a = remaining[(i + m - 1) % m];
+            let b = remaining[i];
+            let c = remaining[(i + 1) % m];
+            let (pa, pb, pc) = (points[a], points[b], points[c]);
+
+            if cross(sub(pb, pa), sub(pc, pb)) <= 0.0 {
+                return false;
+            }
+
+            !remaining.iter().any(|&j| {
+                j != a && j != b && j != c && point_in_triangle(points[j], pa, pb, pc)
+            })
+        });
+
+        match ear {
+            Some(i) => {
+                let a = remaining[(i + m - 1) % m];
+                let c = remaining[(i + 1) % m];
+                triangles.push([a, remaining[i], c]);
+                remaining.remove(i);
+            }
+            None => break,
+        }
+    }
+
+    for i in 1..remaining.len().saturating_sub(1) {
+        triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+    }
+
+    triangles
+}
+
+pub(super) struct Tessellator<'a> {
+    pub out: &'a mut Vec<ShapeVertex>,
+    pub anti_aliasing: bool,
+}
+
+impl Tessellator<'_> {
+    fn push(&mut self, pos: Vec2, color: [f32; 4], aa: Vec2) {
+        self.out.push(ShapeVertex { pos, color, aa });
+    }
+
+    fn triangle(&mut self, v: [(Vec2, f32); 3], color: [f32; 4], aa_width: f32) {
+        for (pos, side) in v {
+            let aa = if self.anti_aliasing { [side, aa_width] } else { [0.0, 1.0] };
+            self.push(pos, color, aa);
+        }
+    }
+
+    /// Fills a simple polygon, convex or not, in either winding order.
+    /// The triangles produced are always counter-clockwise.
+    pub fn fill_polygon(&mut self, points: &[Vec2], color: [f32; 4]) {
+        let mut points = dedup_points(points);
+        if points.len() < 3 {
+            return;
+        }
+
+        let area = signed_area(&points);
+        if area.abs() < 1e-6 {
+            return;
+        }
+        if area < 0.0 {
+            points.reverse();
+        }
+
+        let n = points.len();
+        let (inner, outer) = if self.anti_aliasing {
+            // Outward miter normals, as in a counter-clockwise polygon the outside is on the right.
+            let edge_normals: Vec<Vec2> = (0..n)
+                .map(|i| {
+                    let d = normalize(sub(points[(i + 1) % n], points[i]));
+                    [d[1], -d[0]]
+                })
+                .collect();
+
+            let mut inner = Vec::with_capacity(n);
+            let mut outer = Vec::with_capacity(n);
+            for i in 0..n {
+                let mut dm = scale(add(edge_normals[(i + n - 1) % n], edge_normals[i]), 0.5);
+                let d2 = dot(dm, dm);
+                if d2 > 1e-6 {
+                    dm = scale(dm, (1.0 / d2).min(100.0));
+                }
+                inner.push(sub(points[i], scale(dm, 0.5)));
+                outer.push(add(points[i], scale(dm, 0.5)));
+            }
+            (inner, outer)
+        } else {
+            (points.clone(), Vec::new())
+        };
+
+        for [a, b, c] in triangulate(&points) {
+            self.triangle([(inner[a], 0.0), (inner[b], 0.0), (inner[c], 0.0)], color, 1.0);
+        }
+
+        if self.anti_aliasing {
+            for i in 0..n {
+                let j = (i + 1) % n;
+                self.triangle([(inner[i], 0.0), (outer[j], 1.0), (inner[j], 0.0)], color, 1.0);
+                self.triangle([(inner[i], 0.0), (outer[i], 1.0), (outer[j], 1.0)], color, 1.0);
+            }
+        }
+    }
+
+    /// Strokes a polyline, closing it back to the first point if `closed` is set.
+    pub fn stroke(&mut self, points: &[Vec2], closed: bool, style: &StrokeStyle, mut color: [f32; 4]) {
+        let points = if closed {
+            dedup_points(points)
+        } else {
+            // An open line may end where it started, so only drop consecutive repeats.
+            let mut points = points.to_vec();
+            points.dedup_by(|a, b| length(sub(*a, *b)) <= 1e-4);
+            points
+        };
+
+        let mut half_width = style.thickness * 0.5;
+        if half_width <= 0.0 {
+            return;
+        }
+
+        // Lines thinner than a pixel are drawn one pixel wide and faded instead.
+        if self.anti_aliasing && half_width < 0.5 {
+            color[3] *= half_width * 2.0;
+            half_width = 0.5;
+        }
+
+        let ext = if self.anti_aliasing { half_width + 0.5 } else { half_width };
+
+        let n = points.len();
+        if n < 2 {
+            if n == 1 && style.cap == LineCap::Round {
+                self.round_fan(points[0], ext, 0.0, 2.0 * PI, color);
+            }
+            return;
+        }
+
+        let segment_count = if closed { n } else { n - 1 };
+        let dirs: Vec<Vec2> = (0..segment_count)
+            .map(|k| normalize(sub(points[(k + 1) % n], points[k])))
+            .collect();
+        let lengths: Vec<f32> = (0..segment_count)
+            .map(|k| length(sub(points[(k + 1) % n], points[k])))
+            .collect();
+
+        // [start_left, start_right, end_left, end_right] for each segment.
+        let mut quads: Vec<[Vec2; 4]> = (0..segment_count)
+            .map(|k| {
+                let offset = scale(perp(dirs[k]), ext);
+                let a = points[k];
+                let b = points[(k + 1) % n];
+                [add(a, offset), sub(a, offset), add(b, offset), sub(b, offset)]
+            })
+            .collect();
+
+        let join_vertices: Vec<usize> = if closed { (0..n).collect() } else { (1..n - 1).collect() };
+        for i in join_vertices {
+            let prev = (i + segment_count - 1) % segment_count;
+            let next = i % segment_count;
+            self.join(points[i], [prev, next], &dirs, &lengths, &mut quads, ext, style, color);
+        }
+
+        if !closed {
+            let last = segment_count - 1;
+            match style.cap {
+                LineCap::Butt => {}
+                LineCap::Square => {
+                    let back = scale(dirs[0], -half_width);
+                    quads[0][0] = add(quads[0][0], back);
+                    quads[0][1] = add(quads[0][1], back);
+                    let forward = scale(dirs[last], half_width);
+                    quads[last][2] = add(quads[last][2], forward);
+                    quads[last][3] = add(quads[last][3], forward);
+                }
+                LineCap::Round => {
+                    let start_angle = start_angle_of(perp(dirs[0]));
+                    self.round_fan(points[0], ext, start_angle, start_angle + PI, color);
+                    let end_angle = start_angle_of(scale(perp(dirs[last]), -1.0));
+                    self.round_fan(points[n - 1], ext, end_angle, end_angle + PI, color);
+                }
+            }
+        }
+
+        for [sl, sr, el, er] in quads {
+            self.triangle([(sl, 1.0), (sr, -1.0), (er, -1.0)], color, ext);
+            self.triangle([(sl, 1.0), (er, -1.0), (el, 1.0)], color, ext);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn join(
+        &mut self,
+        p: Vec2,
+        [prev, next]: [usize; 2],
+        dirs: &[Vec2],
+        lengths: &[f32],
+        quads: &mut [[Vec2; 4]],
+        ext: f32,
+        style: &StrokeStyle,
+        color: [f32; 4],
+    ) {
+        let (d0, d1) = (dirs[prev], dirs[next]);
+        let turn = cross(d0, d1);
+        if turn.abs() < 1e-6 && dot(d0, d1) > 0.0 {
+            return;
+        }
+
+        let (n0, n1) = (perp(d0), perp(d1));
+        let bisector = normalize(add(n0, n1));
+        let cos_half = dot(bisector, n0);
+        let miter_length = if cos_half > 1e-4 { ext / cos_half } else { f32::INFINITY };
+
+        // Turning left puts the outside of the corner on the right, and vice versa.
+        let outer_sign = if turn > 0.0 { -1.0 } else { 1.0 };
+        let inner_side = -outer_sign;
+
+        // Where the inner edges of the two segments meet, kept from running past
+        // the far end of the shorter segment.
+        let max_inner = (lengths[prev].min(lengths[next]).powi(2) + ext * ext).sqrt();
+        let inner = add(p, scale(bisector, inner_side * miter_length.min(max_inner)));
+
+        let (inner_prev, inner_next, outer_prev, outer_next) = if outer_sign < 0.0 {
+            (2, 0, 3, 1)
+        } else {
+            (3, 1, 2, 0)
+        };
+        quads[prev][inner_prev] = inner;
+        quads[next][inner_next] = inner;
+
+        let o0 = add(p, scale(n0, outer_sign * ext));
+        let o1 = add(p, scale(n1, outer_sign * ext));
+
+        let join = match style.join {
+            LineJoin::Miter if miter_length / ext <= style.miter_limit => {
+                let miter = add(p, scale(bisector, outer_sign * miter_length));
+                quads[prev][outer_prev] = miter;
+                quads[next][outer_next] = miter;
+                return;
+            }
+            LineJoin::Miter => LineJoin::Bevel,
+            other => other,
+        };
+
+        match join {
+            LineJoin::Round => {
+                let a0 = start_angle_of(sub(o0, p));
+                let mut sweep = start_angle_of(sub(o1, p)) - a0;
+                // Go around the outside of the corner.
+                if outer_sign < 0.0 {
+                    while sweep <= 0.0 { sweep += 2.0 * PI; }
+                } else {
+                    while sweep >= 0.0 { sweep -= 2.0 * PI; }
+                }
+
+                let segments = arc_segments(ext, sweep);
+                let mut last = o0;
+                for s in 1..=segments {
+                    let point = add(p, scale(from_angle(a0 + sweep * s as f32 / segments as f32), ext));
+                    self.triangle([(inner, inner_side), (last, outer_sign), (point, outer_sign)], color, ext);
+                    last = point;
+                }
+            }
+            _ => {
+                self.triangle([(inner, inner_side), (o0, outer_sign), (o1, outer_sign)], color, ext);
+            }
+        }
+    }
+
+    /// A fan from `center` to an arc of the given radius, fading out towards the arc.
+    fn round_fan(&mut self, center: Vec2, radius: f32, start: f32, end: f32, color: [f32; 4]) {
+        let segments = arc_segments(radius, end - start);
+        let mut last = add(center, scale(from_angle(start), radius));
+        for s in 1..=segments {
+            let angle = start + (end - start) * s as f32 / segments as f32;
+            let point = add(center, scale(from_angle(angle), radius));
+            self.triangle([(center, 0.0), (last, 1.0), (point, 1.0)], color, radius);
+            last = point;
+        }
+    }
+}
+
+fn start_angle_of(v: Vec2) -> f32 {
+    v[1].atan2(v[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: [f32; 4] = [1.0; 4];
+
+    fn fill(points: &[Vec2], anti_aliasing: bool) -> Vec<[Vec2; 3]> {
+        let mut out = Vec::new();
+        Tessellator { out: &mut out, anti_aliasing }.fill_polygon(points, WHITE);
+        assert_eq!(out.len() % 3, 0);
+        out.chunks(3).map(|t| [t[0].pos, t[1].pos, t[2].pos]).collect()
+    }
+
+    fn area(t: &[Vec2; 3]) -> f32 {
+        cross(sub(t[1], t[0]), sub(t[2], t[0])) * 0.5
+    }
+
+    fn total_area(triangles: &[[Vec2; 3]]) -> f32 {
+        triangles.iter().map(area).sum()
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-3, "{} is not close to {}", actual, expected);
+    }
+
+    /// Even-odd point in polygon test.
+    fn inside(p: Vec2, polygon: &[Vec2]) -> bool {
+        let n = polygon.len();
+        let mut result = false;
+        for i in 0..n {
+            let (a, b) = (polygon[i], polygon[(i + 1) % n]);
+            if (a[1] > p[1]) != (b[1] > p[1]) && p[0] < a[0] + (p[1] - a[1]) / (b[1] - a[1]) * (b[0] - a[0]) {
+                result = !result;
+            }
+        }
+        result
+    }
+
+    /// Checks that the triangles are counter-clockwise, lie inside the polygon and cover its area exactly.
+    fn assert_covers(triangles: &[[Vec2; 3]], polygon: &[Vec2], expected_area: f32) {
+        for t in triangles {
+            assert!(area(t) >= 0.0, "{:?} is clockwise", t);
+            if area(t) > 1e-6 {
+                let centroid = scale(add(add(t[0], t[1]), t[2]), 1.0 / 3.0);
+                assert!(inside(centroid, polygon), "{:?} lies outside the polygon", t);
+            }
+        }
+        assert_close(total_area(triangles), expected_area);
+    }
+
+    const SQUARE: [Vec2; 4] = [[0.0, 0.0], [2.0, 0.0], [2.0, 2.0], [0.0, 2.0]];
+
+    // An L shape, concave at (1, 1).
+    const L_SHAPE: [Vec2; 6] = [[0.0, 0.0], [2.0, 0.0], [2.0, 1.0], [1.0, 1.0], [1.0, 2.0], [0.0, 2.0]];
+
+    #[test]
+    fn fills_convex_polygon() {
+        let triangles = fill(&SQUARE, false);
+        assert_eq!(triangles.len(), 2);
+        assert_covers(&triangles, &SQUARE, 4.0);
+
+        let hexagon: Vec<Vec2> = (0..6).map(|i| from_angle(i as f32 * PI / 3.0)).collect();
+        let triangles = fill(&hexagon, false);
+        assert_eq!(triangles.len(), 4);
+        assert_covers(&triangles, &hexagon, signed_area(&hexagon));
+    }
+
+    #[test]
+    fn fills_concave_polygon() {
+        let triangles = fill(&L_SHAPE, false);
+        assert_eq!(triangles.len(), 4);
+        assert_covers(&triangles, &L_SHAPE, 3.0);
+
+        // A star with five concave vertices.
+        let star: Vec<Vec2> = (0..10)
+            .map(|i| scale(from_angle(i as f32 * PI / 5.0), if i % 2 == 0 { 2.0 } else { 0.8 }))
+            .collect();
+        let triangles = fill(&star, false);
+        assert_eq!(triangles.len(), 8);
+        assert_covers(&triangles, &star, signed_area(&star));
+    }
+
+    #[test]
+    fn clockwise_input_gives_counter_clockwise_triangles() {
+        let mut clockwise = L_SHAPE.to_vec();
+        clockwise.reverse();
+        assert!(signed_area(&clockwise) < 0.0);
+        assert_covers(&fill(&clockwise, false), &L_SHAPE, 3.0);
+    }
+
+    #[test]
+    fn collinear_points_on_an_edge() {
+        let points = [[0.0, 0.0], [1.0, 0.0], [2.0, 0.0], [2.0, 2.0], [0.0, 2.0]];
+        assert_covers(&fill(&points, false), &points, 4.0);
+    }
+
+    #[test]
+    fn fully_collinear_polygon_is_skipped() {
+        assert!(fill(&[[0.0, 0.0], [1.0, 1.0], [2.0, 2.0], [3.0, 3.0]], false).is_empty());
+        assert!(fill(&[[0.0, 0.0], [1.0, 1.0], [2.0, 2.0]], true).is_empty());
+    }
+
+    #[test]
+    fn degenerate_polygons_are_skipped() {
+        assert!(fill(&[], false).is_empty());
+        assert!(fill(&[[1.0, 1.0], [2.0, 2.0]], false).is_empty());
+        assert!(fill(&[[1.0, 1.0], [1.0, 1.0], [1.0, 1.0], [1.0, 1.0]], false).is_empty());
+    }
+
+    #[test]
+    fn repeated_closing_point_is_ignored() {
+        let mut closed = SQUARE.to_vec();
+        closed.push(SQUARE[0]);
+        let triangles = fill(&closed, false);
+        assert_eq!(triangles.len(), 2);
+        assert_covers(&triangles, &SQUARE, 4.0);
+    }
+
+    #[test]
+    fn anti_aliasing_adds_a_fringe() {
+        let mut out = Vec::new();
+        Tessellator { out: &mut out, anti_aliasing: true }.fill_polygon(&SQUARE, WHITE);
+
+        // Interior triangles plus two per edge for the fringe.
+        assert_eq!(out.len(), (2 + 2 * 4) * 3);
+        assert!(out.iter().all(|v| (0.0..=1.0).contains(&v.aa[0])));
+
+        // The fringe straddles the edge, half a pixel on either side.
+        let triangles: Vec<[Vec2; 3]> = out.chunks(3).map(|t| [t[0].pos, t[1].pos, t[2].pos]).collect();
+        assert_close(total_area(&triangles), 3.0 * 3.0);
+        assert!(triangles.iter().all(|t| area(t) >= 0.0));
+    }
+
+    #[test]
+    fn triangulate_returns_n_minus_two_triangles() {
+        assert_eq!(triangulate(&L_SHAPE).len(), 4);
+        assert_eq!(triangulate(&SQUARE).len(), 2);
+        assert!(triangulate(&SQUARE[..2]).is_empty());
+    }
+
+    #[test]
+    fn stroke_straight_line() {
+        let mut out = Vec::new();
+        let style = StrokeStyle::new(2.0);
+        Tessellator { out: &mut out, anti_aliasing: false }.stroke(&[[0.0, 0.0], [10.0, 0.0]], false, &style, WHITE);
+
+        let triangles: Vec<[Vec2; 3]> = out.chunks(3).map(|t| [t[0].pos, t[1].pos, t[2].pos]).collect();
+        assert_eq!(triangles.len(), 2);
+        assert_close(total_area(&triangles).abs(), 20.0);
+    }
+
+    #[test]
+    fn arc_points_include_both_ends() {
+        let mut points = Vec::new();
+        arc_points([1.0, 1.0], [2.0, 2.0], 0.0, PI, &mut points);
+        assert!(points.len() >= 3);
+        assert_close(points[0][0], 3.0);
+        assert_close(points[0][1], 1.0);
+        assert_close(points[points.len() - 1][0], -1.0);
+        assert_close(points[points.len() - 1][1], 1.0);
+    }
+}
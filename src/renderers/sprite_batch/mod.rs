@@ -0,0 +1,382 @@
+use std::cell::Cell;
+
+use crate::Error;
+use gl;
+use gl::types::*;
+
+use crate::renderers::{
+    Renderer,
+    Viewport,
+    Transformable,
+    Mat4,
+    blend::{BlendMode, BlendState},
+};
+use crate::shader::{ShaderProgram, TextureUnit};
+
+const VCODE : &str = r#"
+#version 450 core
+layout (location = 0) in vec2 in_corner;
+layout (location = 1) in vec4 in_pos_size;
+layout (location = 2) in vec4 in_origin_rotation_depth;
+layout (location = 3) in vec4 in_source_rect;
+layout (location = 4) in vec4 in_tint;
+out vec2 v_uv;
+out vec4 v_tint;
+uniform mat4 u_transform;
+
+void main() {
+    vec2 origin = in_origin_rotation_depth.xy;
+    float rotation = in_origin_rotation_depth.z;
+    float depth = in_origin_rotation_depth.w;
+
+    vec2 local = (in_corner - origin) * in_pos_size.zw;
+    float c = cos(rotation);
+    float s = sin(rotation);
+    vec2 rotated = vec2(c * local.x - s * local.y, s * local.x + c * local.y);
+
+    gl_Position = u_transform * vec4(in_pos_size.xy + rotated, depth, 1.0);
+    v_uv = in_source_rect.xy + vec2(in_corner.x, 1.0 - in_corner.y) * in_source_rect.zw;
+    v_tint = in_tint;
+}
+"#;
+
+const FCODE : &str = r#"
+#version 450 core
+in vec2 v_uv;
+in vec4 v_tint;
+out vec4 fColor;
+layout (location = 1) out vec4 fNormal;
+uniform sampler2D u_tex1;
+uniform bool u_input_premultiplied;
+uniform bool u_output_premultiplied;
+void main() {
+    vec4 color = texture(u_tex1, v_uv);
+    if (u_input_premultiplied) {
+        color *= vec4(v_tint.rgb * v_tint.a, v_tint.a);
+    } else {
+        color *= v_tint;
+        if (u_output_premultiplied) {
+            color.rgb *= color.a;
+        }
+    }
+    fColor = color;
+
+    // A flat surface, for the scene of a lighting renderer with normal mapping.
+    bool premultiplied = u_input_premultiplied || u_output_premultiplied;
+    fNormal = vec4(premultiplied ? vec3(0.5, 0.5, 1.0) * color.a : vec3(0.5, 0.5, 1.0), color.a);
+}
+"#;
+
+/// A single textured quad drawn by a [`SpriteBatchRenderer`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprite {
+    /// Position of the origin point, before the batch transform is applied.
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+
+    /// Rotation around the origin point, in radians, counter-clockwise.
+    pub rotation: f32,
+
+    /// The point the sprite is positioned and rotated around, relative to its size.
+    /// `[0.0, 0.0]` is the bottom-left corner and `[0.5, 0.5]` the center.
+    pub origin: [f32; 2],
+
+    /// The region of the texture to draw, as `[u, v, width, height]` in texture coordinates.
+    /// `v` is measured from the first row of the texture, which is drawn at the top of the sprite.
+    pub source_rect: [f32; 4],
+
+    /// Multiplied with the texture color.
+    pub tint: [f32; 4],
+
+    /// Z coordinate passed through the transform. Only has an effect if depth testing is enabled.
+    pub depth: f32,
+}
+
+impl Sprite {
+    /// A sprite showing the whole texture, untinted, with its origin at the center.
+    pub fn new(position: [f32; 2], size: [f32; 2]) -> Self {
+        Self {
+            position,
+            size,
+            rotation: 0.0,
+            origin: [0.5, 0.5],
+            source_rect: [0.0, 0.0, 1.0, 1.0],
+            tint: [1.0, 1.0, 1.0, 1.0],
+            depth: 0.0,
+        }
+    }
+
+    fn instance_data(&self) -> [f32; FLOATS_PER_INSTANCE] {
+        [
+            self.position[0], self.position[1], self.size[0], self.size[1],
+            self.origin[0], self.origin[1], self.rotation, self.depth,
+            self.source_rect[0], self.source_rect[1], self.source_rect[2], self.source_rect[3],
+            self.tint[0], self.tint[1], self.tint[2], self.tint[3],
+        ]
+    }
+}
+
+impl Default for Sprite {
+    fn default() -> Self {
+        Self::new([0.0, 0.0], [1.0, 1.0])
+    }
+}
+
+const FLOATS_PER_INSTANCE: usize = 16;
+
+/// Draws any number of sprites from a single texture in one instanced draw call.
+///
+/// Sprites are collected on the CPU and uploaded the next time the batch is rendered.
+/// Like [`TextureRenderer`](crate::renderers::texture_renderer::TextureRenderer), the
+/// texture must be bound to the configured texture unit before rendering.
+pub struct SpriteBatchRenderer {
+    viewport: Viewport,
+    program: ShaderProgram,
+    vao: u32,
+    corner_buffer: u32,
+    instance_buffer: u32,
+    blend_mode: BlendMode,
+
+    sprites: Vec<Sprite>,
+
+    /// Number of sprites the instance buffer has room for.
+    instance_capacity: Cell<usize>,
+    dirty: Cell<bool>,
+}
+
+impl SpriteBatchRenderer {
+    pub fn new() -> Result<Self, Error> {
+        let program = ShaderProgram::new(VCODE, FCODE)?;
+
+        #[rustfmt::skip]
+        let corners: &[f32] = &[
+            0.0, 0.0,
+            0.0, 1.0,
+            1.0, 1.0,
+            1.0, 0.0,
+        ];
+
+        let corner_buffer = glh::create_buffer(corners, gl::STATIC_DRAW)?;
+        let mut instance_buffer = 0;
+        let mut vao = 0;
+        unsafe {
+            gl::CreateBuffers(1, &mut instance_buffer);
+            gl::GenVertexArrays(1, &mut vao);
+        }
+
+        glh::enable_interleaved_vertex_array_attributes(
+            vao,
+            corner_buffer,
+            gl::FLOAT,
+            false,
+            0,
+            &[2],
+        )?;
+
+        glh::enable_interleaved_vertex_array_attributes(
+            vao,
+            instance_buffer,
+            gl::FLOAT,
+            false,
+            1,
+            &[4, 4, 4, 4],
+        )?;
+
+        unsafe {
+            for index in 1..=4 {
+                gl::VertexAttribDivisor(index, 1);
+            }
+        }
+
+        let mut self_ = Self {
+            viewport: Viewport::default(),
+            program,
+            vao,
+            corner_buffer,
+            instance_buffer,
+            blend_mode: BlendMode::Alpha,
+            sprites: Vec::new(),
+            instance_capacity: Cell::new(0),
+            dirty: Cell::new(false),
+        };
+
+        self_.set_texture_unit(0);
+        self_.set_blend_mode(BlendMode::Alpha);
+        self_.clear_transform();
+
+        Ok(self_)
+    }
+
+    /// Sets the texture unit to sample from. The texture must be bound
+    /// to that unit separately, before rendering.
+    pub fn set_texture_unit(&mut self, texture_unit: GLint) {
+        self.program.set_builtin("u_tex1", TextureUnit(texture_unit));
+    }
+
+    /// Sets the blend mode used while rendering, like
+    /// [`TextureRenderer::set_blend_mode`](crate::renderers::texture_renderer::TextureRenderer::set_blend_mode).
+    /// The previous OpenGL blend state is restored afterwards.
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+        self.program.set_builtin("u_input_premultiplied", blend_mode == BlendMode::PremultipliedAlpha);
+        self.program.set_builtin("u_output_premultiplied", blend_mode.wants_premultiplied_output());
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    pub fn sprites(&self) -> &[Sprite] {
+        &self.sprites
+    }
+
+    /// Gives mutable access to the sprites. The batch is re-uploaded on the next render.
+    pub fn sprites_mut(&mut self) -> &mut Vec<Sprite> {
+        self.dirty.set(true);
+        &mut self.sprites
+    }
+
+    pub fn set_sprites(&mut self, sprites: &[Sprite]) {
+        self.sprites.clear();
+        self.sprites.extend_from_slice(sprites);
+        self.dirty.set(true);
+    }
+
+    pub fn push(&mut self, sprite: Sprite) {
+        self.sprites.push(sprite);
+        self.dirty.set(true);
+    }
+
+    pub fn clear(&mut self) {
+        self.sprites.clear();
+        self.dirty.set(true);
+    }
+
+    fn upload_instances(&self) {
+        let data: Vec<f32> = self
+            .sprites
+            .iter()
+            .flat_map(|sprite| sprite.instance_data())
+            .collect();
+
+        let byte_len = std::mem::size_of_val(data.as_slice());
+        unsafe {
+            if let Some(capacity) = grown_capacity(self.instance_capacity.get(), self.sprites.len()) {
+                let capacity_bytes = capacity * FLOATS_PER_INSTANCE * std::mem::size_of::<f32>();
+                gl::NamedBufferData(
+                    self.instance_buffer,
+                    capacity_bytes as isize,
+                    std::ptr::null(),
+                    gl::DYNAMIC_DRAW,
+                );
+                self.instance_capacity.set(capacity);
+            }
+
+            gl::NamedBufferSubData(self.instance_buffer, 0, byte_len as isize, data.as_ptr() as *const _);
+        }
+
+        self.dirty.set(false);
+    }
+}
+
+/// The number of sprites to make room for if `needed` don't fit in `capacity`, or `None` if
+/// they do. Grows to powers of two, so a batch growing a sprite at a time is rarely reallocated.
+fn grown_capacity(capacity: usize, needed: usize) -> Option<usize> {
+    (needed > capacity).then(|| needed.next_power_of_two())
+}
+
+impl Renderer for SpriteBatchRenderer {
+    fn set_viewport(&mut self, viewport: Viewport) {
+        self.viewport = viewport;
+    }
+
+    fn render(&self) {
+        if self.sprites.is_empty() {
+            return;
+        }
+
+        if self.dirty.get() {
+            self.upload_instances();
+        }
+
+        self.viewport.gl_viewport();
+        let blend_state = BlendState::capture();
+        self.blend_mode.apply();
+        self.program.use_program();
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::DrawArraysInstanced(gl::TRIANGLE_FAN, 0, 4, self.sprites.len() as i32);
+        }
+        blend_state.restore();
+    }
+}
+
+impl Transformable for SpriteBatchRenderer {
+    fn set_transform(&mut self, transform: Mat4) {
+        self.program.set_builtin("u_transform", transform);
+    }
+}
+
+impl Drop for SpriteBatchRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+            gl::DeleteBuffers(1, &self.corner_buffer);
+            gl::DeleteBuffers(1, &self.instance_buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instance_data_matches_vertex_attributes() {
+        let sprite = Sprite {
+            position: [1.0, 2.0],
+            size: [3.0, 4.0],
+            rotation: 5.0,
+            origin: [0.25, 0.75],
+            source_rect: [0.1, 0.2, 0.3, 0.4],
+            tint: [0.5, 0.6, 0.7, 0.8],
+            depth: 9.0,
+        };
+
+        // in_pos_size, in_origin_rotation_depth, in_source_rect and in_tint, in order.
+        assert_eq!(
+            sprite.instance_data(),
+            [
+                1.0, 2.0, 3.0, 4.0,
+                0.25, 0.75, 5.0, 9.0,
+                0.1, 0.2, 0.3, 0.4,
+                0.5, 0.6, 0.7, 0.8,
+            ]
+        );
+    }
+
+    #[test]
+    fn new_sprite_shows_whole_texture_around_center() {
+        let data = Sprite::new([10.0, 20.0], [30.0, 40.0]).instance_data();
+        assert_eq!(data[0..4], [10.0, 20.0, 30.0, 40.0]);
+        assert_eq!(data[4..8], [0.5, 0.5, 0.0, 0.0]);
+        assert_eq!(data[8..12], [0.0, 0.0, 1.0, 1.0]);
+        assert_eq!(data[12..16], [1.0, 1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn grown_capacity_only_grows_when_needed() {
+        assert_eq!(grown_capacity(0, 0), None);
+        assert_eq!(grown_capacity(8, 5), None);
+        assert_eq!(grown_capacity(8, 8), None);
+    }
+
+    #[test]
+    fn grown_capacity_rounds_up_to_power_of_two() {
+        assert_eq!(grown_capacity(0, 1), Some(1));
+        assert_eq!(grown_capacity(0, 3), Some(4));
+        assert_eq!(grown_capacity(4, 5), Some(8));
+        assert_eq!(grown_capacity(8, 64), Some(64));
+        assert_eq!(grown_capacity(64, 65), Some(128));
+    }
+}
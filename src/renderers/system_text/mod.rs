@@ -1,14 +1,18 @@
 use std::collections::HashMap;
+use std::path::Path;
 
 include!(concat!(env!("OUT_DIR"), "/system_text_font.rs"));
 
-use crate::renderers::{Renderer, Viewport};
+use crate::renderers::{FrameContext, Renderer, Viewport};
+use crate::shader::{HotShaderProgram, ReloadStatus, ShaderProgram};
 
 type Error = Box<dyn std::error::Error>;
 
 pub struct SystemTextRenderer {
     viewport: Viewport,
-    program: u32,
+    program: ShaderProgram,
+    shader_files: Option<HotShaderProgram>,
+    window_size: [i32; 2],
     character_vertices: HashMap<char, Vec<f32>>,
     text: Option<SystemText>,
 }
@@ -90,6 +94,8 @@ impl SystemTextRenderer {
         let mut self_ = Self {
             viewport,
             program,
+            shader_files: None,
+            window_size: [800, 600],
             character_vertices,
             text: None,
         };
@@ -103,11 +109,37 @@ impl SystemTextRenderer {
     /// If you want to make sure the text has a consistent size, no matter the size of the viewport,
     /// you can set the window size.
     pub fn set_window_size(&mut self, size: [i32; 2]) {
-        unsafe {
-            let loc = gl::GetUniformLocation(self.program, "windowSize\0".as_ptr() as *const i8);
-            gl::UseProgram(self.program);
-            gl::Uniform2f(loc, size[0] as f32, size[1] as f32);
-        }
+        self.window_size = size;
+        self.current_program().set_builtin("windowSize", [size[0] as f32, size[1] as f32]);
+    }
+
+    /// Development mode: loads the shaders from `vshader.glsl` and `fshader.glsl` in `directory`
+    /// instead of the copies built into the crate, and reloads them in [`update`](Renderer::update)
+    /// whenever they change. Point it at `src/renderers/system_text/shaders` in a glenda checkout.
+    ///
+    /// Fails if the files can't be loaded or compiled. After that, a failed reload keeps the
+    /// previous shaders and reports the error through [`shader_error`](Self::shader_error).
+    pub fn watch_shader_files<P: AsRef<Path>>(&mut self, directory: P) -> Result<(), Error> {
+        let directory = directory.as_ref();
+        let shader_files = HotShaderProgram::new(directory.join("vshader.glsl"), directory.join("fshader.glsl"))?;
+        self.shader_files = Some(shader_files);
+        self.set_window_size(self.window_size);
+        Ok(())
+    }
+
+    /// Goes back to the built-in shaders.
+    pub fn stop_watching_shader_files(&mut self) {
+        self.shader_files = None;
+        self.set_window_size(self.window_size);
+    }
+
+    /// The error of the last failed reload in development mode, if the current files don't compile.
+    pub fn shader_error(&self) -> Option<&str> {
+        self.shader_files.as_ref().and_then(HotShaderProgram::error)
+    }
+
+    fn current_program(&self) -> &ShaderProgram {
+        self.shader_files.as_ref().map_or(&self.program, HotShaderProgram::program)
     }
 
     pub fn set_text(&mut self, text: &str) {
@@ -146,11 +178,18 @@ impl Renderer for SystemTextRenderer {
         self.viewport = _viewport;
     }
 
+    fn update(&mut self, _ctx: &mut FrameContext) {
+        let reloaded = self.shader_files.as_mut().is_some_and(|files| files.poll() == ReloadStatus::Reloaded);
+        if reloaded {
+            self.set_window_size(self.window_size);
+        }
+    }
+
     fn render(&self) {
         if let Some(ref text) = self.text {
             self.viewport.gl_viewport();
+            self.current_program().use_program();
             unsafe {
-                gl::UseProgram(self.program);
                 gl::BindVertexArray(text.vao);
                 gl::DrawArrays(gl::LINES, 0, text.num_indices as i32);
             }
@@ -158,14 +197,6 @@ impl Renderer for SystemTextRenderer {
     }
 }
 
-impl Drop for SystemTextRenderer {
-    fn drop(&mut self) {
-        unsafe {
-            gl::DeleteProgram(self.program);
-        }
-    }
-}
-
 fn create_character_vertices() -> HashMap<char, Vec<f32>> {
     let mut vertices = HashMap::new();
 
@@ -212,14 +243,9 @@ fn create_vertex_array() -> Result<(u32, u32), Error> {
     }
 }
 
-fn create_program() -> Result<u32, Error> {
+fn create_program() -> Result<ShaderProgram, Error> {
     let vcode = include_str!("shaders/vshader.glsl");
     let fcode = include_str!("shaders/fshader.glsl");
 
-    let program = glh::ProgramBuilder::new()
-        .with_vertex_shader(vcode)?
-        .with_fragment_shader(fcode)?
-        .build()?;
-
-    Ok(program)
+    ShaderProgram::new(vcode, fcode)
 }
@@ -1,6 +1,10 @@
 #version 330 core
 out vec4 fColor;
+layout(location = 1) out vec4 fNormal;
 void main()
 {
     fColor = vec4(1.0f, 1.0f, 1.0f, 1.0f);
+
+    // A flat surface, for the scene of a lighting renderer with normal mapping.
+    fNormal = vec4(0.5f, 0.5f, 1.0f, 1.0f);
 }
@@ -4,11 +4,16 @@ use gl;
 use gl::types::*;
 
 use crate::renderers::{
+    FrameContext,
     Renderer,
     Viewport,
     Transformable,
     Mat4,
+    blend::{BlendMode, BlendState},
+    palette::{apply_palette_uniforms, with_palette_glsl, PaletteSettings},
+    sampler::{Sampler, SamplerSettings},
 };
+use crate::shader::{build_with_hooks, CustomShader, CustomShaderFiles, HookedProgram, ReloadStatus, ShaderProgram, TextureUnit, UniformValue};
 
 const VCODE : &str = r#"
 #version 450 core
@@ -16,38 +21,105 @@ layout (location = 0) in vec2 in_pos;
 layout (location = 1) in vec2 in_uv;
 out vec2 v_uv;
 uniform mat4 u_transform;
+uniform vec4 u_source_rect;
+
+vec4 position(vec2 pos);
 
 void main() {
-    gl_Position = u_transform * vec4(in_pos, 0.0, 1.0);
-    v_uv = in_uv;
+    gl_Position = position(in_pos);
+    v_uv = u_source_rect.xy + in_uv * u_source_rect.zw;
+}
+"#;
+
+const DEFAULT_VERTEX_HOOK : &str = r#"
+vec4 position(vec2 pos) {
+    return u_transform * vec4(pos, 0.0, 1.0);
 }
 "#;
 
 const FCODE : &str = r#"
 #version 450 core
 in vec2 v_uv;
-out vec4 fColor;
+layout (location = 0) out vec4 fColor;
+layout (location = 1) out vec4 fNormal;
 uniform sampler2D u_tex1;
+uniform vec4 u_tint;
+uniform float u_opacity;
+uniform bool u_input_premultiplied;
+uniform bool u_output_premultiplied;
+uniform bool u_sharp_bilinear;
+uniform bool u_normal_mapped;
+uniform sampler2D u_normal_map;
+
+// Snaps to the nearest texel center except within one screen pixel of a texel
+// edge, where linear filtering blends the two texels. Keeps upscaled pixel art
+// crisp at non-integer scales without the shimmer of nearest filtering.
+vec2 sharp_bilinear_uv(vec2 uv) {
+    vec2 tex_size = vec2(textureSize(u_tex1, 0));
+    vec2 texel = uv * tex_size;
+    vec2 seam = floor(texel + 0.5);
+    vec2 texels_per_pixel = max(fwidth(texel), vec2(1e-5));
+    texel = seam + clamp((texel - seam) / texels_per_pixel, -0.5, 0.5);
+    return texel / tex_size;
+}
+
+vec4 sample_texture(vec2 uv) {
+    if (u_indexed) {
+        return palette_color(u_tex1, uv);
+    }
+    return texture(u_tex1, u_sharp_bilinear ? sharp_bilinear_uv(uv) : uv);
+}
+
+vec4 effect(vec2 uv);
+
 void main() {
-    fColor = texture(u_tex1, v_uv);
+    vec4 color = effect(v_uv);
+    if (u_input_premultiplied) {
+        color *= vec4(u_tint.rgb * u_tint.a, u_tint.a) * u_opacity;
+    } else {
+        color *= u_tint;
+        color.a *= u_opacity;
+        if (u_output_premultiplied) {
+            color.rgb *= color.a;
+        }
+    }
+    fColor = color;
+
+    // Only kept when drawing into the scene of a lighting renderer with normal mapping.
+    vec3 normal = u_normal_mapped ? texture(u_normal_map, v_uv).rgb : vec3(0.5, 0.5, 1.0);
+    bool premultiplied = u_input_premultiplied || u_output_premultiplied;
+    fNormal = vec4(premultiplied ? normal * color.a : normal, color.a);
+}
+"#;
+
+const DEFAULT_FRAGMENT_HOOK : &str = r#"
+vec4 effect(vec2 uv) {
+    return sample_texture(uv);
 }
 "#;
 
 pub struct TextureRenderer {
     viewport: Viewport,
-    program: u32,
+    shader: HookedProgram,
     vao: u32,
     buffer: u32,
-    uloc_tex1: GLint,
-    uloc_transform: GLint,
+    blend_mode: BlendMode,
+    texture_unit: GLint,
+    sampler: Option<Sampler>,
+
+    // Kept to set again when the program is rebuilt.
+    transform: Mat4,
+    source_rect: [f32; 4],
+    tint: [f32; 4],
+    opacity: f32,
+    sharp_bilinear: bool,
+    palette: Option<PaletteSettings>,
+    normal_map_texture_unit: Option<GLint>,
 }
 
 impl TextureRenderer {
     pub fn new() -> Result<Self, Error> {
-        let program = glh::ProgramBuilder::new()
-            .with_vertex_shader(VCODE)?
-            .with_fragment_shader(FCODE)?
-            .build()?;
+        let shader = HookedProgram::new(build_program)?;
 
         #[rustfmt::skip]
         let vertices: &[f32] = &[
@@ -72,70 +144,237 @@ impl TextureRenderer {
             component_counts,
         )?;
 
-        let uloc_tex1;
-        let uloc_transform;
-
-        unsafe {
-            uloc_tex1 = gl::GetUniformLocation(program, "u_tex1\0".as_ptr() as *const i8);
-            uloc_transform = gl::GetUniformLocation(program, "u_transform\0".as_ptr() as *const i8);
-        }
-
         let mut self_ = Self {
             viewport: Viewport::default(),
-            program,
+            shader,
             vao,
             buffer,
-            uloc_tex1,
-            uloc_transform,
+            blend_mode: BlendMode::Alpha,
+            texture_unit: 0,
+            sampler: None,
+            transform: Mat4::identity(),
+            source_rect: [0.0, 0.0, 1.0, 1.0],
+            tint: [1.0, 1.0, 1.0, 1.0],
+            opacity: 1.0,
+            sharp_bilinear: false,
+            palette: None,
+            normal_map_texture_unit: None,
         };
 
-        self_.set_texture_unit(0); // Default to texture unit 0
-        self_.clear_transform();
+        self_.apply_uniforms();
 
         Ok(self_)
     }
 
+    /// Sets all uniforms of the current program from the stored state.
+    fn apply_uniforms(&mut self) {
+        self.set_texture_unit(self.texture_unit);
+        self.set_source_rect(self.source_rect);
+        self.set_tint(self.tint);
+        self.set_opacity(self.opacity);
+        self.set_blend_mode(self.blend_mode);
+        self.set_sharp_bilinear(self.sharp_bilinear);
+        self.set_palette(self.palette);
+        self.set_normal_map_texture_unit(self.normal_map_texture_unit);
+        self.set_transform(self.transform);
+    }
+
     /// This only sets the texture unit to use for the shader.
     /// The texture itself must managed separately, and bound
     /// to the specified texture unit before rendering. Learn
     /// more about texture units in OpenGL, they'r dumb and
     /// confusing.
     pub fn set_texture_unit(&mut self, texture_unit: GLint) {
-        unsafe {
-            gl::UseProgram(self.program);
-            gl::Uniform1i(self.uloc_tex1, texture_unit);
+        self.texture_unit = texture_unit;
+        self.shader.program().set_builtin("u_tex1", TextureUnit(texture_unit));
+    }
+
+    /// Draws only part of the texture, given as `[u, v, width, height]` in texture
+    /// coordinates, with `v` measured from the first row of the texture.
+    /// Use [`AtlasRegion::uv_rect`](crate::texture_atlas::AtlasRegion::uv_rect)
+    /// to draw one region of an atlas.
+    pub fn set_source_rect(&mut self, rect: [f32; 4]) {
+        self.source_rect = rect;
+        self.shader.program().set_builtin("u_source_rect", rect);
+    }
+
+    /// Draws the whole texture again.
+    pub fn clear_source_rect(&mut self) {
+        self.set_source_rect([0.0, 0.0, 1.0, 1.0]);
+    }
+
+    /// Sets the blend mode used while rendering. The previous OpenGL blend
+    /// state is restored afterwards. Use `PremultipliedAlpha` if the texture's
+    /// color is already multiplied by its alpha.
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+        self.shader.program().set_builtin("u_input_premultiplied", blend_mode == BlendMode::PremultipliedAlpha);
+        self.shader.program().set_builtin("u_output_premultiplied", blend_mode.wants_premultiplied_output());
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    /// Sets a color the texture is multiplied with. Defaults to opaque white.
+    pub fn set_tint(&mut self, tint: [f32; 4]) {
+        self.tint = tint;
+        self.shader.program().set_builtin("u_tint", tint);
+    }
+
+    /// Sets the overall opacity, from 0.0 (invisible) to 1.0 (the default).
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity.clamp(0.0, 1.0);
+        self.shader.program().set_builtin("u_opacity", self.opacity);
+    }
+
+    /// Gives the renderer its own sampler, bound to its texture unit while rendering,
+    /// overriding the filtering and wrapping set on the texture. Use `None` to go back
+    /// to the texture's own parameters.
+    pub fn set_sampler(&mut self, settings: Option<SamplerSettings>) -> Result<(), Error> {
+        match (settings, &mut self.sampler) {
+            (Some(settings), Some(sampler)) => sampler.set_settings(settings),
+            (Some(settings), None) => self.sampler = Some(Sampler::new(settings)?),
+            (None, _) => self.sampler = None,
         }
+        Ok(())
+    }
+
+    /// Enables "sharp bilinear" sampling for upscaling pixel art: texels stay crisp,
+    /// and only the edges between them are anti-aliased. Requires linear magnification,
+    /// for example with `set_sampler(Some(SamplerSettings::linear()))`.
+    pub fn set_sharp_bilinear(&mut self, enabled: bool) {
+        self.sharp_bilinear = enabled;
+        self.shader.program().set_builtin("u_sharp_bilinear", enabled);
+    }
+
+    /// Draws the texture as 8-bit palette indices, looking up each texel's color in a palette
+    /// texture, or goes back to drawing its colors directly with `None`. The index texture is
+    /// always sampled without filtering, so sharp bilinear sampling has no effect.
+    ///
+    /// Palettes can be swapped by changing the row or binding another palette texture, and
+    /// cycled by changing the palette's colors, without touching the index texture.
+    pub fn set_palette(&mut self, palette: Option<PaletteSettings>) {
+        self.palette = palette;
+        apply_palette_uniforms(self.shader.program(), palette);
+    }
+
+    pub fn palette(&self) -> Option<PaletteSettings> {
+        self.palette
+    }
+
+    /// Sets the texture unit of a normal map for the texture, or `None` for a flat surface.
+    /// The normal map must have the same layout as the texture, so the same texture
+    /// coordinates apply, and be bound to the texture unit separately, before rendering.
+    ///
+    /// Normals are only used inside a [`LightingRenderer`](crate::renderers::lighting_renderer::LightingRenderer)
+    /// with normal mapping enabled, which shades each pixel by how much it faces each light.
+    /// They are in tangent space, with green pointing up, and don't follow the transform's rotation.
+    pub fn set_normal_map_texture_unit(&mut self, texture_unit: Option<GLint>) {
+        self.normal_map_texture_unit = texture_unit;
+        self.shader.program().set_builtin("u_normal_mapped", texture_unit.is_some());
+        self.shader.program().set_builtin("u_normal_map", TextureUnit(texture_unit.unwrap_or(0)));
+    }
+
+    pub fn normal_map_texture_unit(&self) -> Option<GLint> {
+        self.normal_map_texture_unit
+    }
+
+    /// Replaces the shader hooks, or goes back to the built-in ones with `None`.
+    /// If the shader fails to compile, the error is returned and the previous shader is kept.
+    ///
+    /// The vertex hook is `vec4 position(vec2 pos)`. It receives the corner of the quad,
+    /// from -1.0 to 1.0, and returns the clip-space position. The built-in one returns
+    /// `u_transform * vec4(pos, 0.0, 1.0)`. `in_uv` holds the corner's texture coordinates.
+    ///
+    /// The fragment hook is `vec4 effect(vec2 uv)`. It receives the texture coordinates,
+    /// already mapped into the source rect, and returns the color before tint and opacity
+    /// are applied. The built-in one returns `sample_texture(uv)`, which samples `u_tex1`
+    /// with sharp bilinear filtering if enabled, or through the palette in palette mode.
+    /// `v_uv`, `u_source_rect` and `u_tex1` are available too, as is
+    /// `palette_color(sampler2D index_texture, vec2 uv)`.
+    ///
+    /// Uniforms declared by the hooks are set with [`set_shader_uniform`](Self::set_shader_uniform),
+    /// and have to be set again after the shader changes.
+    pub fn set_custom_shader(&mut self, shader: Option<CustomShader>) -> Result<(), Error> {
+        self.shader.set_custom_shader(shader)?;
+        self.apply_uniforms();
+        Ok(())
+    }
+
+    pub fn custom_shader(&self) -> Option<&CustomShader> {
+        self.shader.custom_shader()
+    }
+
+    /// Development mode: loads the hooks from `files` and reloads them in
+    /// [`update`](Renderer::update) whenever they change. See [`CustomShaderFiles`].
+    pub fn watch_shader_files(&mut self, files: CustomShaderFiles) -> Result<(), Error> {
+        self.shader.watch_files(files)?;
+        self.apply_uniforms();
+        Ok(())
+    }
+
+    /// Stops reloading the hooks. The last ones that compiled stay in use.
+    pub fn stop_watching_shader_files(&mut self) {
+        self.shader.stop_watching_files();
+    }
+
+    /// The error of the last failed reload in development mode, if the current files don't compile.
+    pub fn shader_error(&self) -> Option<&str> {
+        self.shader.files_error()
+    }
+
+    /// Sets a uniform declared by a custom shader. Fails if the uniform doesn't exist,
+    /// was removed by the compiler as unused, or has a different type.
+    pub fn set_shader_uniform<V: UniformValue>(&mut self, name: &str, value: V) -> Result<(), Error> {
+        self.shader.program().set_uniform(name, value)
     }
 }
 
+fn build_program(custom: Option<&CustomShader>) -> Result<ShaderProgram, Error> {
+    build_with_hooks(VCODE, DEFAULT_VERTEX_HOOK, &with_palette_glsl(FCODE), DEFAULT_FRAGMENT_HOOK, custom)
+}
+
 impl Renderer for TextureRenderer {
     fn set_viewport(&mut self, viewport: Viewport) {
         self.viewport = viewport;
     }
 
+    fn update(&mut self, _ctx: &mut FrameContext) {
+        if self.shader.poll_files() == ReloadStatus::Reloaded {
+            self.apply_uniforms();
+        }
+    }
+
     fn render(&self) {
         self.viewport.gl_viewport();
+        let blend_state = BlendState::capture();
+        self.blend_mode.apply();
+        if let Some(ref sampler) = self.sampler {
+            sampler.bind(self.texture_unit as GLuint);
+        }
+        self.shader.program().use_program();
         unsafe {
-            gl::UseProgram(self.program);
             gl::BindVertexArray(self.vao);
             gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
         }
+        if self.sampler.is_some() {
+            Sampler::unbind(self.texture_unit as GLuint);
+        }
+        blend_state.restore();
     }
 }
 
 impl Transformable for TextureRenderer {
     fn set_transform(&mut self, transform: Mat4) {
-        unsafe {
-            gl::UseProgram(self.program);
-            gl::UniformMatrix4fv(self.uloc_transform, 1, gl::FALSE, transform.as_ptr());
-        }
+        self.transform = transform;
+        self.shader.program().set_builtin("u_transform", transform);
     }
 }
 
 impl Drop for TextureRenderer {
     fn drop(&mut self) {
         unsafe {
-            gl::DeleteProgram(self.program);
             gl::DeleteVertexArrays(1, &self.vao);
             gl::DeleteBuffers(1, &self.buffer);
         }
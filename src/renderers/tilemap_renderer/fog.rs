@@ -0,0 +1,373 @@
+use gl::types::*;
+
+use crate::shader::{ShaderProgram, TextureUnit};
+use crate::Error;
+
+/// How much of a tile the player can see.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TileVisibility {
+    /// Never seen.
+    #[default]
+    Unexplored,
+
+    /// Seen before, but not currently in view.
+    Explored,
+
+    /// Currently in view.
+    Visible,
+}
+
+impl TileVisibility {
+    /// The value stored in the fog texture, which the shader interpolates between tiles.
+    fn texel(self) -> u8 {
+        match self {
+            TileVisibility::Unexplored => 0,
+            TileVisibility::Explored => 128,
+            TileVisibility::Visible => 255,
+        }
+    }
+}
+
+/// How a [`TilemapRenderer`](super::TilemapRenderer) draws a fog of war over its tiles.
+///
+/// The fog texture, such as a [`FogOfWar`], has one texel per tile and must be bound to its
+/// texture unit before rendering.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FogSettings {
+    /// Texture unit the fog texture is bound to.
+    pub texture_unit: GLint,
+
+    /// Color mixed over unexplored tiles, by its alpha. Defaults to opaque black,
+    /// which hides them.
+    pub unexplored_color: [f32; 4],
+
+    /// Color mixed over explored tiles that aren't in view. Defaults to half-transparent
+    /// black, which darkens them.
+    pub explored_color: [f32; 4],
+
+    /// Whether the fog fades across tile boundaries, rather than following the tile edges.
+    pub smooth: bool,
+}
+
+impl FogSettings {
+    pub fn new(texture_unit: GLint) -> Self {
+        Self {
+            texture_unit,
+            unexplored_color: [0.0, 0.0, 0.0, 1.0],
+            explored_color: [0.0, 0.0, 0.0, 0.5],
+            smooth: true,
+        }
+    }
+
+    pub fn with_unexplored_color(mut self, color: [f32; 4]) -> Self {
+        self.unexplored_color = color;
+        self
+    }
+
+    pub fn with_explored_color(mut self, color: [f32; 4]) -> Self {
+        self.explored_color = color;
+        self
+    }
+
+    pub fn with_smooth(mut self, smooth: bool) -> Self {
+        self.smooth = smooth;
+        self
+    }
+}
+
+/// The visibility of each tile of a map, kept in a texture with one texel per tile, laid out
+/// like the tiles of a [`TilemapRenderer`](super::TilemapRenderer): row by row, starting from
+/// the top.
+///
+/// A copy of the visibility is kept, so changes only upload the tiles that changed.
+pub struct FogOfWar {
+    texture: GLuint,
+    map_size: [usize; 2],
+    texels: Vec<u8>,
+}
+
+impl FogOfWar {
+    /// Creates the fog for a map of `map_size` tiles, all unexplored.
+    pub fn new(map_size: [usize; 2]) -> Result<Self, Error> {
+        if map_size[0] == 0 || map_size[1] == 0 {
+            return Err(format!("FogOfWar::new: Invalid map size {:?}", map_size).into());
+        }
+
+        let mut texture = 0;
+        unsafe {
+            gl::CreateTextures(gl::TEXTURE_2D, 1, &mut texture);
+            gl::TextureStorage2D(texture, 1, gl::R8, map_size[0] as GLsizei, map_size[1] as GLsizei);
+            gl::TextureParameteri(texture, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TextureParameteri(texture, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::TextureParameteri(texture, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TextureParameteri(texture, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+        }
+
+        let fog = Self {
+            texture,
+            map_size,
+            texels: vec![TileVisibility::Unexplored.texel(); map_size[0] * map_size[1]],
+        };
+
+        fog.upload([0, 0], map_size);
+        Ok(fog)
+    }
+
+    pub fn texture(&self) -> GLuint {
+        self.texture
+    }
+
+    pub fn map_size(&self) -> [usize; 2] {
+        self.map_size
+    }
+
+    /// Binds the fog texture to a texture unit (0 for `GL_TEXTURE0`, and so on).
+    pub fn bind(&self, texture_unit: GLuint) {
+        unsafe {
+            gl::BindTextureUnit(texture_unit, self.texture);
+        }
+    }
+
+    /// The visibility of a tile, given as `[column, row]`, or `None` outside the map.
+    pub fn visibility(&self, tile: [usize; 2]) -> Option<TileVisibility> {
+        let texel = *self.texels.get(self.index(tile)?)?;
+        Some(match texel {
+            0 => TileVisibility::Unexplored,
+            255 => TileVisibility::Visible,
+            _ => TileVisibility::Explored,
+        })
+    }
+
+    pub fn set_visibility(&mut self, tile: [usize; 2], visibility: TileVisibility) -> Result<(), Error> {
+        let Some(index) = self.index(tile) else {
+            return Err(format!("FogOfWar::set_visibility: Tile {:?} is outside the map", tile).into());
+        };
+
+        if self.texels[index] != visibility.texel() {
+            self.texels[index] = visibility.texel();
+            self.upload(tile, [1, 1]);
+        }
+        Ok(())
+    }
+
+    /// Replaces the visibility of every tile.
+    pub fn set_all(&mut self, visibility: &[TileVisibility]) -> Result<(), Error> {
+        if visibility.len() != self.texels.len() {
+            return Err(format!(
+                "FogOfWar::set_all: {} tiles don't match the map size {:?}",
+                visibility.len(), self.map_size
+            ).into());
+        }
+
+        for (texel, visibility) in self.texels.iter_mut().zip(visibility) {
+            *texel = visibility.texel();
+        }
+        self.upload([0, 0], self.map_size);
+        Ok(())
+    }
+
+    /// Makes the tiles whose centers are within `radius` tiles of `center` visible, such as
+    /// the sight range of a unit. `center` is in tiles from the top-left corner of the map,
+    /// so the center of the top-left tile is `[0.5, 0.5]`.
+    pub fn reveal(&mut self, center: [f32; 2], radius: f32) {
+        let Some((first, last)) = Self::tile_range(self.map_size, center, radius) else {
+            return;
+        };
+
+        let visible = TileVisibility::Visible.texel();
+        let mut changed = false;
+        for y in first[1]..=last[1] {
+            for x in first[0]..=last[0] {
+                let dx = x as f32 + 0.5 - center[0];
+                let dy = y as f32 + 0.5 - center[1];
+                let texel = &mut self.texels[y * self.map_size[0] + x];
+                if dx * dx + dy * dy <= radius * radius && *texel != visible {
+                    *texel = visible;
+                    changed = true;
+                }
+            }
+        }
+
+        if changed {
+            self.upload_range(first, last);
+        }
+    }
+
+    /// Turns every visible tile into an explored one, to reveal what is in view again, such
+    /// as after units moved.
+    pub fn hide_visible(&mut self) {
+        if let Some((first, last)) = Self::hide_texels(&mut self.texels, self.map_size[0]) {
+            self.upload_range(first, last);
+        }
+    }
+
+    fn index(&self, [x, y]: [usize; 2]) -> Option<usize> {
+        (x < self.map_size[0] && y < self.map_size[1]).then(|| y * self.map_size[0] + x)
+    }
+
+    /// The first and last tile of the square around a circle, clipped to a map of `map_size`
+    /// tiles, or `None` if the square is outside the map.
+    fn tile_range(map_size: [usize; 2], center: [f32; 2], radius: f32) -> Option<([usize; 2], [usize; 2])> {
+        if radius.is_nan() || radius < 0.0 || center.iter().any(|c| c.is_nan()) {
+            return None;
+        }
+
+        let mut first = [0; 2];
+        let mut last = [0; 2];
+        for axis in 0..2 {
+            let low = (center[axis] - radius).floor().max(0.0);
+            let high = (center[axis] + radius).floor().min(map_size[axis] as f32 - 1.0);
+            if high < low {
+                return None;
+            }
+            first[axis] = low as usize;
+            last[axis] = high as usize;
+        }
+
+        Some((first, last))
+    }
+
+    /// Turns the visible texels of a map `width` tiles wide into explored ones, and returns the
+    /// first and last tile of the rectangle around them, or `None` if none were visible.
+    fn hide_texels(texels: &mut [u8], width: usize) -> Option<([usize; 2], [usize; 2])> {
+        let (visible, explored) = (TileVisibility::Visible.texel(), TileVisibility::Explored.texel());
+        let mut range: Option<([usize; 2], [usize; 2])> = None;
+        for (index, texel) in texels.iter_mut().enumerate().filter(|(_, texel)| **texel == visible) {
+            *texel = explored;
+            let tile = [index % width, index / width];
+            range = Some(match range {
+                Some((first, last)) => (
+                    [first[0].min(tile[0]), first[1].min(tile[1])],
+                    [last[0].max(tile[0]), last[1].max(tile[1])],
+                ),
+                None => (tile, tile),
+            });
+        }
+        range
+    }
+
+    /// Uploads the tiles from `first` to `last`, inclusive.
+    fn upload_range(&self, first: [usize; 2], last: [usize; 2]) {
+        self.upload(first, [last[0] - first[0] + 1, last[1] - first[1] + 1]);
+    }
+
+    /// Uploads a rectangle of tiles from the copy.
+    fn upload(&self, pos: [usize; 2], size: [usize; 2]) {
+        let offset = pos[1] * self.map_size[0] + pos[0];
+        unsafe {
+            // Rows of single bytes aren't 4-byte aligned, and the rectangle is read out of
+            // the rows of the whole map.
+            let (mut alignment, mut row_length) = (0, 0);
+            gl::GetIntegerv(gl::UNPACK_ALIGNMENT, &mut alignment);
+            gl::GetIntegerv(gl::UNPACK_ROW_LENGTH, &mut row_length);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::PixelStorei(gl::UNPACK_ROW_LENGTH, self.map_size[0] as GLint);
+            gl::TextureSubImage2D(
+                self.texture,
+                0,
+                pos[0] as GLint,
+                pos[1] as GLint,
+                size[0] as GLsizei,
+                size[1] as GLsizei,
+                gl::RED,
+                gl::UNSIGNED_BYTE,
+                self.texels[offset..].as_ptr() as *const _,
+            );
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, alignment);
+            gl::PixelStorei(gl::UNPACK_ROW_LENGTH, row_length);
+        }
+    }
+}
+
+impl Drop for FogOfWar {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.texture);
+        }
+    }
+}
+
+/// Sets the fog uniforms of the tilemap program.
+pub(super) fn apply_fog_uniforms(program: &ShaderProgram, settings: Option<FogSettings>) {
+    let fog = settings.unwrap_or(FogSettings::new(0));
+    program.set_builtin("u_fogged", settings.is_some());
+    program.set_builtin("u_fog", TextureUnit(fog.texture_unit));
+    program.set_builtin("u_fog_smooth", fog.smooth);
+    program.set_builtin("u_fog_unexplored", fog.unexplored_color);
+    program.set_builtin("u_fog_explored", fog.explored_color);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP_SIZE: [usize; 2] = [10, 8];
+
+    fn tile_range(center: [f32; 2], radius: f32) -> Option<([usize; 2], [usize; 2])> {
+        FogOfWar::tile_range(MAP_SIZE, center, radius)
+    }
+
+    #[test]
+    fn tile_range_covers_the_square_around_the_circle() {
+        assert_eq!(tile_range([4.5, 3.5], 2.0), Some(([2, 1], [6, 5])));
+        assert_eq!(tile_range([4.5, 3.5], 0.0), Some(([4, 3], [4, 3])));
+    }
+
+    #[test]
+    fn tile_range_is_clipped_to_the_map() {
+        assert_eq!(tile_range([0.5, 7.5], 3.0), Some(([0, 4], [3, 7])));
+        assert_eq!(tile_range([9.5, 0.5], 100.0), Some(([0, 0], [9, 7])));
+    }
+
+    #[test]
+    fn tile_range_with_negative_center() {
+        assert_eq!(tile_range([-1.5, 2.5], 2.0), Some(([0, 0], [0, 4])));
+        assert_eq!(tile_range([-3.0, 2.5], 2.0), None);
+        assert_eq!(tile_range([2.5, -0.5], 0.25), None);
+    }
+
+    #[test]
+    fn tile_range_outside_the_map() {
+        assert_eq!(tile_range([12.5, 3.5], 2.0), None);
+        assert_eq!(tile_range([4.5, 8.0], 0.25), Some(([4, 7], [4, 7])));
+        assert_eq!(tile_range([4.5, 9.0], 0.25), None);
+        assert_eq!(tile_range([f32::INFINITY, 3.5], 2.0), None);
+    }
+
+    #[test]
+    fn tile_range_without_a_valid_circle() {
+        assert_eq!(tile_range([f32::NAN, 3.5], 2.0), None);
+        assert_eq!(tile_range([4.5, f32::NAN], 2.0), None);
+        assert_eq!(tile_range([4.5, 3.5], f32::NAN), None);
+        assert_eq!(tile_range([4.5, 3.5], -1.0), None);
+    }
+
+    #[test]
+    fn hide_texels_returns_the_rectangle_around_visible_tiles() {
+        let (unexplored, explored, visible) = (
+            TileVisibility::Unexplored.texel(),
+            TileVisibility::Explored.texel(),
+            TileVisibility::Visible.texel(),
+        );
+
+        #[rustfmt::skip]
+        let mut texels = vec![
+            unexplored, unexplored, unexplored, unexplored,
+            unexplored, explored,   visible,    unexplored,
+            visible,    unexplored, explored,   unexplored,
+        ];
+
+        assert_eq!(FogOfWar::hide_texels(&mut texels, 4), Some(([0, 1], [2, 2])));
+        assert!(!texels.contains(&visible));
+        assert_eq!(texels[6], explored);
+        assert_eq!(texels[8], explored);
+        assert_eq!(texels[0], unexplored);
+    }
+
+    #[test]
+    fn hide_texels_without_visible_tiles() {
+        let mut texels = vec![TileVisibility::Explored.texel(); 12];
+        assert_eq!(FogOfWar::hide_texels(&mut texels, 4), None);
+        assert_eq!(texels, [TileVisibility::Explored.texel(); 12]);
+    }
+}
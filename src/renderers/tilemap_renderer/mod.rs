@@ -1,17 +1,24 @@
 use gl::{self, types::*};
 
 use crate::renderers::{
+    FrameContext,
     Renderer,
     Viewport,
     Transformable,
     Mat4,
+    palette::{apply_palette_uniforms, with_palette_glsl, PaletteSettings},
 };
+use crate::shader::{build_with_hooks, CustomShader, CustomShaderFiles, HookedProgram, ReloadStatus, ShaderProgram, TextureUnit, UniformValue};
+
+mod fog;
+pub use fog::{FogOfWar, FogSettings, TileVisibility};
+use fog::apply_fog_uniforms;
 
 type Error = Box<dyn std::error::Error>;
 
 pub struct TilemapRenderer {
     viewport: Viewport,
-    program: GLuint,
+    shader: HookedProgram,
 
     vao: GLuint,
     buffer: GLuint,
@@ -19,10 +26,15 @@ pub struct TilemapRenderer {
     /// Size of the tilemap in tiles
     map_size: [usize; 2],
 
-    uloc_transform: GLint,
-    uloc_tileset_texture_unit: GLint,
-    uloc_map_tile_size: GLint,
-    uloc_map_offset: GLint,
+
+    // Kept to set again when the program is rebuilt.
+    transform: Mat4,
+    tileset_texture_unit: GLint,
+    map_tile_size: [f32; 2],
+    map_offset: [f32; 2],
+    palette: Option<PaletteSettings>,
+    normal_map_texture_unit: Option<GLint>,
+    fog: Option<FogSettings>,
 }
 
 pub struct TilesetLayout {
@@ -41,63 +53,155 @@ impl TilemapRenderer {
         let VaoAndBuffer { vao, buffer } =
             create_tilemap_vao(map_size, tile_indices, tileset_layout)?;
 
-        let program = create_tilemap_shader_program()?;
-
-        let uloc_transform;
-        let uloc_tileset_texture_unit;
-        let uloc_map_tile_size;
-        let uloc_map_offset;
-        unsafe {
-            uloc_transform = gl::GetUniformLocation(program, "u_transform\0".as_ptr() as _);
-            uloc_tileset_texture_unit = gl::GetUniformLocation(program, "u_tileset_texture\0".as_ptr() as _);
-            uloc_map_tile_size = gl::GetUniformLocation(program, "u_map_tile_size\0".as_ptr() as _);
-            uloc_map_offset = gl::GetUniformLocation(program, "u_map_offset\0".as_ptr() as _);
-        }
+        let shader = HookedProgram::new(create_tilemap_shader_program)?;
 
         let mut self_ = Self {
             viewport: Viewport::default(),
-            program,
+            shader,
             vao,
             buffer,
             map_size,
 
-            uloc_transform,
-            uloc_tileset_texture_unit,
-            uloc_map_tile_size,
-            uloc_map_offset,
+
+            transform: Mat4::identity(),
+            tileset_texture_unit: 0,
+            map_tile_size: [1.0, 1.0],
+            map_offset: [0.0, 0.0],
+            palette: None,
+            normal_map_texture_unit: None,
+            fog: None,
         };
 
-        self_.set_tileset_texture_unit(0);
-        self_.set_map_tile_size([1.0, 1.0]);
-        self_.set_map_offset([0.0, 0.0]);
-        self_.clear_transform();
+        self_.apply_uniforms();
 
         Ok(self_)
     }
 
+    /// Sets all uniforms of the current program from the stored state.
+    fn apply_uniforms(&mut self) {
+        self.set_tileset_texture_unit(self.tileset_texture_unit);
+        self.set_map_tile_size(self.map_tile_size);
+        self.set_map_offset(self.map_offset);
+        self.set_palette(self.palette);
+        self.set_normal_map_texture_unit(self.normal_map_texture_unit);
+        self.set_fog(self.fog);
+        self.set_transform(self.transform);
+    }
+
+    /// Size of the map in tiles.
+    pub fn map_size(&self) -> [usize; 2] {
+        self.map_size
+    }
+
     /// Sets the texture unit for the tileset texture.
     /// Note that binding of the texture must be done separately
     pub fn set_tileset_texture_unit(&mut self, texture_unit: GLint) {
-        unsafe {
-            gl::UseProgram(self.program);
-            gl::Uniform1i(self.uloc_tileset_texture_unit, texture_unit);
-        }
+        self.tileset_texture_unit = texture_unit;
+        self.shader.program().set_builtin("u_tileset_texture", TextureUnit(texture_unit));
     }
 
     /// Sets the size of each tile in the map, in normalized device coordinates.
     /// This is independent of the size of the tiles in the tileset texture.
     pub fn set_map_tile_size(&mut self, tile_size: [f32; 2]) {
-        unsafe {
-            gl::UseProgram(self.program);
-            gl::Uniform2f(self.uloc_map_tile_size, tile_size[0], tile_size[1]);
-        }
+        self.map_tile_size = tile_size;
+        self.shader.program().set_builtin("u_map_tile_size", tile_size);
     }
 
     pub fn set_map_offset(&mut self, offset: [f32; 2]) {
-        unsafe {
-            gl::UseProgram(self.program);
-            gl::Uniform2f(self.uloc_map_offset, offset[0], offset[1]);
-        }
+        self.map_offset = offset;
+        self.shader.program().set_builtin("u_map_offset", offset);
+    }
+
+    /// Draws the tileset as 8-bit palette indices, looking up each texel's color in a palette
+    /// texture, or goes back to drawing its colors directly with `None`. The tileset is
+    /// always sampled without filtering in palette mode.
+    ///
+    /// Palettes can be swapped by changing the row or binding another palette texture, and
+    /// cycled by changing the palette's colors, without touching the tileset.
+    pub fn set_palette(&mut self, palette: Option<PaletteSettings>) {
+        self.palette = palette;
+        apply_palette_uniforms(self.shader.program(), palette);
+    }
+
+    pub fn palette(&self) -> Option<PaletteSettings> {
+        self.palette
+    }
+
+    /// Sets the texture unit of a normal map for the tileset, or `None` for flat tiles.
+    /// The normal map must have the same layout as the tileset, and be bound to the texture
+    /// unit separately, before rendering.
+    ///
+    /// Normals are only used inside a [`LightingRenderer`](crate::renderers::lighting_renderer::LightingRenderer)
+    /// with normal mapping enabled. They are in tangent space, with green pointing up.
+    pub fn set_normal_map_texture_unit(&mut self, texture_unit: Option<GLint>) {
+        self.normal_map_texture_unit = texture_unit;
+        self.shader.program().set_builtin("u_normal_mapped", texture_unit.is_some());
+        self.shader.program().set_builtin("u_normal_map", TextureUnit(texture_unit.unwrap_or(0)));
+    }
+
+    pub fn normal_map_texture_unit(&self) -> Option<GLint> {
+        self.normal_map_texture_unit
+    }
+
+    /// Draws a fog of war over the tiles, from a texture with the visibility of each tile such
+    /// as a [`FogOfWar`] of the same [`map_size`](Self::map_size), or removes it with `None`.
+    ///
+    /// Tiles are shaded as they become visible in the fog texture, without touching the map.
+    pub fn set_fog(&mut self, fog: Option<FogSettings>) {
+        self.fog = fog;
+        apply_fog_uniforms(self.shader.program(), fog);
+    }
+
+    pub fn fog(&self) -> Option<FogSettings> {
+        self.fog
+    }
+
+    /// Replaces the shader hooks, or goes back to the built-in ones with `None`.
+    /// If the shader fails to compile, the error is returned and the previous shader is kept.
+    ///
+    /// The vertex hook is `vec4 position(vec2 pos)`. It receives the position of a tile
+    /// corner, already scaled by the map tile size and moved by the map offset, and returns
+    /// the clip-space position. The built-in one returns `u_transform * vec4(pos, 0.0, 1.0)`.
+    ///
+    /// The fragment hook is `vec4 effect(vec2 uv)`. It receives the texture coordinates in
+    /// the tileset and returns the color. The built-in one returns `sample_tileset(uv)`,
+    /// which samples `u_tileset_texture`, through the palette in palette mode. The fog of
+    /// war is drawn over the color it returns.
+    ///
+    /// Uniforms declared by the hooks are set with [`set_shader_uniform`](Self::set_shader_uniform),
+    /// and have to be set again after the shader changes.
+    pub fn set_custom_shader(&mut self, shader: Option<CustomShader>) -> Result<(), Error> {
+        self.shader.set_custom_shader(shader)?;
+        self.apply_uniforms();
+        Ok(())
+    }
+
+    pub fn custom_shader(&self) -> Option<&CustomShader> {
+        self.shader.custom_shader()
+    }
+
+    /// Development mode: loads the hooks from `files` and reloads them in
+    /// [`update`](Renderer::update) whenever they change. See [`CustomShaderFiles`].
+    pub fn watch_shader_files(&mut self, files: CustomShaderFiles) -> Result<(), Error> {
+        self.shader.watch_files(files)?;
+        self.apply_uniforms();
+        Ok(())
+    }
+
+    /// Stops reloading the hooks. The last ones that compiled stay in use.
+    pub fn stop_watching_shader_files(&mut self) {
+        self.shader.stop_watching_files();
+    }
+
+    /// The error of the last failed reload in development mode, if the current files don't compile.
+    pub fn shader_error(&self) -> Option<&str> {
+        self.shader.files_error()
+    }
+
+    /// Sets a uniform declared by a custom shader. Fails if the uniform doesn't exist,
+    /// was removed by the compiler as unused, or has a different type.
+    pub fn set_shader_uniform<V: UniformValue>(&mut self, name: &str, value: V) -> Result<(), Error> {
+        self.shader.program().set_uniform(name, value)
     }
 }
 
@@ -106,17 +210,22 @@ impl Drop for TilemapRenderer {
         unsafe {
             gl::DeleteVertexArrays(1, &self.vao);
             gl::DeleteBuffers(1, &self.buffer);
-            gl::DeleteProgram(self.program);
         }
     }
 }
 
 impl Renderer for TilemapRenderer {
+    fn update(&mut self, _ctx: &mut FrameContext) {
+        if self.shader.poll_files() == ReloadStatus::Reloaded {
+            self.apply_uniforms();
+        }
+    }
+
     fn render(&self) {
         self.viewport.gl_viewport();
         let vcount = (self.map_size[0] * self.map_size[1] * 6) as i32;
+        self.shader.program().use_program();
         unsafe {
-            gl::UseProgram(self.program);
             gl::BindVertexArray(self.vao);
             gl::DrawArrays(gl::TRIANGLES, 0, vcount);
         }
@@ -129,47 +238,104 @@ impl Renderer for TilemapRenderer {
 
 impl Transformable for TilemapRenderer {
     fn set_transform(&mut self, transform: Mat4) {
-        unsafe {
-            gl::UseProgram(self.program);
-            gl::UniformMatrix4fv(self.uloc_transform, 1, gl::FALSE, transform.as_ptr());
-        }
+        self.transform = transform;
+        self.shader.program().set_builtin("u_transform", transform);
     }
 }
 
-fn create_tilemap_shader_program() -> Result<GLuint, Error> {
+fn create_tilemap_shader_program(custom: Option<&CustomShader>) -> Result<ShaderProgram, Error> {
     const TILEMAP_VCODE: &str = r#"
         #version 450 core
         layout(location = 0) in vec2 pos;
         layout(location = 1) in vec2 uv;
         out vec2 v_uv;
+        out vec2 v_map_pos;
 
         uniform mat4 u_transform;
         uniform vec2 u_map_tile_size;
         uniform vec2 u_map_offset;
 
+        vec4 position(vec2 pos);
+
         void main() {
             v_uv = uv;
+            v_map_pos = vec2(pos.x, -pos.y);
             vec2 p = pos * u_map_tile_size + u_map_offset;
-            gl_Position = u_transform * vec4(p, 0.0, 1.0);
+            gl_Position = position(p);
+        }
+        "#;
+
+    const DEFAULT_VERTEX_HOOK: &str = r#"
+        vec4 position(vec2 pos) {
+            return u_transform * vec4(pos, 0.0, 1.0);
         }
         "#;
 
     const TILEMAP_FCODE: &str = r#"
         #version 450 core
         in vec2 v_uv;
-        out vec4 f_color;
+        in vec2 v_map_pos;
+        layout(location = 0) out vec4 f_color;
+        layout(location = 1) out vec4 f_normal;
         uniform sampler2D u_tileset_texture;
+        uniform bool u_normal_mapped;
+        uniform sampler2D u_normal_map;
+        uniform bool u_fogged;
+        uniform sampler2D u_fog;
+        uniform bool u_fog_smooth;
+        uniform vec4 u_fog_unexplored;
+        uniform vec4 u_fog_explored;
+
+        vec4 sample_tileset(vec2 uv) {
+            if (u_indexed) {
+                return palette_color(u_tileset_texture, uv);
+            }
+            return texture(u_tileset_texture, uv);
+        }
+
+        // Mixes the fog color for the visibility at the pixel over the color. The fog texture
+        // has 0.0 for unexplored tiles, 0.5 for explored ones and 1.0 for visible ones.
+        vec4 apply_fog(vec4 color) {
+            if (!u_fogged) {
+                return color;
+            }
+
+            ivec2 size = textureSize(u_fog, 0);
+            float visibility = u_fog_smooth
+                ? smoothstep(0.0, 1.0, texture(u_fog, v_map_pos / vec2(size)).r)
+                : texelFetch(u_fog, clamp(ivec2(floor(v_map_pos)), ivec2(0), size - 1), 0).r;
+
+            visibility = clamp(visibility * 2.0, 0.0, 2.0);
+            vec4 fog = visibility < 1.0
+                ? mix(u_fog_unexplored, u_fog_explored, visibility)
+                : mix(u_fog_explored, vec4(u_fog_explored.rgb, 0.0), visibility - 1.0);
+            return vec4(mix(color.rgb, fog.rgb, fog.a), color.a);
+        }
+
+        vec4 effect(vec2 uv);
+
         void main() {
-            f_color = texture(u_tileset_texture, v_uv);
+            f_color = apply_fog(effect(v_uv));
+
+            // Only kept when drawing into the scene of a lighting renderer with normal mapping.
+            vec3 normal = u_normal_mapped ? texture(u_normal_map, v_uv).rgb : vec3(0.5, 0.5, 1.0);
+            f_normal = vec4(normal, f_color.a);
         }
         "#;
 
-    let program = glh::ProgramBuilder::new()
-        .with_vertex_shader(TILEMAP_VCODE)?
-        .with_fragment_shader(TILEMAP_FCODE)?
-        .build()?;
+    const DEFAULT_FRAGMENT_HOOK: &str = r#"
+        vec4 effect(vec2 uv) {
+            return sample_tileset(uv);
+        }
+        "#;
 
-    Ok(program)
+    build_with_hooks(
+        TILEMAP_VCODE,
+        DEFAULT_VERTEX_HOOK,
+        &with_palette_glsl(TILEMAP_FCODE),
+        DEFAULT_FRAGMENT_HOOK,
+        custom,
+    )
 }
 
 struct VaoAndBuffer {
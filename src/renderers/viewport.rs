@@ -0,0 +1,475 @@
+use crate::gl;
+
+/// A rectangle in window pixels, with the origin at the bottom-left as in OpenGL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub pos: [i32; 2],
+    pub size: [i32; 2],
+}
+
+impl Viewport {
+    pub fn new(pos: [i32; 2], size: [i32; 2]) -> Self {
+        Self { pos, size }
+    }
+
+    pub fn gl_viewport(&self) {
+        unsafe {
+            gl::Viewport(self.pos[0], self.pos[1], self.size[0], self.size[1]);
+        }
+    }
+
+    /// The x coordinate one past the right edge.
+    pub fn right(&self) -> i32 {
+        self.pos[0] + self.size[0]
+    }
+
+    /// The y coordinate one past the top edge.
+    pub fn top(&self) -> i32 {
+        self.pos[1] + self.size[1]
+    }
+
+    pub fn center(&self) -> [f32; 2] {
+        [
+            self.pos[0] as f32 + self.size[0] as f32 * 0.5,
+            self.pos[1] as f32 + self.size[1] as f32 * 0.5,
+        ]
+    }
+
+    /// Width divided by height. Zero if the viewport has no height.
+    pub fn aspect_ratio(&self) -> f32 {
+        if self.size[1] == 0 {
+            0.0
+        } else {
+            self.size[0] as f32 / self.size[1] as f32
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size[0] <= 0 || self.size[1] <= 0
+    }
+
+    /// Whether a point in pixels lies inside the viewport.
+    /// The left and bottom edges are inclusive, the right and top edges exclusive.
+    pub fn contains(&self, point: [i32; 2]) -> bool {
+        point[0] >= self.pos[0]
+            && point[0] < self.right()
+            && point[1] >= self.pos[1]
+            && point[1] < self.top()
+    }
+
+    /// The overlapping area of two viewports, or `None` if they don't overlap.
+    pub fn intersect(&self, other: &Viewport) -> Option<Viewport> {
+        let x1 = self.pos[0].max(other.pos[0]);
+        let y1 = self.pos[1].max(other.pos[1]);
+        let x2 = self.right().min(other.right());
+        let y2 = self.top().min(other.top());
+
+        if x2 <= x1 || y2 <= y1 {
+            None
+        } else {
+            Some(Viewport::new([x1, y1], [x2 - x1, y2 - y1]))
+        }
+    }
+
+    /// The smallest viewport containing both viewports.
+    /// Empty viewports are ignored.
+    pub fn union(&self, other: &Viewport) -> Viewport {
+        if other.is_empty() {
+            return *self;
+        }
+        if self.is_empty() {
+            return *other;
+        }
+
+        let x1 = self.pos[0].min(other.pos[0]);
+        let y1 = self.pos[1].min(other.pos[1]);
+        let x2 = self.right().max(other.right());
+        let y2 = self.top().max(other.top());
+
+        Viewport::new([x1, y1], [x2 - x1, y2 - y1])
+    }
+
+    /// The parts of this viewport not covered by `other`, as up to four non-overlapping viewports:
+    /// full-height strips on the left and right, then the strips below and above `other`.
+    pub fn subtract(&self, other: &Viewport) -> Vec<Viewport> {
+        let Some(overlap) = self.intersect(other) else {
+            return if self.is_empty() { Vec::new() } else { vec![*self] };
+        };
+
+        let regions = [
+            Viewport::new(self.pos, [overlap.pos[0] - self.pos[0], self.size[1]]),
+            Viewport::new([overlap.right(), self.pos[1]], [self.right() - overlap.right(), self.size[1]]),
+            Viewport::new([overlap.pos[0], self.pos[1]], [overlap.size[0], overlap.pos[1] - self.pos[1]]),
+            Viewport::new([overlap.pos[0], overlap.top()], [overlap.size[0], self.top() - overlap.top()]),
+        ];
+
+        regions.into_iter().filter(|r| !r.is_empty()).collect()
+    }
+
+    /// Moves each edge inwards by the given number of pixels.
+    /// If the edges cross, the result has a size of zero.
+    pub fn inset(&self, left: i32, right: i32, top: i32, bottom: i32) -> Viewport {
+        let width = self.size[0] - left - right;
+        let height = self.size[1] - top - bottom;
+        let size = if width < 0 || height < 0 {
+            [0, 0]
+        } else {
+            [width, height]
+        };
+
+        Viewport::new([self.pos[0] + left, self.pos[1] + bottom], size)
+    }
+
+    /// Moves each edge outwards by the given number of pixels.
+    pub fn outset(&self, left: i32, right: i32, top: i32, bottom: i32) -> Viewport {
+        self.inset(-left, -right, -top, -bottom)
+    }
+
+    /// Splits the viewport into a left and right part at the given split point.
+    pub fn split_horizontal(&self, split_point: SplitPoint) -> (Viewport, Viewport) {
+        let sp = split_point.to_absolute(self.size[0]);
+        let left = Viewport::new(self.pos, [sp, self.size[1]]);
+        let right = Viewport::new([self.pos[0] + sp, self.pos[1]], [self.size[0] - sp, self.size[1]]);
+        (left, right)
+    }
+
+    /// Splits the viewport into two parts stacked along the y axis at the given split point.
+    /// The first part starts at `pos`, which is the bottom edge in OpenGL window coordinates.
+    pub fn split_vertical(&self, split_point: SplitPoint) -> (Viewport, Viewport) {
+        let sp = split_point.to_absolute(self.size[1]);
+        let first = Viewport::new(self.pos, [self.size[0], sp]);
+        let second = Viewport::new([self.pos[0], self.pos[1] + sp], [self.size[0], self.size[1] - sp]);
+        (first, second)
+    }
+
+    /// Places a rectangle of the given size inside the viewport.
+    /// `align` is the fractional position along each axis: 0.0 puts the rectangle at the
+    /// left or bottom edge, 0.5 centers it and 1.0 puts it at the right or top edge.
+    /// The rectangle may be larger than the viewport, in which case it overhangs.
+    pub fn place(&self, size: [i32; 2], align: [f32; 2]) -> Viewport {
+        let free = [self.size[0] - size[0], self.size[1] - size[1]];
+        let offset = [
+            (free[0] as f32 * align[0]) as i32,
+            (free[1] as f32 * align[1]) as i32,
+        ];
+        Viewport::new([self.pos[0] + offset[0], self.pos[1] + offset[1]], size)
+    }
+
+    /// The largest centered viewport with the given aspect ratio that fits inside this one.
+    pub fn aspect_fit(&self, aspect_ratio: f32) -> Viewport {
+        let new_width = (self.size[1] as f32 * aspect_ratio).round() as i32;
+        let size = if new_width <= self.size[0] {
+            [new_width, self.size[1]]
+        } else {
+            [self.size[0], (self.size[0] as f32 / aspect_ratio).round() as i32]
+        };
+        self.place(size, [0.5, 0.5])
+    }
+
+    /// The smallest centered viewport with the given aspect ratio that covers this one.
+    pub fn aspect_fill(&self, aspect_ratio: f32) -> Viewport {
+        let new_width = (self.size[1] as f32 * aspect_ratio).round() as i32;
+        let size = if new_width >= self.size[0] {
+            [new_width, self.size[1]]
+        } else {
+            [self.size[0], (self.size[0] as f32 / aspect_ratio).round() as i32]
+        };
+        self.place(size, [0.5, 0.5])
+    }
+
+    /// Converts a point in window pixels (bottom-left origin) to normalized device
+    /// coordinates relative to this viewport, where the viewport spans -1.0 to 1.0.
+    /// An empty viewport has no meaningful mapping, so every point maps to its center, `[0.0, 0.0]`.
+    pub fn pixel_to_ndc(&self, pixel: [f32; 2]) -> [f32; 2] {
+        if self.is_empty() {
+            return [0.0, 0.0];
+        }
+
+        [
+            (pixel[0] - self.pos[0] as f32) / self.size[0] as f32 * 2.0 - 1.0,
+            (pixel[1] - self.pos[1] as f32) / self.size[1] as f32 * 2.0 - 1.0,
+        ]
+    }
+
+    /// Converts normalized device coordinates relative to this viewport to window pixels
+    /// (bottom-left origin). The inverse of [`pixel_to_ndc`](Self::pixel_to_ndc).
+    pub fn ndc_to_pixel(&self, ndc: [f32; 2]) -> [f32; 2] {
+        [
+            self.pos[0] as f32 + (ndc[0] + 1.0) * 0.5 * self.size[0] as f32,
+            self.pos[1] as f32 + (ndc[1] + 1.0) * 0.5 * self.size[1] as f32,
+        ]
+    }
+
+    /// Mirrors the viewport vertically within a window of the given height.
+    /// Converts between OpenGL window coordinates (origin at the bottom-left)
+    /// and the top-left origin used by most windowing systems. Applying it twice
+    /// gives back the original viewport.
+    pub fn flip_y(&self, window_height: i32) -> Viewport {
+        Viewport::new([self.pos[0], window_height - self.top()], self.size)
+    }
+
+    /// Mirrors a point vertically within a window of the given height,
+    /// converting between top-left and bottom-left origins.
+    pub fn flip_point_y(point: [f32; 2], window_height: i32) -> [f32; 2] {
+        [point[0], window_height as f32 - point[1]]
+    }
+}
+
+/// Defines the split point of a split renderer.
+/// Use a negative value to specify a split point relative to the far edge of the viewport.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SplitPoint {
+    /// Absolute split point in pixels.
+    Absolute(i32),
+
+    /// Ratio of the viewport size (0.0 to 1.0).
+    Ratio(f32),
+}
+
+impl SplitPoint {
+    pub fn to_absolute(&self, viewport_size: i32) -> i32 {
+        let mut sp = match self {
+            SplitPoint::Absolute(x) => *x,
+            SplitPoint::Ratio(r) => (viewport_size as f32 * r) as i32,
+        };
+
+        if sp < 0 {
+            sp += viewport_size
+        }
+
+        sp.clamp(0, viewport_size)
+    }
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::new([0, 0], [0, 0])
+    }
+}
+
+impl From<[[i32; 2]; 2]> for Viewport {
+    fn from(arr: [[i32; 2]; 2]) -> Self {
+        Self::new(arr[0], arr[1])
+    }
+}
+
+impl From<[i32; 4]> for Viewport {
+    fn from(arr: [i32; 4]) -> Self {
+        Self::new([arr[0], arr[1]], [arr[2], arr[3]])
+    }
+}
+
+impl From<(i32, i32, i32, i32)> for Viewport {
+    fn from(arr: (i32, i32, i32, i32)) -> Self {
+        Self::new([arr.0, arr.1], [arr.2, arr.3])
+    }
+}
+
+impl From<[i32;2]> for Viewport {
+    fn from(arr: [i32;2]) -> Self {
+        Self::new([0, 0], arr)
+    }
+}
+
+impl From<(i32, i32)> for Viewport {
+    fn from(arr: (i32, i32)) -> Self {
+        Self::new([0, 0], [arr.0, arr.1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vp(x: i32, y: i32, w: i32, h: i32) -> Viewport {
+        Viewport::new([x, y], [w, h])
+    }
+
+    #[test]
+    fn contains_is_half_open() {
+        let v = vp(0, 0, 10, 10);
+        assert!(v.contains([0, 0]));
+        assert!(v.contains([9, 9]));
+        assert!(!v.contains([10, 5]));
+        assert!(!v.contains([5, 10]));
+        assert!(!v.contains([-1, 0]));
+    }
+
+    #[test]
+    fn empty_contains_nothing() {
+        assert!(!vp(5, 5, 0, 10).contains([5, 5]));
+        assert!(!vp(5, 5, -3, 10).contains([4, 5]));
+    }
+
+    #[test]
+    fn intersect_overlapping() {
+        let a = vp(0, 0, 10, 10);
+        let b = vp(5, 2, 10, 4);
+        assert_eq!(a.intersect(&b), Some(vp(5, 2, 5, 4)));
+        assert_eq!(b.intersect(&a), Some(vp(5, 2, 5, 4)));
+    }
+
+    #[test]
+    fn intersect_contained() {
+        let outer = vp(0, 0, 100, 100);
+        let inner = vp(10, 20, 30, 40);
+        assert_eq!(outer.intersect(&inner), Some(inner));
+    }
+
+    #[test]
+    fn intersect_touching_or_disjoint_is_none() {
+        let a = vp(0, 0, 10, 10);
+        assert_eq!(a.intersect(&vp(10, 0, 10, 10)), None);
+        assert_eq!(a.intersect(&vp(0, 10, 10, 10)), None);
+        assert_eq!(a.intersect(&vp(50, 50, 10, 10)), None);
+    }
+
+    #[test]
+    fn intersect_with_empty_is_none() {
+        let a = vp(0, 0, 10, 10);
+        assert_eq!(a.intersect(&vp(5, 5, 0, 0)), None);
+        assert_eq!(vp(5, 5, 0, 3).intersect(&a), None);
+    }
+
+    #[test]
+    fn union_covers_both() {
+        let a = vp(0, 0, 10, 10);
+        let b = vp(20, -5, 5, 5);
+        assert_eq!(a.union(&b), vp(0, -5, 25, 15));
+        assert_eq!(b.union(&a), vp(0, -5, 25, 15));
+    }
+
+    #[test]
+    fn union_ignores_empty() {
+        let a = vp(3, 4, 10, 10);
+        let empty = vp(-100, -100, 0, 0);
+        assert_eq!(a.union(&empty), a);
+        assert_eq!(empty.union(&a), a);
+        assert!(empty.union(&Viewport::default()).is_empty());
+    }
+
+    #[test]
+    fn subtract_leaves_non_overlapping_strips() {
+        let a = vp(0, 0, 10, 10);
+        let hole = vp(3, 4, 2, 2);
+        let parts = a.subtract(&hole);
+
+        assert_eq!(parts, vec![vp(0, 0, 3, 10), vp(5, 0, 5, 10), vp(3, 0, 2, 4), vp(3, 6, 2, 4)]);
+        let area: i32 = parts.iter().map(|p| p.size[0] * p.size[1]).sum();
+        assert_eq!(area, 100 - 4);
+        assert!(a.subtract(&a).is_empty());
+        assert_eq!(a.subtract(&vp(20, 20, 5, 5)), vec![a]);
+    }
+
+    #[test]
+    fn inset_and_outset() {
+        let v = vp(0, 0, 100, 50);
+        assert_eq!(v.inset(10, 20, 5, 15), vp(10, 15, 70, 30));
+        assert_eq!(vp(10, 10, 20, 20).outset(1, 2, 3, 4), vp(9, 6, 23, 27));
+        assert_eq!(v.inset(10, 20, 5, 15).outset(10, 20, 5, 15), v);
+    }
+
+    #[test]
+    fn inset_past_the_edges_is_empty() {
+        let v = vp(0, 0, 100, 50);
+        let crossed = v.inset(60, 60, 0, 0);
+        assert!(crossed.is_empty());
+        assert_eq!(crossed.size, [0, 0]);
+        assert!(v.inset(0, 0, 30, 30).is_empty());
+        assert_eq!(v.inset(50, 50, 25, 25).size, [0, 0]);
+    }
+
+    #[test]
+    fn split_horizontal() {
+        let v = vp(10, 20, 100, 50);
+        assert_eq!(v.split_horizontal(SplitPoint::Ratio(0.25)), (vp(10, 20, 25, 50), vp(35, 20, 75, 50)));
+        assert_eq!(v.split_horizontal(SplitPoint::Absolute(-30)), (vp(10, 20, 70, 50), vp(80, 20, 30, 50)));
+    }
+
+    #[test]
+    fn split_vertical() {
+        let v = vp(0, 0, 40, 30);
+        assert_eq!(v.split_vertical(SplitPoint::Absolute(10)), (vp(0, 0, 40, 10), vp(0, 10, 40, 20)));
+        assert_eq!(v.split_vertical(SplitPoint::Ratio(0.5)), (vp(0, 0, 40, 15), vp(0, 15, 40, 15)));
+    }
+
+    #[test]
+    fn split_clamps_to_the_viewport() {
+        let v = vp(0, 0, 100, 50);
+        assert_eq!(v.split_horizontal(SplitPoint::Absolute(200)), (v, vp(100, 0, 0, 50)));
+        assert_eq!(v.split_vertical(SplitPoint::Absolute(-200)), (vp(0, 0, 100, 0), v));
+
+        let (first, second) = Viewport::default().split_horizontal(SplitPoint::Ratio(0.5));
+        assert!(first.is_empty() && second.is_empty());
+    }
+
+    #[test]
+    fn place_aligns_within_the_viewport() {
+        let v = vp(0, 0, 100, 100);
+        assert_eq!(v.place([20, 10], [0.5, 0.5]), vp(40, 45, 20, 10));
+        assert_eq!(v.place([20, 10], [0.0, 0.0]), vp(0, 0, 20, 10));
+        assert_eq!(v.place([20, 10], [1.0, 1.0]), vp(80, 90, 20, 10));
+    }
+
+    #[test]
+    fn place_overhangs_when_too_large() {
+        let v = vp(10, 10, 100, 100);
+        assert_eq!(v.place([120, 100], [0.5, 0.5]), vp(0, 10, 120, 100));
+    }
+
+    #[test]
+    fn aspect_fit() {
+        let v = vp(0, 0, 200, 100);
+        assert_eq!(v.aspect_fit(1.0), vp(50, 0, 100, 100));
+        assert_eq!(v.aspect_fit(4.0), vp(0, 25, 200, 50));
+        assert_eq!(v.aspect_fit(2.0), v);
+    }
+
+    #[test]
+    fn aspect_fill() {
+        let v = vp(0, 0, 200, 100);
+        assert_eq!(v.aspect_fill(1.0), vp(0, -50, 200, 200));
+        assert_eq!(v.aspect_fill(4.0), vp(-100, 0, 400, 100));
+        assert_eq!(v.aspect_fill(2.0), v);
+    }
+
+    #[test]
+    fn aspect_of_empty_viewport_is_empty() {
+        assert!(Viewport::default().aspect_fit(1.5).is_empty());
+        assert!(Viewport::default().aspect_fill(1.5).is_empty());
+        assert!(vp(0, 0, 100, 0).aspect_fit(2.0).is_empty());
+    }
+
+    #[test]
+    fn pixel_to_ndc_maps_corners_and_center() {
+        let v = vp(10, 20, 100, 50);
+        assert_eq!(v.pixel_to_ndc([10.0, 20.0]), [-1.0, -1.0]);
+        assert_eq!(v.pixel_to_ndc([110.0, 70.0]), [1.0, 1.0]);
+        assert_eq!(v.pixel_to_ndc([60.0, 45.0]), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn ndc_to_pixel_inverts_pixel_to_ndc() {
+        let v = vp(10, 20, 100, 50);
+        for pixel in [[10.0, 20.0], [35.0, 60.0], [110.0, 70.0], [0.0, 0.0]] {
+            let back = v.ndc_to_pixel(v.pixel_to_ndc(pixel));
+            assert!((back[0] - pixel[0]).abs() < 1e-4 && (back[1] - pixel[1]).abs() < 1e-4);
+        }
+        assert_eq!(v.ndc_to_pixel([0.0, 0.0]), v.center());
+    }
+
+    #[test]
+    fn pixel_to_ndc_of_empty_viewport_is_finite() {
+        assert_eq!(Viewport::default().pixel_to_ndc([5.0, 5.0]), [0.0, 0.0]);
+        assert_eq!(vp(0, 0, 100, 0).pixel_to_ndc([50.0, 0.0]), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn flip_y_round_trips() {
+        let v = vp(10, 20, 30, 40);
+        assert_eq!(v.flip_y(100), vp(10, 40, 30, 40));
+        assert_eq!(v.flip_y(100).flip_y(100), v);
+        assert_eq!(Viewport::flip_point_y([3.0, 25.0], 100), [3.0, 75.0]);
+        assert_eq!(Viewport::flip_point_y(Viewport::flip_point_y([3.0, 25.0], 100), 100), [3.0, 25.0]);
+    }
+}
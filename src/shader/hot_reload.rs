@@ -0,0 +1,341 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use gl::types::GLenum;
+
+use crate::Error;
+
+use super::{CustomShader, ShaderProgram};
+
+/// How often files are checked for changes, unless set otherwise.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// What happened during a [`HotShaderProgram::poll`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReloadStatus {
+    /// No file changed, or it wasn't time to check yet.
+    Unchanged,
+
+    /// A file changed and the program was rebuilt.
+    Reloaded,
+
+    /// A file changed, but it couldn't be read or compiled. The previous program is still used.
+    Failed,
+}
+
+/// A file and the modification time it had when it was last read.
+#[derive(Debug, Clone)]
+struct WatchedFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl WatchedFile {
+    fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            modified: None,
+        }
+    }
+
+    /// Whether the modification time changed since the last read. A file that disappears
+    /// counts as changed once, and again when it comes back.
+    fn changed(&self) -> bool {
+        modified_time(&self.path) != self.modified
+    }
+
+    fn read(&mut self) -> Result<String, Error> {
+        // Taken before reading, so a write during the read is picked up by the next poll.
+        self.modified = modified_time(&self.path);
+        fs::read_to_string(&self.path).map_err(|e| format!("{}: {}", self.path.display(), e).into())
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Limits how often the file system is checked, so polling can be done every frame.
+/// The first check is never delayed.
+#[derive(Debug, Clone)]
+struct PollTimer {
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl PollTimer {
+    fn new() -> Self {
+        Self {
+            interval: DEFAULT_POLL_INTERVAL,
+            last: None,
+        }
+    }
+
+    fn ready(&mut self) -> bool {
+        let now = Instant::now();
+        if self.last.is_some_and(|last| now.duration_since(last) < self.interval) {
+            return false;
+        }
+
+        self.last = Some(now);
+        true
+    }
+}
+
+/// A [`ShaderProgram`] loaded from files, which is rebuilt when the files change.
+///
+/// Meant for development, to edit shaders without restarting the application. Call
+/// [`poll`](Self::poll) regularly, for example once per frame; the files are only checked
+/// every [`DEFAULT_POLL_INTERVAL`] unless set otherwise. When a new version fails to compile,
+/// the previous program keeps being used and the error is available from
+/// [`error`](Self::error) until a later version compiles.
+///
+/// Uniforms are not carried over to the new program, so they have to be set again after
+/// a reload.
+pub struct HotShaderProgram {
+    program: ShaderProgram,
+    stages: Vec<(GLenum, WatchedFile)>,
+    timer: PollTimer,
+    error: Option<String>,
+}
+
+impl HotShaderProgram {
+    /// Loads and compiles a vertex and a fragment shader. Unlike later reloads, failing to
+    /// compile here is an error, since there is no previous program to fall back to.
+    pub fn new<P: AsRef<Path>, Q: AsRef<Path>>(vertex_path: P, fragment_path: Q) -> Result<Self, Error> {
+        Self::with_stages(vec![
+            (gl::VERTEX_SHADER, WatchedFile::new(vertex_path.as_ref())),
+            (gl::FRAGMENT_SHADER, WatchedFile::new(fragment_path.as_ref())),
+        ])
+    }
+
+    /// Loads and compiles a compute shader.
+    pub fn compute<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::with_stages(vec![(gl::COMPUTE_SHADER, WatchedFile::new(path.as_ref()))])
+    }
+
+    fn with_stages(mut stages: Vec<(GLenum, WatchedFile)>) -> Result<Self, Error> {
+        let program = build(&mut stages).map_err(|e| format!("HotShaderProgram::new: {}", e))?;
+
+        Ok(Self {
+            program,
+            stages,
+            timer: PollTimer::new(),
+            error: None,
+        })
+    }
+
+    /// The current program: the last one that compiled.
+    pub fn program(&self) -> &ShaderProgram {
+        &self.program
+    }
+
+    /// The error of the last reload, if it failed.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Sets the minimum time between two checks of the files.
+    pub fn set_poll_interval(&mut self, interval: Duration) {
+        self.timer.interval = interval;
+    }
+
+    /// Rebuilds the program if any of the files changed since they were last read.
+    pub fn poll(&mut self) -> ReloadStatus {
+        if !self.timer.ready() || !self.stages.iter().any(|(_, file)| file.changed()) {
+            return ReloadStatus::Unchanged;
+        }
+
+        match self.reload() {
+            Ok(()) => ReloadStatus::Reloaded,
+            Err(_) => ReloadStatus::Failed,
+        }
+    }
+
+    /// Reads all files again and rebuilds the program, whether they changed or not.
+    /// On failure the previous program is kept.
+    pub fn reload(&mut self) -> Result<(), Error> {
+        match build(&mut self.stages) {
+            Ok(program) => {
+                self.program = program;
+                self.error = None;
+                Ok(())
+            }
+            Err(e) => {
+                let message = format!("HotShaderProgram::reload: {}", e);
+                self.error = Some(message.clone());
+                Err(message.into())
+            }
+        }
+    }
+}
+
+fn build(stages: &mut [(GLenum, WatchedFile)]) -> Result<ShaderProgram, Error> {
+    let mut builder = glh::ProgramBuilder::new();
+    for (stage, file) in stages.iter_mut() {
+        let source = file.read()?;
+        let in_file = |e: Error| format!("{}: {}", file.path.display(), e);
+        builder = match *stage {
+            gl::VERTEX_SHADER => builder.with_vertex_shader(&source).map_err(in_file)?,
+            gl::FRAGMENT_SHADER => builder.with_fragment_shader(&source).map_err(in_file)?,
+            _ => builder.with_compute_shader(&source).map_err(in_file)?,
+        };
+    }
+
+    Ok(ShaderProgram::from_raw(builder.build()?))
+}
+
+/// [`CustomShader`] hooks loaded from files, reloaded when the files change.
+///
+/// Renderers with shader hooks take these in `watch_shader_files`, which fails if the files
+/// can't be loaded or compiled, and reload them in [`update`](crate::renderers::Renderer::update)
+/// whenever they change, replacing the hooks set with `set_custom_shader`. A failed reload keeps
+/// the previous shader and reports the error through the renderer's `shader_error`. Uniforms
+/// declared by the hooks are reset by a reload, so give them initializers or set them every frame.
+///
+/// To drive the reloading yourself, pass the result of
+/// [`poll`](Self::poll) to a renderer's `set_custom_shader`, which keeps the previous shader
+/// if the new one fails to compile:
+///
+/// ```ignore
+/// if let Some(shader) = hooks.poll() {
+///     if let Err(e) = shader.and_then(|shader| renderer.set_custom_shader(Some(shader))) {
+///         eprintln!("{}", e);
+///     }
+/// }
+/// ```
+pub struct CustomShaderFiles {
+    vertex: Option<WatchedFile>,
+    fragment: Option<WatchedFile>,
+    timer: PollTimer,
+    loaded: bool,
+    error: Option<String>,
+}
+
+impl CustomShaderFiles {
+    /// Loads only the fragment hook from a file.
+    pub fn fragment<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            vertex: None,
+            fragment: Some(WatchedFile::new(path.as_ref())),
+            timer: PollTimer::new(),
+            loaded: false,
+            error: None,
+        }
+    }
+
+    pub fn with_vertex<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.vertex = Some(WatchedFile::new(path.as_ref()));
+        self
+    }
+
+    /// Sets the minimum time between two checks of the files.
+    pub fn set_poll_interval(&mut self, interval: Duration) {
+        self.timer.interval = interval;
+    }
+
+    /// Reads the files, whether they changed or not.
+    pub fn load(&mut self) -> Result<CustomShader, Error> {
+        self.loaded = true;
+        let read = |file: &mut Option<WatchedFile>| file.as_mut().map(WatchedFile::read).transpose();
+
+        Ok(CustomShader {
+            vertex: read(&mut self.vertex).map_err(|e| format!("CustomShaderFiles::load: {}", e))?,
+            fragment: read(&mut self.fragment).map_err(|e| format!("CustomShaderFiles::load: {}", e))?,
+        })
+    }
+
+    /// Reads the files if any of them changed since they were last read, which is always
+    /// the case on the first call. Returns `None` if nothing changed.
+    pub fn poll(&mut self) -> Option<Result<CustomShader, Error>> {
+        if !self.timer.ready() {
+            return None;
+        }
+
+        let changed = self.vertex.iter().chain(&self.fragment).any(WatchedFile::changed);
+        if self.loaded && !changed {
+            return None;
+        }
+
+        Some(self.load())
+    }
+
+    /// Like [`poll`](Self::poll), but passes changed hooks to `apply`, usually a renderer's
+    /// `set_custom_shader`. If the files can't be read or `apply` fails, the error is available
+    /// from [`error`](Self::error) until a later version is applied.
+    pub fn poll_with<F>(&mut self, apply: F) -> ReloadStatus
+    where
+        F: FnOnce(CustomShader) -> Result<(), Error>,
+    {
+        let Some(shader) = self.poll() else {
+            return ReloadStatus::Unchanged;
+        };
+
+        match shader.and_then(apply) {
+            Ok(()) => {
+                self.error = None;
+                ReloadStatus::Reloaded
+            }
+            Err(e) => {
+                self.error = Some(e.to_string());
+                ReloadStatus::Failed
+            }
+        }
+    }
+
+    /// The error of the last reload through [`poll_with`](Self::poll_with), if it failed.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_hook(name: &str, source: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("glenda-{}-{}.glsl", std::process::id(), name));
+        fs::write(&path, source).unwrap();
+        path
+    }
+
+    #[test]
+    fn poll_with_applies_hooks_once() {
+        let path = write_hook("applies", "vec4 effect(vec2 uv) { return vec4(1.0); }");
+        let mut files = CustomShaderFiles::fragment(&path);
+        files.set_poll_interval(Duration::ZERO);
+
+        let mut applied = Vec::new();
+        let status = files.poll_with(|shader| {
+            applied.push(shader);
+            Ok(())
+        });
+        assert_eq!(status, ReloadStatus::Reloaded);
+        assert_eq!(applied, [CustomShader::fragment("vec4 effect(vec2 uv) { return vec4(1.0); }")]);
+        assert_eq!(files.poll_with(|_| panic!("unchanged files are applied again")), ReloadStatus::Unchanged);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn poll_with_keeps_the_error_until_a_reload_succeeds() {
+        let path = write_hook("error", "");
+        let mut files = CustomShaderFiles::fragment(&path);
+
+        assert_eq!(files.poll_with(|_| Err("does not compile".into())), ReloadStatus::Failed);
+        assert_eq!(files.error(), Some("does not compile"));
+
+        assert_eq!(files.load().unwrap(), CustomShader::fragment(""));
+        fs::remove_file(&path).unwrap();
+        files.set_poll_interval(Duration::ZERO);
+        assert_eq!(files.poll_with(|_| Ok(())), ReloadStatus::Failed);
+        assert!(files.error().unwrap().contains("CustomShaderFiles::load"));
+
+        fs::write(&path, "").unwrap();
+        assert_eq!(files.poll_with(|_| Ok(())), ReloadStatus::Reloaded);
+        assert_eq!(files.error(), None);
+
+        fs::remove_file(path).unwrap();
+    }
+}
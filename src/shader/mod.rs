@@ -0,0 +1,357 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+
+use gl::types::*;
+
+use crate::Error;
+
+mod hot_reload;
+mod uniform;
+
+pub use hot_reload::{CustomShaderFiles, HotShaderProgram, ReloadStatus, DEFAULT_POLL_INTERVAL};
+pub use uniform::{glsl_type_name, TextureUnit, UniformValue};
+
+/// GLSL replacing the hook functions of a built-in renderer's shaders, for effects such as
+/// palette swaps, outlines or dissolves.
+///
+/// Each renderer that supports custom shaders documents the functions it calls and the inputs
+/// available to them. Hooks are compiled after the renderer's own shader code, so they can use
+/// its inputs, uniforms and helper functions, and declare uniforms of their own. Line numbers in
+/// compile errors count from the start of the hook.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CustomShader {
+    pub vertex: Option<String>,
+    pub fragment: Option<String>,
+}
+
+impl CustomShader {
+    /// Replaces only the fragment hook.
+    pub fn fragment<S: Into<String>>(source: S) -> Self {
+        Self {
+            vertex: None,
+            fragment: Some(source.into()),
+        }
+    }
+
+    pub fn with_vertex<S: Into<String>>(mut self, source: S) -> Self {
+        self.vertex = Some(source.into());
+        self
+    }
+}
+
+/// Builds a renderer's program from its shader code, which declares the hook functions,
+/// followed by the hook implementations: the custom ones if given, the defaults otherwise.
+pub(crate) fn build_with_hooks(
+    vertex: &str,
+    default_vertex_hook: &str,
+    fragment: &str,
+    default_fragment_hook: &str,
+    custom: Option<&CustomShader>,
+) -> Result<ShaderProgram, Error> {
+    let vertex_hook = custom.and_then(|c| c.vertex.as_deref()).unwrap_or(default_vertex_hook);
+    let fragment_hook = custom.and_then(|c| c.fragment.as_deref()).unwrap_or(default_fragment_hook);
+
+    ShaderProgram::new(
+        &format!("{}\n#line 1\n{}", vertex, vertex_hook),
+        &format!("{}\n#line 1\n{}", fragment, fragment_hook),
+    )
+}
+
+/// A renderer's program built with [`build_with_hooks`], together with the custom hooks it
+/// was built from and, in development mode, the files they are reloaded from.
+///
+/// Uniforms don't carry over to a rebuilt program, so renderers set their own again whenever
+/// [`set_custom_shader`](Self::set_custom_shader), [`watch_files`](Self::watch_files) or
+/// [`poll_files`](Self::poll_files) replace it.
+pub(crate) struct HookedProgram {
+    program: ShaderProgram,
+    build: fn(Option<&CustomShader>) -> Result<ShaderProgram, Error>,
+    custom_shader: Option<CustomShader>,
+    files: Option<CustomShaderFiles>,
+}
+
+impl HookedProgram {
+    /// Builds the program with the default hooks. `build` passes the hooks on to
+    /// [`build_with_hooks`] along with the renderer's shader code.
+    pub fn new(build: fn(Option<&CustomShader>) -> Result<ShaderProgram, Error>) -> Result<Self, Error> {
+        Ok(Self {
+            program: build(None)?,
+            build,
+            custom_shader: None,
+            files: None,
+        })
+    }
+
+    pub fn program(&self) -> &ShaderProgram {
+        &self.program
+    }
+
+    pub fn custom_shader(&self) -> Option<&CustomShader> {
+        self.custom_shader.as_ref()
+    }
+
+    /// Rebuilds the program with `shader`, or with the default hooks for `None`.
+    /// On failure the previous program is kept.
+    pub fn set_custom_shader(&mut self, shader: Option<CustomShader>) -> Result<(), Error> {
+        self.program = (self.build)(shader.as_ref())?;
+        self.custom_shader = shader;
+        Ok(())
+    }
+
+    /// Rebuilds the program with the hooks loaded from `files`, and keeps the files to reload
+    /// them from in [`poll_files`](Self::poll_files). Fails if they can't be loaded or compiled.
+    pub fn watch_files(&mut self, mut files: CustomShaderFiles) -> Result<(), Error> {
+        self.set_custom_shader(Some(files.load()?))?;
+        self.files = Some(files);
+        Ok(())
+    }
+
+    /// Stops reloading the hooks. The last ones that compiled stay in use.
+    pub fn stop_watching_files(&mut self) {
+        self.files = None;
+    }
+
+    /// The error of the last failed reload, if the current files don't compile.
+    pub fn files_error(&self) -> Option<&str> {
+        self.files.as_ref().and_then(CustomShaderFiles::error)
+    }
+
+    /// Rebuilds the program if the watched files changed. If the new hooks don't compile,
+    /// the previous program is kept and the error is available from
+    /// [`files_error`](Self::files_error).
+    pub fn poll_files(&mut self) -> ReloadStatus {
+        let Some(files) = self.files.as_mut() else {
+            return ReloadStatus::Unchanged;
+        };
+
+        let (program, custom_shader, build) = (&mut self.program, &mut self.custom_shader, self.build);
+        files.poll_with(|shader| {
+            *program = build(Some(&shader))?;
+            *custom_shader = Some(shader);
+            Ok(())
+        })
+    }
+}
+
+/// An active uniform or vertex attribute of a linked program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariableInfo {
+    /// The name, without any `[0]` suffix for arrays.
+    pub name: String,
+    pub location: GLint,
+
+    /// The type, such as `gl::FLOAT_VEC4`. See [`glsl_type_name`].
+    pub gl_type: GLenum,
+
+    /// Number of array elements, 1 if the variable isn't an array.
+    pub size: GLint,
+}
+
+/// A linked OpenGL program, with its active uniforms and attributes looked up once at link time.
+///
+/// Uniforms are set by name with [`set_uniform`](Self::set_uniform), which checks that the
+/// uniform exists and has a matching type. The program doesn't need to be in use to set them.
+/// The program is deleted when dropped.
+pub struct ShaderProgram {
+    program: GLuint,
+    uniforms: HashMap<String, VariableInfo>,
+    attributes: HashMap<String, VariableInfo>,
+}
+
+impl ShaderProgram {
+    /// Compiles and links a vertex and a fragment shader.
+    pub fn new(vertex_source: &str, fragment_source: &str) -> Result<Self, Error> {
+        let program = glh::ProgramBuilder::new()
+            .with_vertex_shader(vertex_source)?
+            .with_fragment_shader(fragment_source)?
+            .build()?;
+
+        Ok(Self::from_raw(program))
+    }
+
+    /// Compiles and links a compute shader.
+    pub fn compute(source: &str) -> Result<Self, Error> {
+        let program = glh::ProgramBuilder::new()
+            .with_compute_shader(source)?
+            .build()?;
+
+        Ok(Self::from_raw(program))
+    }
+
+    /// Takes ownership of an already linked program, such as one from `glh::ProgramBuilder`.
+    pub fn from_raw(program: GLuint) -> Self {
+        let uniforms = unsafe {
+            active_variables(program, gl::ACTIVE_UNIFORMS, gl::ACTIVE_UNIFORM_MAX_LENGTH, gl::GetActiveUniform, gl::GetUniformLocation)
+        };
+        let attributes = unsafe {
+            active_variables(program, gl::ACTIVE_ATTRIBUTES, gl::ACTIVE_ATTRIBUTE_MAX_LENGTH, gl::GetActiveAttrib, gl::GetAttribLocation)
+        };
+
+        Self {
+            program,
+            uniforms,
+            attributes,
+        }
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.program
+    }
+
+    /// Makes this the current program, for drawing or dispatching.
+    pub fn use_program(&self) {
+        unsafe {
+            gl::UseProgram(self.program);
+        }
+    }
+
+    /// Active uniforms outside of uniform blocks. Uniforms the compiler found unused are not included.
+    pub fn uniforms(&self) -> impl Iterator<Item = &VariableInfo> {
+        self.uniforms.values()
+    }
+
+    pub fn uniform(&self, name: &str) -> Option<&VariableInfo> {
+        self.uniforms.get(name)
+    }
+
+    pub fn has_uniform(&self, name: &str) -> bool {
+        self.uniforms.contains_key(name)
+    }
+
+    /// The location of an active uniform, or an error if there is none with that name.
+    pub fn uniform_location(&self, name: &str) -> Result<GLint, Error> {
+        self.uniforms
+            .get(name)
+            .map(|uniform| uniform.location)
+            .ok_or_else(|| format!("ShaderProgram: No active uniform named '{}'", name).into())
+    }
+
+    /// Active vertex attributes, not including built-ins such as `gl_VertexID`.
+    pub fn attributes(&self) -> impl Iterator<Item = &VariableInfo> {
+        self.attributes.values()
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&VariableInfo> {
+        self.attributes.get(name)
+    }
+
+    /// Sets a uniform by name. Fails if the program has no active uniform with that name,
+    /// or if its type doesn't match the value.
+    pub fn set_uniform<V: UniformValue>(&self, name: &str, value: V) -> Result<(), Error> {
+        let uniform = self
+            .uniforms
+            .get(name)
+            .ok_or_else(|| format!("ShaderProgram::set_uniform: No active uniform named '{}'", name))?;
+
+        self.write_uniform(uniform, value)
+    }
+
+    /// Like [`set_uniform`](Self::set_uniform), but does nothing if the uniform is not active,
+    /// for example because the compiler removed it as unused. A type mismatch is still an error.
+    pub fn set_uniform_if_active<V: UniformValue>(&self, name: &str, value: V) -> Result<(), Error> {
+        match self.uniforms.get(name) {
+            Some(uniform) => self.write_uniform(uniform, value),
+            None => Ok(()),
+        }
+    }
+
+    /// Sets a uniform of the crate's own shader code, which the compiler may have removed
+    /// as unused. A type mismatch is a bug in the crate.
+    pub(crate) fn set_builtin<V: UniformValue>(&self, name: &str, value: V) {
+        let result = self.set_uniform_if_active(name, value);
+        debug_assert!(result.is_ok(), "{:?}", result.err());
+    }
+
+    fn write_uniform<V: UniformValue>(&self, uniform: &VariableInfo, value: V) -> Result<(), Error> {
+        if !V::accepts(uniform.gl_type) {
+            return Err(format!(
+                "ShaderProgram::set_uniform: Uniform '{}' is a {}, but the value is a {}",
+                uniform.name, glsl_type_name(uniform.gl_type), V::GLSL_TYPE
+            ).into());
+        }
+
+        if value.count() > uniform.size as usize {
+            return Err(format!(
+                "ShaderProgram::set_uniform: Uniform '{}' has {} elements, but {} were given",
+                uniform.name, uniform.size, value.count()
+            ).into());
+        }
+
+        unsafe {
+            value.write(self.program, uniform.location);
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for ShaderProgram {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteProgram(self.program);
+        }
+    }
+}
+
+type GetActiveFn = unsafe fn(GLuint, GLuint, GLsizei, *mut GLsizei, *mut GLint, *mut GLenum, *mut GLchar);
+type GetLocationFn = unsafe fn(GLuint, *const GLchar) -> GLint;
+
+unsafe fn active_variables(
+    program: GLuint,
+    count_query: GLenum,
+    max_length_query: GLenum,
+    get_active: GetActiveFn,
+    get_location: GetLocationFn,
+) -> HashMap<String, VariableInfo> {
+    let mut variables = HashMap::new();
+
+    let mut count = 0;
+    let mut max_length = 0;
+    unsafe {
+        gl::GetProgramiv(program, count_query, &mut count);
+        gl::GetProgramiv(program, max_length_query, &mut max_length);
+    }
+
+    let mut name_buffer = vec![0u8; max_length.max(1) as usize];
+    for index in 0..count.max(0) as GLuint {
+        let mut length = 0;
+        let mut size = 0;
+        let mut gl_type = 0;
+        unsafe {
+            get_active(
+                program,
+                index,
+                name_buffer.len() as GLsizei,
+                &mut length,
+                &mut size,
+                &mut gl_type,
+                name_buffer.as_mut_ptr() as *mut GLchar,
+            );
+        }
+
+        let full_name = String::from_utf8_lossy(&name_buffer[..length.max(0) as usize]).into_owned();
+        if full_name.starts_with("gl_") {
+            continue;
+        }
+
+        let location = match CString::new(full_name.as_str()) {
+            Ok(c_name) => unsafe { get_location(program, c_name.as_ptr()) },
+            Err(_) => -1,
+        };
+
+        // Members of uniform blocks have no location.
+        if location < 0 {
+            continue;
+        }
+
+        let name = full_name.strip_suffix("[0]").unwrap_or(&full_name).to_string();
+        variables.insert(name.clone(), VariableInfo {
+            name,
+            location,
+            gl_type,
+            size,
+        });
+    }
+
+    variables
+}
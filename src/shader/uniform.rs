@@ -0,0 +1,161 @@
+use gl::types::*;
+use nalgebra::{Matrix3, Matrix4};
+
+/// A texture unit to assign to a sampler uniform, 0 for `GL_TEXTURE0` and so on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureUnit(pub GLint);
+
+/// A Rust value that can be written to a uniform of a matching GLSL type.
+///
+/// Arrays of uniforms are set from slices, starting at the first element.
+pub trait UniformValue {
+    /// The GLSL type the value is written as, for error messages.
+    const GLSL_TYPE: &'static str;
+
+    /// Whether the value can be written to a uniform of the given type, as reported by
+    /// `glGetActiveUniform`.
+    fn accepts(gl_type: GLenum) -> bool;
+
+    /// Number of array elements written.
+    fn count(&self) -> usize {
+        1
+    }
+
+    /// Writes the value.
+    ///
+    /// # Safety
+    ///
+    /// `location` must be the location of an active uniform of `program`
+    /// of an accepted type, with room for `count()` elements.
+    unsafe fn write(&self, program: GLuint, location: GLint);
+}
+
+macro_rules! impl_uniform_value {
+    ($ty:ty, $glsl:literal, [$($gl_type:path),+], |$self_:ident, $program:ident, $location:ident| $write:expr) => {
+        impl UniformValue for $ty {
+            const GLSL_TYPE: &'static str = $glsl;
+
+            fn accepts(gl_type: GLenum) -> bool {
+                matches!(gl_type, $($gl_type)|+)
+            }
+
+            unsafe fn write(&$self_, $program: GLuint, $location: GLint) {
+                unsafe { $write }
+            }
+        }
+    };
+}
+
+impl_uniform_value!(f32, "float", [gl::FLOAT], |self, p, l| gl::ProgramUniform1f(p, l, *self));
+impl_uniform_value!([f32; 2], "vec2", [gl::FLOAT_VEC2], |self, p, l| gl::ProgramUniform2fv(p, l, 1, self.as_ptr()));
+impl_uniform_value!([f32; 3], "vec3", [gl::FLOAT_VEC3], |self, p, l| gl::ProgramUniform3fv(p, l, 1, self.as_ptr()));
+impl_uniform_value!([f32; 4], "vec4", [gl::FLOAT_VEC4], |self, p, l| gl::ProgramUniform4fv(p, l, 1, self.as_ptr()));
+impl_uniform_value!(i32, "int", [gl::INT, gl::BOOL], |self, p, l| gl::ProgramUniform1i(p, l, *self));
+impl_uniform_value!([i32; 2], "ivec2", [gl::INT_VEC2], |self, p, l| gl::ProgramUniform2iv(p, l, 1, self.as_ptr()));
+impl_uniform_value!([i32; 3], "ivec3", [gl::INT_VEC3], |self, p, l| gl::ProgramUniform3iv(p, l, 1, self.as_ptr()));
+impl_uniform_value!([i32; 4], "ivec4", [gl::INT_VEC4], |self, p, l| gl::ProgramUniform4iv(p, l, 1, self.as_ptr()));
+impl_uniform_value!(u32, "uint", [gl::UNSIGNED_INT, gl::BOOL], |self, p, l| gl::ProgramUniform1ui(p, l, *self));
+impl_uniform_value!(bool, "bool", [gl::BOOL], |self, p, l| gl::ProgramUniform1i(p, l, *self as GLint));
+impl_uniform_value!(Matrix3<f32>, "mat3", [gl::FLOAT_MAT3], |self, p, l| gl::ProgramUniformMatrix3fv(p, l, 1, gl::FALSE, self.as_ptr()));
+impl_uniform_value!(Matrix4<f32>, "mat4", [gl::FLOAT_MAT4], |self, p, l| gl::ProgramUniformMatrix4fv(p, l, 1, gl::FALSE, self.as_ptr()));
+
+impl UniformValue for TextureUnit {
+    const GLSL_TYPE: &'static str = "sampler";
+
+    fn accepts(gl_type: GLenum) -> bool {
+        is_sampler(gl_type)
+    }
+
+    unsafe fn write(&self, program: GLuint, location: GLint) {
+        unsafe { gl::ProgramUniform1i(program, location, self.0) }
+    }
+}
+
+macro_rules! impl_uniform_slice {
+    ($ty:ty, $glsl:literal, $gl_type:path, |$self_:ident, $program:ident, $location:ident, $count:ident| $write:expr) => {
+        impl UniformValue for &[$ty] {
+            const GLSL_TYPE: &'static str = $glsl;
+
+            fn accepts(gl_type: GLenum) -> bool {
+                gl_type == $gl_type
+            }
+
+            fn count(&self) -> usize {
+                self.len()
+            }
+
+            unsafe fn write(&$self_, $program: GLuint, $location: GLint) {
+                let $count = $self_.len() as GLsizei;
+                unsafe { $write }
+            }
+        }
+    };
+}
+
+impl_uniform_slice!(f32, "float[]", gl::FLOAT, |self, p, l, n| gl::ProgramUniform1fv(p, l, n, self.as_ptr()));
+impl_uniform_slice!([f32; 2], "vec2[]", gl::FLOAT_VEC2, |self, p, l, n| gl::ProgramUniform2fv(p, l, n, self.as_ptr() as *const f32));
+impl_uniform_slice!([f32; 3], "vec3[]", gl::FLOAT_VEC3, |self, p, l, n| gl::ProgramUniform3fv(p, l, n, self.as_ptr() as *const f32));
+impl_uniform_slice!([f32; 4], "vec4[]", gl::FLOAT_VEC4, |self, p, l, n| gl::ProgramUniform4fv(p, l, n, self.as_ptr() as *const f32));
+impl_uniform_slice!(i32, "int[]", gl::INT, |self, p, l, n| gl::ProgramUniform1iv(p, l, n, self.as_ptr()));
+
+pub(super) fn is_sampler(gl_type: GLenum) -> bool {
+    matches!(
+        gl_type,
+        gl::SAMPLER_1D
+            | gl::SAMPLER_2D
+            | gl::SAMPLER_3D
+            | gl::SAMPLER_CUBE
+            | gl::SAMPLER_1D_SHADOW
+            | gl::SAMPLER_2D_SHADOW
+            | gl::SAMPLER_1D_ARRAY
+            | gl::SAMPLER_2D_ARRAY
+            | gl::SAMPLER_2D_ARRAY_SHADOW
+            | gl::SAMPLER_CUBE_SHADOW
+            | gl::SAMPLER_2D_MULTISAMPLE
+            | gl::SAMPLER_2D_MULTISAMPLE_ARRAY
+            | gl::SAMPLER_BUFFER
+            | gl::SAMPLER_2D_RECT
+            | gl::INT_SAMPLER_2D
+            | gl::INT_SAMPLER_3D
+            | gl::INT_SAMPLER_2D_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_2D
+            | gl::UNSIGNED_INT_SAMPLER_3D
+            | gl::UNSIGNED_INT_SAMPLER_2D_ARRAY
+    )
+}
+
+/// The GLSL name of a type reported by `glGetActiveUniform` or `glGetActiveAttrib`.
+pub fn glsl_type_name(gl_type: GLenum) -> &'static str {
+    match gl_type {
+        gl::FLOAT => "float",
+        gl::FLOAT_VEC2 => "vec2",
+        gl::FLOAT_VEC3 => "vec3",
+        gl::FLOAT_VEC4 => "vec4",
+        gl::INT => "int",
+        gl::INT_VEC2 => "ivec2",
+        gl::INT_VEC3 => "ivec3",
+        gl::INT_VEC4 => "ivec4",
+        gl::UNSIGNED_INT => "uint",
+        gl::UNSIGNED_INT_VEC2 => "uvec2",
+        gl::UNSIGNED_INT_VEC3 => "uvec3",
+        gl::UNSIGNED_INT_VEC4 => "uvec4",
+        gl::BOOL => "bool",
+        gl::BOOL_VEC2 => "bvec2",
+        gl::BOOL_VEC3 => "bvec3",
+        gl::BOOL_VEC4 => "bvec4",
+        gl::FLOAT_MAT2 => "mat2",
+        gl::FLOAT_MAT3 => "mat3",
+        gl::FLOAT_MAT4 => "mat4",
+        gl::SAMPLER_1D => "sampler1D",
+        gl::SAMPLER_2D => "sampler2D",
+        gl::SAMPLER_3D => "sampler3D",
+        gl::SAMPLER_CUBE => "samplerCube",
+        gl::SAMPLER_2D_ARRAY => "sampler2DArray",
+        gl::SAMPLER_2D_SHADOW => "sampler2DShadow",
+        gl::SAMPLER_BUFFER => "samplerBuffer",
+        gl::INT_SAMPLER_2D => "isampler2D",
+        gl::UNSIGNED_INT_SAMPLER_2D => "usampler2D",
+        t if is_sampler(t) => "sampler",
+        _ => "unknown type",
+    }
+}
@@ -0,0 +1,243 @@
+use std::fmt;
+
+use serde::de::{self, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+
+use super::{AnimationTag, PlaybackMode, SpriteFrame, SpriteSheet};
+use crate::Error;
+
+#[derive(Deserialize)]
+struct AsepriteFile {
+    frames: AsepriteFrames,
+    meta: AsepriteMeta,
+}
+
+#[derive(Deserialize)]
+struct AsepriteFrame {
+    frame: AsepriteRect,
+
+    /// Milliseconds.
+    duration: f32,
+}
+
+#[derive(Deserialize)]
+struct AsepriteRect {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+}
+
+#[derive(Deserialize)]
+struct AsepriteMeta {
+    size: AsepriteSize,
+
+    #[serde(rename = "frameTags", default)]
+    frame_tags: Vec<AsepriteTag>,
+}
+
+#[derive(Deserialize)]
+struct AsepriteSize {
+    w: f32,
+    h: f32,
+}
+
+#[derive(Deserialize)]
+struct AsepriteTag {
+    name: String,
+    from: usize,
+    to: usize,
+
+    #[serde(default = "default_direction")]
+    direction: String,
+}
+
+fn default_direction() -> String {
+    "forward".to_string()
+}
+
+/// Aseprite exports frames either as an array or as an object keyed by file name.
+/// The object form relies on document order, so both are read as a list.
+struct AsepriteFrames(Vec<AsepriteFrame>);
+
+impl<'de> Deserialize<'de> for AsepriteFrames {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FramesVisitor;
+
+        impl<'de> Visitor<'de> for FramesVisitor {
+            type Value = AsepriteFrames;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an array or map of frames")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut frames = Vec::new();
+                while let Some(frame) = seq.next_element()? {
+                    frames.push(frame);
+                }
+                Ok(AsepriteFrames(frames))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut frames = Vec::new();
+                while let Some((_, frame)) = map.next_entry::<de::IgnoredAny, AsepriteFrame>()? {
+                    frames.push(frame);
+                }
+                Ok(AsepriteFrames(frames))
+            }
+        }
+
+        deserializer.deserialize_any(FramesVisitor)
+    }
+}
+
+pub(super) fn parse(json: &str) -> Result<SpriteSheet, Error> {
+    let file: AsepriteFile = serde_json::from_str(json)?;
+    let [w, h] = [file.meta.size.w, file.meta.size.h];
+    if w <= 0.0 || h <= 0.0 {
+        return Err("Aseprite sheet: meta.size must be positive".into());
+    }
+
+    let frames = file
+        .frames
+        .0
+        .iter()
+        .map(|frame| SpriteFrame {
+            source_rect: [frame.frame.x / w, frame.frame.y / h, frame.frame.w / w, frame.frame.h / h],
+            duration: frame.duration / 1000.0,
+        })
+        .collect();
+
+    let mut sheet = SpriteSheet::new(frames);
+    for tag in file.meta.frame_tags {
+        let (mode, reverse) = match tag.direction.as_str() {
+            "forward" => (PlaybackMode::Loop, false),
+            "reverse" => (PlaybackMode::Loop, true),
+            "pingpong" => (PlaybackMode::PingPong, false),
+            "pingpong_reverse" => (PlaybackMode::PingPong, true),
+            other => {
+                return Err(format!("Aseprite sheet: unknown direction '{}' in tag '{}'", other, tag.name).into());
+            }
+        };
+
+        sheet.add_tag(AnimationTag {
+            name: tag.name,
+            from: tag.from,
+            to: tag.to,
+            mode,
+            reverse,
+        })?;
+    }
+
+    Ok(sheet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = r#"{
+        "frames": {
+            "walk 0.aseprite": { "frame": { "x": 0, "y": 0, "w": 16, "h": 16 }, "rotated": false, "duration": 100 },
+            "walk 1.aseprite": { "frame": { "x": 16, "y": 0, "w": 16, "h": 16 }, "rotated": false, "duration": 250 },
+            "walk 2.aseprite": { "frame": { "x": 32, "y": 16, "w": 32, "h": 16 }, "rotated": false, "duration": 50 }
+        },
+        "meta": {
+            "app": "https://www.aseprite.org/",
+            "size": { "w": 64, "h": 32 },
+            "frameTags": [
+                { "name": "walk", "from": 0, "to": 1, "direction": "forward" },
+                { "name": "back", "from": 1, "to": 2, "direction": "reverse" },
+                { "name": "bob", "from": 0, "to": 2, "direction": "pingpong", "repeat": "3" },
+                { "name": "bob_back", "from": 0, "to": 2, "direction": "pingpong_reverse" },
+                { "name": "plain", "from": 2, "to": 2 }
+            ]
+        }
+    }"#;
+
+    const ARRAY: &str = r#"{
+        "frames": [
+            { "filename": "walk 0.aseprite", "frame": { "x": 0, "y": 0, "w": 16, "h": 16 }, "duration": 100 },
+            { "filename": "walk 1.aseprite", "frame": { "x": 16, "y": 0, "w": 16, "h": 16 }, "duration": 250 },
+            { "filename": "walk 2.aseprite", "frame": { "x": 32, "y": 16, "w": 32, "h": 16 }, "duration": 50 }
+        ],
+        "meta": { "size": { "w": 64, "h": 32 } }
+    }"#;
+
+    fn assert_frames(sheet: &SpriteSheet) {
+        let frames = sheet.frames();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].source_rect, [0.0, 0.0, 0.25, 0.5]);
+        assert_eq!(frames[1].source_rect, [0.25, 0.0, 0.25, 0.5]);
+        assert_eq!(frames[2].source_rect, [0.5, 0.5, 0.5, 0.5]);
+        assert_eq!(frames[0].duration, 0.1);
+        assert_eq!(frames[1].duration, 0.25);
+        assert_eq!(frames[2].duration, 0.05);
+    }
+
+    #[test]
+    fn parses_hash_frames_in_document_order() {
+        assert_frames(&parse(HASH).unwrap());
+    }
+
+    #[test]
+    fn parses_array_frames() {
+        let sheet = parse(ARRAY).unwrap();
+        assert_frames(&sheet);
+        assert_eq!(sheet.tags().count(), 0);
+    }
+
+    #[test]
+    fn parses_tags() {
+        let sheet = parse(HASH).unwrap();
+        let expect = |name: &str, from, to, mode, reverse| {
+            let tag = sheet.tag(name).unwrap();
+            assert_eq!((tag.from, tag.to, tag.mode, tag.reverse), (from, to, mode, reverse), "{}", name);
+        };
+
+        expect("walk", 0, 1, PlaybackMode::Loop, false);
+        expect("back", 1, 2, PlaybackMode::Loop, true);
+        expect("bob", 0, 2, PlaybackMode::PingPong, false);
+        expect("bob_back", 0, 2, PlaybackMode::PingPong, true);
+        expect("plain", 2, 2, PlaybackMode::Loop, false);
+    }
+
+    #[test]
+    fn rejects_unknown_direction() {
+        let json = ARRAY.replace(
+            r#""size": { "w": 64, "h": 32 }"#,
+            r#""size": { "w": 64, "h": 32 }, "frameTags": [{ "name": "t", "from": 0, "to": 1, "direction": "sideways" }]"#,
+        );
+        assert!(parse(&json).is_err());
+    }
+
+    #[test]
+    fn rejects_tag_out_of_range() {
+        let json = ARRAY.replace(
+            r#""size": { "w": 64, "h": 32 }"#,
+            r#""size": { "w": 64, "h": 32 }, "frameTags": [{ "name": "t", "from": 1, "to": 3 }]"#,
+        );
+        assert!(parse(&json).is_err());
+    }
+
+    #[test]
+    fn rejects_missing_fields() {
+        assert!(parse(&ARRAY.replace(r#", "duration": 250"#, "")).is_err());
+        assert!(parse(&ARRAY.replace(r#""x": 16, "#, "")).is_err());
+        assert!(parse(r#"{ "frames": [] }"#).is_err());
+        assert!(parse(r#"{ "meta": { "size": { "w": 1, "h": 1 } } }"#).is_err());
+        assert!(parse(r#"{ "frames": [], "meta": {} }"#).is_err());
+    }
+
+    #[test]
+    fn rejects_empty_size() {
+        assert!(parse(&ARRAY.replace(r#""w": 64"#, r#""w": 0"#)).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_frames() {
+        assert!(parse(r#"{ "frames": 3, "meta": { "size": { "w": 1, "h": 1 } } }"#).is_err());
+        assert!(parse("not json").is_err());
+    }
+}
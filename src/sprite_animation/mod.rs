@@ -0,0 +1,508 @@
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::renderers::sprite_batch::Sprite;
+use crate::renderers::texture_renderer::TextureRenderer;
+use crate::renderers::tilemap_renderer::TilesetLayout;
+use crate::texture_atlas::AtlasLayout;
+use crate::Error;
+
+mod aseprite;
+
+/// One frame of a sprite sheet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpriteFrame {
+    /// The region of the sheet texture, as `[u, v, width, height]` in texture coordinates.
+    pub source_rect: [f32; 4],
+
+    /// How long the frame is shown, in seconds.
+    pub duration: f32,
+}
+
+/// How an animation continues after its last frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlaybackMode {
+    /// Starts over from the first frame.
+    #[default]
+    Loop,
+
+    /// Runs backwards to the first frame, then forwards again.
+    PingPong,
+
+    /// Stops on the last frame.
+    OneShot,
+}
+
+/// A named range of frames within a sprite sheet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnimationTag {
+    pub name: String,
+
+    /// Index of the first frame.
+    pub from: usize,
+
+    /// Index of the last frame, inclusive.
+    pub to: usize,
+    pub mode: PlaybackMode,
+
+    /// Plays the range from `to` down to `from`.
+    pub reverse: bool,
+}
+
+/// The frames of a sprite sheet and the animations defined over them.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SpriteSheet {
+    frames: Vec<SpriteFrame>,
+    tags: BTreeMap<String, AnimationTag>,
+}
+
+impl SpriteSheet {
+    pub fn new(frames: Vec<SpriteFrame>) -> Self {
+        Self {
+            frames,
+            tags: BTreeMap::new(),
+        }
+    }
+
+    /// Uses every cell of a grid as a frame, in row-major order,
+    /// each shown for `frame_duration` seconds.
+    pub fn from_grid(layout: &TilesetLayout, frame_duration: f32) -> Self {
+        let cell_size = [
+            layout.tile_size[0] as f32 / layout.texture_size[0] as f32,
+            layout.tile_size[1] as f32 / layout.texture_size[1] as f32,
+        ];
+
+        let count = layout.tile_count[0] * layout.tile_count[1];
+        let frames = (0..count)
+            .map(|i| {
+                let tx = i % layout.tile_count[0];
+                let ty = i / layout.tile_count[0];
+                SpriteFrame {
+                    source_rect: [
+                        tx as f32 * cell_size[0],
+                        ty as f32 * cell_size[1],
+                        cell_size[0],
+                        cell_size[1],
+                    ],
+                    duration: frame_duration,
+                }
+            })
+            .collect();
+
+        Self::new(frames)
+    }
+
+    /// Uses the named atlas regions as frames, in the order given,
+    /// each shown for `frame_duration` seconds.
+    pub fn from_atlas(layout: &AtlasLayout, names: &[&str], frame_duration: f32) -> Result<Self, Error> {
+        let frames = names
+            .iter()
+            .map(|name| {
+                let source_rect = layout
+                    .uv_rect(name)
+                    .ok_or_else(|| format!("SpriteSheet::from_atlas: No region named '{}'", name))?;
+                Ok(SpriteFrame {
+                    source_rect,
+                    duration: frame_duration,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(Self::new(frames))
+    }
+
+    /// Reads a sheet exported by Aseprite as JSON, in either the array or the hash layout.
+    /// Frame durations and tags are taken from the file. The `repeat` field of tags is ignored.
+    pub fn from_aseprite_json(json: &str) -> Result<Self, Error> {
+        aseprite::parse(json)
+    }
+
+    pub fn frames(&self) -> &[SpriteFrame] {
+        &self.frames
+    }
+
+    pub fn frame(&self, index: usize) -> Option<&SpriteFrame> {
+        self.frames.get(index)
+    }
+
+    pub fn add_tag(&mut self, tag: AnimationTag) -> Result<(), Error> {
+        if tag.from > tag.to || tag.to >= self.frames.len() {
+            return Err(format!(
+                "SpriteSheet::add_tag({}): Frame range {}..={} is invalid for {} frames",
+                tag.name, tag.from, tag.to, self.frames.len()
+            ).into());
+        }
+
+        self.tags.insert(tag.name.clone(), tag);
+        Ok(())
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&AnimationTag> {
+        self.tags.get(name)
+    }
+
+    pub fn tags(&self) -> impl Iterator<Item = &AnimationTag> {
+        self.tags.values()
+    }
+}
+
+/// Plays frame sequences from a shared [`SpriteSheet`].
+///
+/// The animation only picks the frame; apply it to a [`TextureRenderer`] or a [`Sprite`]
+/// for drawing. Call [`update`](Self::update) every frame to advance it.
+#[derive(Debug, Clone)]
+pub struct AnimatedSprite {
+    sheet: Rc<SpriteSheet>,
+
+    // The frame range being played, inclusive.
+    from: usize,
+    to: usize,
+    mode: PlaybackMode,
+    reverse: bool,
+
+    /// Position within the range, counted in playing order.
+    position: usize,
+
+    /// +1 or -1, only ever -1 in ping-pong mode.
+    step: isize,
+    frame_time: f32,
+    speed: f32,
+    playing: bool,
+    finished: bool,
+}
+
+impl AnimatedSprite {
+    /// Starts looping over all frames of the sheet.
+    pub fn new(sheet: Rc<SpriteSheet>) -> Self {
+        let to = sheet.frames.len().saturating_sub(1);
+        Self {
+            sheet,
+            from: 0,
+            to,
+            mode: PlaybackMode::Loop,
+            reverse: false,
+            position: 0,
+            step: 1,
+            frame_time: 0.0,
+            speed: 1.0,
+            playing: true,
+            finished: false,
+        }
+    }
+
+    pub fn sheet(&self) -> &Rc<SpriteSheet> {
+        &self.sheet
+    }
+
+    /// Plays the named tag from its start, using the tag's own playback mode.
+    pub fn play(&mut self, tag: &str) -> Result<(), Error> {
+        let tag = self
+            .sheet
+            .tag(tag)
+            .ok_or_else(|| format!("AnimatedSprite::play: No tag named '{}'", tag))?;
+
+        let (from, to, mode, reverse) = (tag.from, tag.to, tag.mode, tag.reverse);
+        self.play_range(from, to, mode, reverse);
+        Ok(())
+    }
+
+    /// Plays a range of frames from its start. `to` is inclusive.
+    pub fn play_range(&mut self, from: usize, to: usize, mode: PlaybackMode, reverse: bool) {
+        let last = self.sheet.frames.len().saturating_sub(1);
+        self.to = to.min(last);
+        self.from = from.min(self.to);
+        self.mode = mode;
+        self.reverse = reverse;
+        self.restart();
+    }
+
+    /// Goes back to the first frame of the current range and resumes playing.
+    pub fn restart(&mut self) {
+        self.position = 0;
+        self.step = 1;
+        self.frame_time = 0.0;
+        self.playing = true;
+        self.finished = false;
+    }
+
+    pub fn set_mode(&mut self, mode: PlaybackMode) {
+        self.mode = mode;
+    }
+
+    /// Multiplies the frame durations' playback rate. 2.0 plays twice as fast.
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn resume(&mut self) {
+        if !self.finished {
+            self.playing = true;
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Whether a one-shot animation has reached its last frame.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Advances the animation by `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        if !self.playing {
+            return;
+        }
+
+        self.frame_time += dt * self.speed;
+        loop {
+            let duration = self.current_frame().map_or(0.0, |frame| frame.duration);
+            if duration <= 0.0 || self.frame_time < duration {
+                break;
+            }
+
+            self.frame_time -= duration;
+            if !self.advance() {
+                self.frame_time = 0.0;
+                self.playing = false;
+                self.finished = true;
+                break;
+            }
+        }
+    }
+
+    /// Moves to the next frame in playing order. Returns false if a one-shot animation is done.
+    fn advance(&mut self) -> bool {
+        let len = self.to - self.from + 1;
+        match self.mode {
+            PlaybackMode::Loop => {
+                self.position = (self.position + 1) % len;
+                true
+            }
+            PlaybackMode::OneShot => {
+                if self.position + 1 < len {
+                    self.position += 1;
+                    true
+                } else {
+                    false
+                }
+            }
+            PlaybackMode::PingPong => {
+                if len > 1 {
+                    let next = self.position as isize + self.step;
+                    if next < 0 || next >= len as isize {
+                        self.step = -self.step;
+                    }
+                    self.position = (self.position as isize + self.step) as usize;
+                }
+                true
+            }
+        }
+    }
+
+    /// Index of the current frame within the sheet.
+    pub fn current_frame_index(&self) -> usize {
+        if self.reverse {
+            self.to - self.position
+        } else {
+            self.from + self.position
+        }
+    }
+
+    pub fn current_frame(&self) -> Option<&SpriteFrame> {
+        self.sheet.frame(self.current_frame_index())
+    }
+
+    /// Texture coordinates of the current frame, or the whole texture if the sheet is empty.
+    pub fn source_rect(&self) -> [f32; 4] {
+        self.current_frame()
+            .map_or([0.0, 0.0, 1.0, 1.0], |frame| frame.source_rect)
+    }
+
+    /// Makes the renderer draw the current frame.
+    pub fn apply_to_renderer(&self, renderer: &mut TextureRenderer) {
+        renderer.set_source_rect(self.source_rect());
+    }
+
+    /// Makes the sprite show the current frame.
+    pub fn apply_to_sprite(&self, sprite: &mut Sprite) {
+        sprite.source_rect = self.source_rect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A sheet of `count` frames lasting a quarter second each, with a tag for every mode.
+    fn sheet(count: usize) -> Rc<SpriteSheet> {
+        let frames = (0..count)
+            .map(|i| SpriteFrame {
+                source_rect: [i as f32, 0.0, 1.0, 1.0],
+                duration: 0.25,
+            })
+            .collect();
+
+        let mut sheet = SpriteSheet::new(frames);
+        let last = count - 1;
+        for (name, from, mode, reverse) in [
+            ("loop", 0, PlaybackMode::Loop, false),
+            ("pingpong", 0, PlaybackMode::PingPong, false),
+            ("oneshot", 0, PlaybackMode::OneShot, false),
+            ("reverse", 1, PlaybackMode::Loop, true),
+        ] {
+            sheet
+                .add_tag(AnimationTag { name: name.to_string(), from, to: last, mode, reverse })
+                .unwrap();
+        }
+        Rc::new(sheet)
+    }
+
+    /// The frame indices seen after each of `steps` updates of one frame duration.
+    fn play(animation: &mut AnimatedSprite, steps: usize) -> Vec<usize> {
+        (0..steps)
+            .map(|_| {
+                animation.update(0.25);
+                animation.current_frame_index()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn loop_wraps_around() {
+        let mut animation = AnimatedSprite::new(sheet(4));
+        assert_eq!(animation.current_frame_index(), 0);
+        assert_eq!(play(&mut animation, 6), [1, 2, 3, 0, 1, 2]);
+        assert!(animation.is_playing());
+    }
+
+    #[test]
+    fn stays_on_frame_until_duration_passes() {
+        let mut animation = AnimatedSprite::new(sheet(4));
+        animation.update(0.125);
+        assert_eq!(animation.current_frame_index(), 0);
+        animation.update(0.125);
+        assert_eq!(animation.current_frame_index(), 1);
+    }
+
+    #[test]
+    fn large_dt_spans_several_frames() {
+        let mut animation = AnimatedSprite::new(sheet(4));
+        animation.update(2.5 + 0.125);
+        assert_eq!(animation.current_frame_index(), 2);
+
+        // The leftover eighth of a second carries over.
+        animation.update(0.125);
+        assert_eq!(animation.current_frame_index(), 3);
+    }
+
+    #[test]
+    fn ping_pong_bounces_without_repeating_ends() {
+        let mut animation = AnimatedSprite::new(sheet(4));
+        animation.play("pingpong").unwrap();
+        assert_eq!(play(&mut animation, 8), [1, 2, 3, 2, 1, 0, 1, 2]);
+    }
+
+    #[test]
+    fn ping_pong_with_single_frame_stays() {
+        let mut animation = AnimatedSprite::new(sheet(4));
+        animation.play_range(2, 2, PlaybackMode::PingPong, false);
+        assert_eq!(play(&mut animation, 3), [2, 2, 2]);
+    }
+
+    #[test]
+    fn one_shot_stops_on_last_frame() {
+        let mut animation = AnimatedSprite::new(sheet(4));
+        animation.play("oneshot").unwrap();
+        assert_eq!(play(&mut animation, 3), [1, 2, 3]);
+        assert!(!animation.is_finished());
+
+        animation.update(10.0);
+        assert_eq!(animation.current_frame_index(), 3);
+        assert!(animation.is_finished());
+        assert!(!animation.is_playing());
+
+        animation.resume();
+        assert!(!animation.is_playing());
+
+        animation.restart();
+        assert_eq!(animation.current_frame_index(), 0);
+        assert!(animation.is_playing());
+    }
+
+    #[test]
+    fn one_shot_finishes_within_one_large_update() {
+        let mut animation = AnimatedSprite::new(sheet(4));
+        animation.play_range(0, 3, PlaybackMode::OneShot, true);
+        animation.update(100.0);
+        assert_eq!(animation.current_frame_index(), 0);
+        assert!(animation.is_finished());
+    }
+
+    #[test]
+    fn reverse_plays_range_backwards() {
+        let mut animation = AnimatedSprite::new(sheet(4));
+        animation.play("reverse").unwrap();
+        assert_eq!(animation.current_frame_index(), 3);
+        assert_eq!(play(&mut animation, 4), [2, 1, 3, 2]);
+    }
+
+    #[test]
+    fn reverse_ping_pong() {
+        let mut animation = AnimatedSprite::new(sheet(3));
+        animation.play_range(0, 2, PlaybackMode::PingPong, true);
+        assert_eq!(animation.current_frame_index(), 2);
+        assert_eq!(play(&mut animation, 5), [1, 0, 1, 2, 1]);
+    }
+
+    #[test]
+    fn speed_and_pause() {
+        let mut animation = AnimatedSprite::new(sheet(4));
+        animation.set_speed(2.0);
+        animation.update(0.125);
+        assert_eq!(animation.current_frame_index(), 1);
+
+        animation.pause();
+        animation.update(10.0);
+        assert_eq!(animation.current_frame_index(), 1);
+
+        animation.resume();
+        animation.update(0.125);
+        assert_eq!(animation.current_frame_index(), 2);
+    }
+
+    #[test]
+    fn zero_duration_frames_do_not_hang() {
+        let frame = SpriteFrame { source_rect: [0.0, 0.0, 1.0, 1.0], duration: 0.0 };
+        let mut animation = AnimatedSprite::new(Rc::new(SpriteSheet::new(vec![frame; 3])));
+        animation.update(1.0);
+        assert_eq!(animation.current_frame_index(), 0);
+    }
+
+    #[test]
+    fn empty_sheet_shows_whole_texture() {
+        let mut animation = AnimatedSprite::new(Rc::new(SpriteSheet::default()));
+        animation.update(1.0);
+        assert_eq!(animation.source_rect(), [0.0, 0.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn play_unknown_tag_fails() {
+        let mut animation = AnimatedSprite::new(sheet(2));
+        assert!(animation.play("missing").is_err());
+    }
+
+    #[test]
+    fn add_tag_checks_range() {
+        let mut sheet = SpriteSheet::new(vec![SpriteFrame { source_rect: [0.0; 4], duration: 1.0 }; 2]);
+        let tag = |from, to| AnimationTag { name: "t".to_string(), from, to, mode: PlaybackMode::Loop, reverse: false };
+        assert!(sheet.add_tag(tag(0, 2)).is_err());
+        assert!(sheet.add_tag(tag(1, 0)).is_err());
+        assert!(sheet.add_tag(tag(1, 1)).is_ok());
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::BTreeMap;
+
+use gl::types::*;
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+mod packer;
+
+/// A named rectangle within an atlas texture, in pixels.
+/// `y` is measured from the first row of the texture data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AtlasRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl AtlasRegion {
+    /// The region as `[u, v, width, height]` in texture coordinates, the form taken by
+    /// [`TextureRenderer::set_source_rect`](crate::renderers::texture_renderer::TextureRenderer::set_source_rect)
+    /// and [`Sprite::source_rect`](crate::renderers::sprite_batch::Sprite::source_rect).
+    pub fn uv_rect(&self, atlas_size: [u32; 2]) -> [f32; 4] {
+        let w = atlas_size[0] as f32;
+        let h = atlas_size[1] as f32;
+        [
+            self.x as f32 / w,
+            self.y as f32 / h,
+            self.width as f32 / w,
+            self.height as f32 / h,
+        ]
+    }
+}
+
+/// The size of an atlas and where each image ended up in it.
+/// This is the metadata exported to and imported from JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct AtlasLayout {
+    pub size: [u32; 2],
+    pub regions: BTreeMap<String, AtlasRegion>,
+}
+
+impl AtlasLayout {
+    pub fn get(&self, name: &str) -> Option<&AtlasRegion> {
+        self.regions.get(name)
+    }
+
+    /// The texture coordinates of the named region, see [`AtlasRegion::uv_rect`].
+    pub fn uv_rect(&self, name: &str) -> Option<[f32; 4]> {
+        self.get(name).map(|region| region.uv_rect(self.size))
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+struct AtlasImage {
+    name: String,
+    size: [u32; 2],
+    pixels: Vec<u8>,
+}
+
+/// Collects RGBA images and packs them into a single atlas.
+pub struct AtlasBuilder {
+    max_size: u32,
+    padding: u32,
+    images: Vec<AtlasImage>,
+}
+
+impl AtlasBuilder {
+    /// `max_size` is the largest width or height the atlas may grow to.
+    pub fn new(max_size: u32) -> Self {
+        Self {
+            max_size,
+            padding: 1,
+            images: Vec::new(),
+        }
+    }
+
+    /// Sets the number of pixels around each image, filled by repeating the image's edge
+    /// pixels so that filtering doesn't bleed neighbouring images in. Defaults to 1.
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Adds an image with 4 bytes per pixel, rows ordered first to last.
+    pub fn add_image<S: Into<String>>(&mut self, name: S, size: [u32; 2], pixels: &[u8]) -> Result<(), Error> {
+        let name = name.into();
+        let expected = size[0] as usize * size[1] as usize * 4;
+        if pixels.len() != expected {
+            return Err(format!(
+                "AtlasBuilder::add_image({}): Data length does not match size: expected {}, got {}",
+                name, expected, pixels.len()
+            ).into());
+        }
+
+        if size[0] == 0 || size[1] == 0 {
+            return Err(format!("AtlasBuilder::add_image({}): Image is empty", name).into());
+        }
+
+        if self.images.iter().any(|image| image.name == name) {
+            return Err(format!("AtlasBuilder::add_image({}): Duplicate image name", name).into());
+        }
+
+        self.images.push(AtlasImage {
+            name,
+            size,
+            pixels: pixels.to_vec(),
+        });
+
+        Ok(())
+    }
+
+    /// Packs the images without producing any pixel data.
+    pub fn pack_layout(&self) -> Result<AtlasLayout, Error> {
+        let padded_sizes: Vec<[u32; 2]> = self
+            .images
+            .iter()
+            .map(|image| [image.size[0] + 2 * self.padding, image.size[1] + 2 * self.padding])
+            .collect();
+
+        let (size, positions) = packer::pack_rects(&padded_sizes, self.max_size).ok_or_else(|| {
+            format!("AtlasBuilder: Images do not fit in a {0}x{0} atlas", self.max_size)
+        })?;
+
+        let regions = self
+            .images
+            .iter()
+            .zip(positions)
+            .map(|(image, pos)| {
+                let region = AtlasRegion {
+                    x: pos[0] + self.padding,
+                    y: pos[1] + self.padding,
+                    width: image.size[0],
+                    height: image.size[1],
+                };
+                (image.name.clone(), region)
+            })
+            .collect();
+
+        Ok(AtlasLayout { size, regions })
+    }
+
+    /// Packs the images and copies them into a single RGBA pixel buffer.
+    pub fn build_pixels(&self) -> Result<(AtlasLayout, Vec<u8>), Error> {
+        let layout = self.pack_layout()?;
+        let atlas_width = layout.size[0] as usize;
+        let mut pixels = vec![0u8; atlas_width * layout.size[1] as usize * 4];
+
+        let padding = self.padding as i64;
+        for image in &self.images {
+            let region = layout.regions[&image.name];
+            let [w, h] = [image.size[0] as i64, image.size[1] as i64];
+
+            for py in -padding..h + padding {
+                let sy = py.clamp(0, h - 1) as usize;
+                let dy = (region.y as i64 + py) as usize;
+                for px in -padding..w + padding {
+                    let sx = px.clamp(0, w - 1) as usize;
+                    let dx = (region.x as i64 + px) as usize;
+
+                    let src = (sy * image.size[0] as usize + sx) * 4;
+                    let dst = (dy * atlas_width + dx) * 4;
+                    pixels[dst..dst + 4].copy_from_slice(&image.pixels[src..src + 4]);
+                }
+            }
+        }
+
+        Ok((layout, pixels))
+    }
+
+    /// Packs the images and uploads the result to a new texture.
+    pub fn build(&self) -> Result<TextureAtlas, Error> {
+        let (layout, pixels) = self.build_pixels()?;
+        let size = [layout.size[0] as i32, layout.size[1] as i32];
+        let texture = glh::create_texture_2d_rgba(size, &pixels)?;
+        Ok(TextureAtlas::from_parts(texture, layout))
+    }
+}
+
+/// An atlas texture together with its layout. Deletes the texture on drop.
+pub struct TextureAtlas {
+    texture: GLuint,
+    layout: AtlasLayout,
+}
+
+impl TextureAtlas {
+    /// Takes ownership of an existing texture, for example one loaded from an image
+    /// exported alongside a layout read with [`AtlasLayout::from_json`].
+    pub fn from_parts(texture: GLuint, layout: AtlasLayout) -> Self {
+        Self { texture, layout }
+    }
+
+    pub fn texture(&self) -> GLuint {
+        self.texture
+    }
+
+    pub fn layout(&self) -> &AtlasLayout {
+        &self.layout
+    }
+
+    pub fn size(&self) -> [u32; 2] {
+        self.layout.size
+    }
+
+    pub fn region(&self, name: &str) -> Option<&AtlasRegion> {
+        self.layout.get(name)
+    }
+
+    /// The texture coordinates of the named region, see [`AtlasRegion::uv_rect`].
+    pub fn uv_rect(&self, name: &str) -> Option<[f32; 4]> {
+        self.layout.uv_rect(name)
+    }
+}
+
+impl Drop for TextureAtlas {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.texture);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(pixels: &[u8], atlas_width: u32, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * atlas_width + x) * 4) as usize;
+        pixels[i..i + 4].try_into().unwrap()
+    }
+
+    /// A 2x2 image with a distinct color in each pixel.
+    fn quad_image() -> Vec<u8> {
+        vec![
+            1, 0, 0, 255, 2, 0, 0, 255,
+            3, 0, 0, 255, 4, 0, 0, 255,
+        ]
+    }
+
+    #[test]
+    fn build_pixels_copies_image() {
+        let mut builder = AtlasBuilder::new(64).with_padding(0);
+        builder.add_image("quad", [2, 2], &quad_image()).unwrap();
+        let (layout, pixels) = builder.build_pixels().unwrap();
+
+        let region = layout.regions["quad"];
+        let width = layout.size[0];
+        assert_eq!(pixel(&pixels, width, region.x, region.y), [1, 0, 0, 255]);
+        assert_eq!(pixel(&pixels, width, region.x + 1, region.y), [2, 0, 0, 255]);
+        assert_eq!(pixel(&pixels, width, region.x, region.y + 1), [3, 0, 0, 255]);
+        assert_eq!(pixel(&pixels, width, region.x + 1, region.y + 1), [4, 0, 0, 255]);
+    }
+
+    #[test]
+    fn build_pixels_repeats_edges_into_padding() {
+        let mut builder = AtlasBuilder::new(64).with_padding(2);
+        builder.add_image("quad", [2, 2], &quad_image()).unwrap();
+        let (layout, pixels) = builder.build_pixels().unwrap();
+
+        let region = layout.regions["quad"];
+        assert!(region.x >= 2 && region.y >= 2);
+        let width = layout.size[0];
+        let at = |dx: i32, dy: i32| {
+            pixel(&pixels, width, (region.x as i32 + dx) as u32, (region.y as i32 + dy) as u32)
+        };
+
+        // Corners extend diagonally, edges extend straight out.
+        assert_eq!(at(-2, -2), [1, 0, 0, 255]);
+        assert_eq!(at(-1, 0), [1, 0, 0, 255]);
+        assert_eq!(at(3, 0), [2, 0, 0, 255]);
+        assert_eq!(at(0, 3), [3, 0, 0, 255]);
+        assert_eq!(at(3, 3), [4, 0, 0, 255]);
+        assert_eq!(at(1, -2), [2, 0, 0, 255]);
+    }
+
+    #[test]
+    fn padding_keeps_images_apart() {
+        let mut builder = AtlasBuilder::new(64).with_padding(1);
+        builder.add_image("a", [4, 4], &[10; 64]).unwrap();
+        builder.add_image("b", [4, 4], &[20; 64]).unwrap();
+        let (layout, pixels) = builder.build_pixels().unwrap();
+
+        for (name, value) in [("a", 10), ("b", 20)] {
+            let region = layout.regions[name];
+            for y in region.y - 1..region.y + region.height + 1 {
+                for x in region.x - 1..region.x + region.width + 1 {
+                    assert_eq!(pixel(&pixels, layout.size[0], x, y), [value; 4], "{} at {}, {}", name, x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pack_layout_fails_when_padding_does_not_fit() {
+        let mut builder = AtlasBuilder::new(8).with_padding(1);
+        builder.add_image("full", [8, 8], &[0; 256]).unwrap();
+        assert!(builder.pack_layout().is_err());
+        assert!(builder.with_padding(0).pack_layout().is_ok());
+    }
+}
@@ -0,0 +1,225 @@
+/// Bottom-left skyline rectangle packer.
+///
+/// The skyline is the upper outline of everything packed so far, stored as
+/// horizontal segments that together cover the full width of the area.
+/// "Bottom" here is the first row of the atlas, so rectangles are packed
+/// towards small `y` values.
+pub(crate) struct SkylinePacker {
+    width: u32,
+    height: u32,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    x: u32,
+    y: u32,
+    width: u32,
+}
+
+impl SkylinePacker {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            segments: vec![Segment { x: 0, y: 0, width }],
+        }
+    }
+
+    /// Finds room for a rectangle and returns its position,
+    /// or `None` if it doesn't fit anywhere.
+    pub fn insert(&mut self, size: [u32; 2]) -> Option<[u32; 2]> {
+        let [w, h] = size;
+
+        // (top edge of the placed rectangle, width of the segment, segment index, y)
+        let mut best: Option<(u32, u32, usize, u32)> = None;
+        for index in 0..self.segments.len() {
+            if let Some(y) = self.fit(index, w, h) {
+                let candidate = (y + h, self.segments[index].width, index, y);
+                if best.is_none_or(|b| (candidate.0, candidate.1) < (b.0, b.1)) {
+                    best = Some(candidate);
+                }
+            }
+        }
+
+        let (_, _, index, y) = best?;
+        let x = self.segments[index].x;
+        self.add_segment(index, Segment { x, y: y + h, width: w });
+
+        Some([x, y])
+    }
+
+    /// The lowest `y` at which a rectangle starting at segment `index` fits.
+    fn fit(&self, index: usize, w: u32, h: u32) -> Option<u32> {
+        let x = self.segments[index].x;
+        if x + w > self.width {
+            return None;
+        }
+
+        let mut y = 0;
+        let mut remaining = w as i64;
+        let mut i = index;
+        while remaining > 0 {
+            let segment = self.segments.get(i)?;
+            y = y.max(segment.y);
+            if y + h > self.height {
+                return None;
+            }
+            remaining -= segment.width as i64;
+            i += 1;
+        }
+
+        Some(y)
+    }
+
+    fn add_segment(&mut self, index: usize, segment: Segment) {
+        self.segments.insert(index, segment);
+
+        // Trim or remove the segments now covered by the new one.
+        let i = index + 1;
+        while i < self.segments.len() {
+            let prev_end = self.segments[i - 1].x + self.segments[i - 1].width;
+            let current = &mut self.segments[i];
+            if current.x >= prev_end {
+                break;
+            }
+
+            let overlap = prev_end - current.x;
+            if current.width <= overlap {
+                self.segments.remove(i);
+            } else {
+                current.x += overlap;
+                current.width -= overlap;
+                break;
+            }
+        }
+
+        // Merge neighbours at the same height.
+        let mut i = 0;
+        while i + 1 < self.segments.len() {
+            if self.segments[i].y == self.segments[i + 1].y {
+                self.segments[i].width += self.segments[i + 1].width;
+                self.segments.remove(i + 1);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Packs rectangles into the smallest power-of-two sized area found,
+/// no larger than `max_size` on either side. Sides that would grow past `max_size`
+/// are clamped to it, so a limit that isn't a power of two can still be used in full.
+/// Returns the chosen size and the position of each rectangle, in input order.
+pub(crate) fn pack_rects(sizes: &[[u32; 2]], max_size: u32) -> Option<([u32; 2], Vec<[u32; 2]>)> {
+    let area: u64 = sizes.iter().map(|s| s[0] as u64 * s[1] as u64).sum();
+    let widest = sizes.iter().map(|s| s[0]).max().unwrap_or(1);
+    let tallest = sizes.iter().map(|s| s[1]).max().unwrap_or(1);
+
+    // Pack tall rectangles first; it gives a much flatter skyline.
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by(|&a, &b| {
+        sizes[b][1].cmp(&sizes[a][1]).then(sizes[b][0].cmp(&sizes[a][0]))
+    });
+
+    let mut size = [widest.max(1).next_power_of_two(), tallest.max(1).next_power_of_two()];
+    while (size[0] as u64 * size[1] as u64) < area {
+        grow(&mut size);
+    }
+
+    loop {
+        let attempt = [size[0].min(max_size), size[1].min(max_size)];
+        if let Some(positions) = pack_into(sizes, &order, attempt) {
+            return Some((attempt, positions));
+        }
+
+        if attempt == [max_size, max_size] {
+            return None;
+        }
+
+        grow(&mut size);
+    }
+}
+
+/// Packs the rectangles in the given order into an area of exactly `size`.
+fn pack_into(sizes: &[[u32; 2]], order: &[usize], size: [u32; 2]) -> Option<Vec<[u32; 2]>> {
+    let mut packer = SkylinePacker::new(size[0], size[1]);
+    let mut positions = vec![[0, 0]; sizes.len()];
+    for &i in order {
+        positions[i] = packer.insert(sizes[i])?;
+    }
+    Some(positions)
+}
+
+fn grow(size: &mut [u32; 2]) {
+    if size[0] <= size[1] {
+        size[0] = size[0].saturating_mul(2);
+    } else {
+        size[1] = size[1].saturating_mul(2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overlaps(a: ([u32; 2], [u32; 2]), b: ([u32; 2], [u32; 2])) -> bool {
+        let ((ap, asz), (bp, bsz)) = (a, b);
+        ap[0] < bp[0] + bsz[0] && bp[0] < ap[0] + asz[0] && ap[1] < bp[1] + bsz[1] && bp[1] < ap[1] + asz[1]
+    }
+
+    fn assert_valid(sizes: &[[u32; 2]], max_size: u32) -> [u32; 2] {
+        let (size, positions) = pack_rects(sizes, max_size).expect("rectangles should fit");
+        assert!(size[0] <= max_size && size[1] <= max_size, "{:?} exceeds {}", size, max_size);
+        assert_eq!(positions.len(), sizes.len());
+
+        for (i, (pos, rect)) in positions.iter().zip(sizes).enumerate() {
+            assert!(pos[0] + rect[0] <= size[0] && pos[1] + rect[1] <= size[1], "rect {} leaves the atlas", i);
+            for j in 0..i {
+                assert!(!overlaps((*pos, *rect), (positions[j], sizes[j])), "rects {} and {} overlap", j, i);
+            }
+        }
+
+        size
+    }
+
+    #[test]
+    fn packs_without_overlaps() {
+        let sizes: Vec<[u32; 2]> = (0..40).map(|i| [3 + (i * 7) % 13, 2 + (i * 5) % 11]).collect();
+        assert_valid(&sizes, 256);
+    }
+
+    #[test]
+    fn packs_equal_squares_tightly() {
+        let size = assert_valid(&[[16, 16]; 16], 1024);
+        assert_eq!(size, [64, 64]);
+    }
+
+    #[test]
+    fn single_rect_gets_power_of_two_size() {
+        assert_eq!(assert_valid(&[[30, 5]], 1024), [32, 8]);
+    }
+
+    #[test]
+    fn uses_full_max_size_when_not_a_power_of_two() {
+        // Needs a 100 pixel wide area, which no power of two up to 100 provides.
+        let size = assert_valid(&[[100, 10]], 100);
+        assert_eq!(size[0], 100);
+
+        let sizes = [[50, 50]; 4];
+        assert_eq!(assert_valid(&sizes, 100), [100, 100]);
+    }
+
+    #[test]
+    fn returns_none_when_too_large() {
+        assert!(pack_rects(&[[65, 10]], 64).is_none());
+        assert!(pack_rects(&[[10, 65]], 64).is_none());
+        assert!(pack_rects(&[[40, 40]; 4], 64).is_none());
+    }
+
+    #[test]
+    fn empty_input_fits() {
+        let (_, positions) = pack_rects(&[], 16).unwrap();
+        assert!(positions.is_empty());
+    }
+}